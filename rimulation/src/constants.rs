@@ -0,0 +1,4 @@
+//! Physical constants shared by the simulation and the formats
+
+/// Standard gravity \[m/s^2\]
+pub const G: f64 = 9.80665;
@@ -0,0 +1,27 @@
+use anyhow::Error;
+
+use crate::scalar::Scalar;
+
+/// Material properties of the fluid flowing through the network
+///
+/// Temperatures T are in \[°C\] and energy densities e in \[GJ/m^3\].
+/// All properties are generic over the [`Scalar`] type to propagate derivatives.
+pub trait FluidProperties {
+    /// Computes the density rho \[kg/m^3\] based on the temperature T \[°C\]
+    fn density<T: Scalar>(&self, t: T) -> T;
+
+    /// Computes the specific heat capacity c_p \[J/(kg K)\] based on the temperature T \[°C\]
+    fn specific_heat<T: Scalar>(&self, t: T) -> T;
+
+    /// Computes the kinematic viscosity nu \[m^2/s\] based on the energy density e \[GJ/m^3\]
+    fn viscosity<T: Scalar>(&self, e: T) -> T;
+
+    /// Computes the thermal conductivity k \[W/(m K)\] based on the temperature T \[°C\]
+    fn thermal_conductivity<T: Scalar>(&self, t: T) -> T;
+
+    /// Computes the energy density e \[GJ/m^3\] based on the temperature T \[°C\]
+    fn energy_density<T: Scalar>(&self, t: T) -> Result<T, Error>;
+
+    /// Computes the temperature T \[°C\] based on the energy density e \[GJ/m^3\]
+    fn temperature<T: Scalar>(&self, e: T) -> T;
+}
@@ -0,0 +1,441 @@
+use std::f64::consts::LN_10;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    scalar::Scalar,
+    transition::{Blend, Transition},
+};
+
+/// Darcy friction factor of fully turbulent flow through a pipe
+///
+/// Reynolds numbers `re` are based on the diameter of the pipe,
+/// the relative roughness is the roughness of the pipe wall divided by the diameter.
+pub trait FrictionModel {
+    /// Computes the Darcy friction factor of turbulent flow
+    fn turbulent<T: Scalar>(&self, re: T, relative_roughness: T) -> T;
+
+    /// Computes the derivative of [`FrictionModel::turbulent`] with respect to the Reynolds number
+    fn turbulent_derivative<T: Scalar>(&self, re: T, relative_roughness: T) -> T;
+}
+
+/// Derivative of `-2 log10(summand + factor / re)` with respect to `re`,
+/// where `factor` depends on `re` with derivative `dfactor`
+fn log_term_derivative<T: Scalar>(summand: T, factor: T, dfactor: T, re: T) -> T {
+    (dfactor / re - factor / (re * re)) / ((summand + factor / re) * LN_10) * -2.
+}
+
+/// Solves the implicit Colebrook-White equation
+/// `1/sqrt(f) = -2 log10(k/3.7 + 2.51/(Re sqrt(f)))` by fixed-point iteration
+///
+/// This is the reference the explicit approximations are compared with.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Colebrook {
+    /// Stop once `1/sqrt(f)` changes by less than this
+    pub tolerance: f64,
+    pub max_iterations: usize,
+}
+
+impl Default for Colebrook {
+    fn default() -> Self {
+        Self {
+            tolerance: 1e-12,
+            max_iterations: 50,
+        }
+    }
+}
+
+impl Colebrook {
+    /// Residual `x + 2 log10(k/3.7 + 2.51 x / Re)` of the equation in `x = 1/sqrt(f)`
+    fn residual<T: Scalar>(x: T, re: T, relative_roughness: T) -> T {
+        (relative_roughness / 3.7 + x * 2.51 / re).log10() * 2. + x
+    }
+}
+
+impl FrictionModel for Colebrook {
+    fn turbulent<T: Scalar>(&self, re: T, relative_roughness: T) -> T {
+        let (re_value, summand) = (re.value(), relative_roughness.value() / 3.7);
+
+        // the iteration is a contraction, start close to the solution
+        let mut x = 1.
+            / SwameeJain
+                .turbulent(re_value, relative_roughness.value())
+                .sqrt();
+        for _ in 0..self.max_iterations {
+            let next = -2. * (summand + 2.51 * x / re_value).log10();
+            let converged = (next - x).abs() < self.tolerance;
+            x = next;
+
+            if converged {
+                break;
+            }
+        }
+
+        // a Newton step at the solution keeps its value,
+        // but carries the derivatives of the implicit function
+        let dfdx = 1. + 2. * 2.51 / (re_value * LN_10 * (summand + 2.51 * x / re_value));
+        let x = T::from_f64(x) - Self::residual(T::from_f64(x), re, relative_roughness) / dfdx;
+
+        (x * x).recip()
+    }
+
+    fn turbulent_derivative<T: Scalar>(&self, re: T, relative_roughness: T) -> T {
+        let summand = relative_roughness / 3.7;
+        let x = self.turbulent(re, relative_roughness).sqrt().recip();
+
+        // implicit differentiation of F(x, Re) = x + 2 log10(summand + 2.51 x / Re) = 0
+        let inner = (summand + x * 2.51 / re) * LN_10;
+        let dfdx = (re * inner).recip() * (2. * 2.51) + 1.;
+        let dfdre = x / (re * re * inner) * (-2. * 2.51);
+        let dx = -dfdre / dfdx;
+
+        dx / (x * x * x) * -2.
+    }
+}
+
+/// Explicit approximation by Swamee and Jain (1976)
+///
+/// Within 3 % of Colebrook-White for relative roughnesses below 1e-2
+/// and Reynolds numbers in \[5e3, 1e8\].
+/// The largest deviations occur in smooth pipes at high Reynolds numbers.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct SwameeJain;
+
+impl FrictionModel for SwameeJain {
+    fn turbulent<T: Scalar>(&self, re: T, relative_roughness: T) -> T {
+        (relative_roughness / 3.7 + re.powf(0.9).recip() * 5.74)
+            .log10()
+            .powi(2)
+            .recip()
+            * 0.25
+    }
+
+    fn turbulent_derivative<T: Scalar>(&self, re: T, relative_roughness: T) -> T {
+        let u = relative_roughness / 3.7 + re.powf(0.9).recip() * 5.74;
+        let du = re.powf(1.9).recip() * (-0.9 * 5.74);
+
+        du / (u.log10().powi(3) * u * LN_10) * -0.5
+    }
+}
+
+/// Explicit approximation by Haaland (1983)
+///
+/// Within 1.5 % of Colebrook-White for Reynolds numbers in \[4e3, 1e8\].
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Haaland;
+
+impl FrictionModel for Haaland {
+    fn turbulent<T: Scalar>(&self, re: T, relative_roughness: T) -> T {
+        let x = ((relative_roughness / 3.7).powf(1.11) + re.recip() * 6.9).log10() * -1.8;
+
+        (x * x).recip()
+    }
+
+    fn turbulent_derivative<T: Scalar>(&self, re: T, relative_roughness: T) -> T {
+        let w = (relative_roughness / 3.7).powf(1.11) + re.recip() * 6.9;
+        let x = w.log10() * -1.8;
+        let dx = (w * re * re).recip() * (1.8 * 6.9 / LN_10);
+
+        dx / (x * x * x) * -2.
+    }
+}
+
+/// Explicit approximation by Serghides (1984), three steps of Steffensen's method
+/// applied to Colebrook-White
+///
+/// Within 0.01 % of Colebrook-White for Reynolds numbers above 4e3.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Serghides;
+
+impl Serghides {
+    fn steps<T: Scalar>(re: T, summand: T) -> (T, T, T) {
+        let a = (summand + re.recip() * 12.).log10() * -2.;
+        let b = (summand + a * 2.51 / re).log10() * -2.;
+        let c = (summand + b * 2.51 / re).log10() * -2.;
+
+        (a, b, c)
+    }
+}
+
+impl FrictionModel for Serghides {
+    fn turbulent<T: Scalar>(&self, re: T, relative_roughness: T) -> T {
+        let (a, b, c) = Self::steps(re, relative_roughness / 3.7);
+
+        (a - ((b - a).powi(2) / (c - b * 2. + a))).powi(2).recip()
+    }
+
+    fn turbulent_derivative<T: Scalar>(&self, re: T, relative_roughness: T) -> T {
+        let summand = relative_roughness / 3.7;
+        let (a, b, c) = Self::steps(re, summand);
+
+        let da = log_term_derivative(summand, T::from_f64(12.), T::zero(), re);
+        let db = log_term_derivative(summand, a * 2.51, da * 2.51, re);
+        let dc = log_term_derivative(summand, b * 2.51, db * 2.51, re);
+
+        let denominator = c - b * 2. + a;
+        let g = a - (b - a).powi(2) / denominator;
+        let dg = da
+            - ((b - a) * (db - da) * denominator * 2. - (b - a).powi(2) * (dc - db * 2. + da))
+                / (denominator * denominator);
+
+        dg / (g * g * g) * -2.
+    }
+}
+
+/// Friction models which can be chosen per network or per parameter set in the parameters
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Friction {
+    /// See [`Colebrook`], with the default tolerance
+    Colebrook,
+    /// See [`SwameeJain`]
+    SwameeJain,
+    /// See [`Haaland`]
+    Haaland,
+    /// See [`Serghides`]
+    #[default]
+    Serghides,
+}
+
+impl FrictionModel for Friction {
+    fn turbulent<T: Scalar>(&self, re: T, relative_roughness: T) -> T {
+        match self {
+            Friction::Colebrook => Colebrook::default().turbulent(re, relative_roughness),
+            Friction::SwameeJain => SwameeJain.turbulent(re, relative_roughness),
+            Friction::Haaland => Haaland.turbulent(re, relative_roughness),
+            Friction::Serghides => Serghides.turbulent(re, relative_roughness),
+        }
+    }
+
+    fn turbulent_derivative<T: Scalar>(&self, re: T, relative_roughness: T) -> T {
+        match self {
+            Friction::Colebrook => {
+                Colebrook::default().turbulent_derivative(re, relative_roughness)
+            }
+            Friction::SwameeJain => SwameeJain.turbulent_derivative(re, relative_roughness),
+            Friction::Haaland => Haaland.turbulent_derivative(re, relative_roughness),
+            Friction::Serghides => Serghides.turbulent_derivative(re, relative_roughness),
+        }
+    }
+}
+
+// Reynolds number transition boundaries for laminar to turbulent flow
+pub const LAMINAR_BOUNDARY: f64 = 2_000.;
+pub const TURBULENT_BOUNDARY: f64 = 4_000.;
+
+/// Cubic bridge between laminar and turbulent friction
+pub const TRANSITION: Transition =
+    Transition::new(LAMINAR_BOUNDARY, TURBULENT_BOUNDARY, Blend::Hermite);
+
+/// Friction factor of laminar flow `64/Re` and its derivative
+fn laminar<T: Scalar>(re: T) -> (T, T) {
+    (re.recip() * 64., (re * re).recip() * -64.)
+}
+
+/// Friction factor in the transition from laminar to turbulent flow and its derivative
+fn transition<T: Scalar>(model: &impl FrictionModel, re: T, relative_roughness: T) -> (T, T) {
+    TRANSITION.evaluate(re, laminar, |re| {
+        (
+            model.turbulent(re, relative_roughness),
+            model.turbulent_derivative(re, relative_roughness),
+        )
+    })
+}
+
+/// Calculates the Darcy friction factor using:
+/// - Laminar flow (Re < 2000): f = 64/Re
+/// - Turbulent flow (Re > 4000): the given friction model
+/// - Transition (2000 <= Re <= 4000): Cubic interpolation, see [`TRANSITION`]
+pub fn darcy_friction<T: Scalar>(model: &impl FrictionModel, re: T, relative_roughness: T) -> T {
+    if re.value() > TURBULENT_BOUNDARY {
+        model.turbulent(re, relative_roughness)
+    } else if re.value() < LAMINAR_BOUNDARY {
+        laminar(re).0
+    } else {
+        transition(model, re, relative_roughness).0
+    }
+}
+
+/// Calculates the derivative of [`darcy_friction`] with respect to the Reynolds number
+pub fn darcy_friction_derivative<T: Scalar>(
+    model: &impl FrictionModel,
+    re: T,
+    relative_roughness: T,
+) -> T {
+    if re.value() > TURBULENT_BOUNDARY {
+        model.turbulent_derivative(re, relative_roughness)
+    } else if re.value() < LAMINAR_BOUNDARY {
+        laminar(re).1
+    } else {
+        transition(model, re, relative_roughness).1
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::scalar::Dual;
+
+    /// Reynolds number, relative roughness and friction factor read from the Moody chart
+    const MOODY_CHART: [(f64, f64, f64); 11] = [
+        (4e3, 0., 0.0399),
+        (1e4, 0., 0.0309),
+        (1e5, 0., 0.0180),
+        (1e6, 0., 0.0116),
+        (1e7, 0., 0.0081),
+        (1e5, 1e-4, 0.0185),
+        (1e4, 1e-3, 0.0324),
+        (1e6, 1e-3, 0.0199),
+        (1e5, 1e-2, 0.0385),
+        (1e7, 1e-2, 0.0379),
+        (1e8, 5e-2, 0.0716),
+    ];
+
+    fn assert_close_to_colebrook(model: impl FrictionModel, max_relative: f64, res: &[f64]) {
+        for re in res {
+            for relative_roughness in [0., 1e-6, 1e-5, 1e-4, 1e-3, 1e-2] {
+                let expected = Colebrook::default().turbulent(*re, relative_roughness);
+                let f = model.turbulent(*re, relative_roughness);
+
+                assert!(
+                    ((f - expected) / expected).abs() < max_relative,
+                    "Re = {}, k/D = {}: expected {}, was {}",
+                    re,
+                    relative_roughness,
+                    expected,
+                    f
+                );
+            }
+        }
+    }
+
+    const RES: [f64; 6] = [5e3, 1e4, 1e5, 1e6, 1e7, 1e8];
+
+    #[test]
+    fn colebrook_matches_moody_chart() {
+        for (re, relative_roughness, expected) in MOODY_CHART {
+            let f = Colebrook::default().turbulent(re, relative_roughness);
+
+            assert!(
+                ((f - expected) / expected).abs() < 5e-3,
+                "Re = {}, k/D = {}: expected {}, was {}",
+                re,
+                relative_roughness,
+                expected,
+                f
+            );
+        }
+    }
+
+    #[test]
+    fn colebrook_solves_equation() {
+        for (re, relative_roughness, _) in MOODY_CHART {
+            let f = Colebrook::default().turbulent(re, relative_roughness);
+
+            let lhs = 1. / f.sqrt();
+            let rhs = -2. * (relative_roughness / 3.7 + 2.51 / (re * f.sqrt())).log10();
+            assert!((lhs - rhs).abs() < 1e-10);
+        }
+    }
+
+    #[test]
+    fn serghides_matches_colebrook() {
+        assert_close_to_colebrook(Serghides, 1e-4, &RES);
+    }
+
+    #[test]
+    fn swamee_jain_matches_colebrook() {
+        assert_close_to_colebrook(SwameeJain, 3e-2, &RES);
+    }
+
+    #[test]
+    fn haaland_matches_colebrook() {
+        assert_close_to_colebrook(Haaland, 1.5e-2, &RES);
+    }
+
+    #[test]
+    fn laminar_and_transition() {
+        for model in MODELS {
+            assert_eq!(darcy_friction(&model, 1_000., 1e-3), 0.064);
+            assert_eq!(darcy_friction(&model, LAMINAR_BOUNDARY, 1e-3), 0.032);
+            assert_relative_eq!(
+                darcy_friction(&model, TURBULENT_BOUNDARY, 1e-3),
+                model.turbulent(TURBULENT_BOUNDARY, 1e-3)
+            );
+        }
+    }
+
+    const MODELS: [Friction; 4] = [
+        Friction::Colebrook,
+        Friction::SwameeJain,
+        Friction::Haaland,
+        Friction::Serghides,
+    ];
+
+    #[test]
+    fn derivatives_match_finite_differences() {
+        for model in MODELS {
+            for re in [500., 2_500., 3_999., 5e3, 1e5, 1e7] {
+                for relative_roughness in [0., 1e-4, 1e-2] {
+                    let dre = re * 1e-6;
+                    let slope = (darcy_friction(&model, re + dre, relative_roughness)
+                        - darcy_friction(&model, re - dre, relative_roughness))
+                        / (2. * dre);
+                    let derivative = darcy_friction_derivative(&model, re, relative_roughness);
+
+                    assert_relative_eq!(derivative, slope, max_relative = 1e-5);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn dual_numbers_match_derivatives() {
+        for model in MODELS {
+            for (re, relative_roughness) in [(1e3, 1e-3), (3e3, 1e-3), (1e5, 0.), (1e6, 1e-3)] {
+                let f = darcy_friction(
+                    &model,
+                    Dual::variable(re),
+                    Dual::constant(relative_roughness),
+                );
+
+                assert_eq!(f.value, darcy_friction(&model, re, relative_roughness));
+                assert_relative_eq!(
+                    f.derivative,
+                    darcy_friction_derivative(&model, re, relative_roughness),
+                    max_relative = 1e-10
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sensitivity_to_roughness() {
+        for model in MODELS {
+            for re in [3e3, 1e5, 1e7] {
+                let relative_roughness = 1e-3;
+                let f = darcy_friction(
+                    &model,
+                    Dual::constant(re),
+                    Dual::variable(relative_roughness),
+                );
+
+                let dk = 1e-9;
+                let slope = (darcy_friction(&model, re, relative_roughness + dk)
+                    - darcy_friction(&model, re, relative_roughness - dk))
+                    / (2. * dk);
+
+                assert_relative_eq!(f.derivative, slope, max_relative = 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn parse_friction() {
+        let friction: Friction =
+            serde_json::from_str("\"swamee_jain\"").expect("could not parse friction model");
+        assert_eq!(friction, Friction::SwameeJain);
+    }
+}
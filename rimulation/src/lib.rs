@@ -1,5 +1,9 @@
+pub mod constants;
+pub mod fluid;
+pub mod friction;
 pub mod output;
 pub mod polynome;
+pub mod scalar;
 pub mod simulation;
 pub mod transition;
 pub mod types;
@@ -1,13 +1,27 @@
-use anyhow::Error;
-use clap::{Parser, Subcommand};
+use anyhow::{anyhow, Error};
+use clap::{Parser, Subcommand, ValueEnum};
 use rimulation::{
-    output::write_temperatures,
+    output::{
+        animation, read_temperatures, render, write_invalid_steps, write_temperatures,
+        TemperatureStatistics,
+    },
+    polynome::fit,
     simulation::simulate_delay,
     types::{
-        formats::custom::load,
-        network::{FixedVelocityPipeParameters, Network},
+        formats::{
+            custom::{
+                self, read_columns,
+                validation::{validate, Severity},
+                Column,
+            },
+            epanet,
+            geojson::{self, PropertyMapping},
+            modelica, proprietary, shapefile,
+        },
+        network::{EmptyPipeParameters, FixedVelocityPipeParameters, Network},
     },
 };
+use std::{collections::HashMap, path::Path};
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
@@ -16,18 +30,171 @@ struct Cli {
     command: Commands,
 }
 
+/// Format of the files describing a network
+#[derive(ValueEnum, Debug, Clone, Copy)]
+enum Format {
+    /// `topology.json`, `scenario.json` and `parameters.json`, or a single `.json`, `.yaml` or
+    /// `.toml` file bundling all three
+    Custom,
+    /// Vendor exports with a `main.json`
+    Proprietary,
+    /// EPANET input file (`.inp`), read with default temperatures and transmittance
+    Epanet,
+}
+
+impl Format {
+    fn load(&self, directory: &str) -> Result<custom::Network, Error> {
+        match self {
+            Format::Custom => custom::load(directory),
+            Format::Proprietary => proprietary::load(directory)?.try_into(),
+            Format::Epanet => Ok(epanet::load(directory, &Default::default())?.0),
+        }
+    }
+
+    /// Like [`Format::load`], but also describes the information lost by reading the network
+    /// into the custom format
+    fn load_lossy(&self, directory: &str) -> Result<(custom::Network, Vec<String>), Error> {
+        match self {
+            Format::Custom => Ok((custom::load(directory)?, vec![])),
+            Format::Proprietary => {
+                let network = proprietary::load(directory)?;
+                let losses = network.lossy_mappings();
+                Ok((network.try_into()?, losses))
+            }
+            Format::Epanet => epanet::load(directory, &Default::default()),
+        }
+    }
+
+    fn save(&self, network: &custom::Network, directory: &str) -> Result<(), Error> {
+        match self {
+            Format::Custom => custom::save(network, directory),
+            Format::Proprietary | Format::Epanet => {
+                Err(anyhow!("writing the {:?} format is not supported", self))
+            }
+        }
+    }
+}
+
 #[derive(Subcommand, Debug)]
 enum Commands {
-    Simulate { directory: String },
-    Recover { directory: String },
+    Simulate {
+        directory: String,
+        #[arg(long, value_enum, default_value_t = Format::Custom)]
+        format: Format,
+    },
+    /// Reports all problems of a network in the custom format
+    Validate { directory: String },
+    /// Converts a network between input formats and reports all information that is lost
+    Convert {
+        input: String,
+        output: String,
+        #[arg(long, value_enum)]
+        from: Format,
+        #[arg(long, value_enum, default_value_t = Format::Custom)]
+        to: Format,
+    },
+    /// Exports a network in the custom format as GeoJSON, e.g. for the review in QGIS
+    ExportGeojson {
+        directory: String,
+        output: String,
+        /// Result of a simulation to add the min, mean and max temperature of the nodes
+        #[arg(long)]
+        results: Option<String>,
+    },
+    /// Imports the topology and pipe parameters of a network in the custom format from GeoJSON
+    ImportGeojson {
+        input: String,
+        directory: String,
+        /// JSON file with the names of the properties that differ from the exported ones
+        #[arg(long)]
+        mapping: Option<String>,
+    },
+    /// Exports a network as Modelica model built from the Modelica Buildings library
+    ExportModelica {
+        directory: String,
+        output: String,
+        #[arg(long, value_enum, default_value_t = Format::Custom)]
+        format: Format,
+        /// Name of the model, which has to match the name of the `.mo` file
+        #[arg(long, default_value = "Network")]
+        name: String,
+        /// Factor converting the demands of consumers to W
+        #[arg(long, default_value_t = 1000.)]
+        demand_unit: f64,
+    },
+    /// Imports the pipes of a polyline shapefile and its attribute table into the custom format
+    ImportShapefile {
+        input: String,
+        directory: String,
+        /// JSON file with the attribute columns and the values derived from them
+        #[arg(long)]
+        options: Option<String>,
+    },
+    /// Draws the internal network with its spanning tree into `{output}.dot` and `{output}.svg`
+    Render {
+        directory: String,
+        output: String,
+        #[arg(long, value_enum, default_value_t = Format::Custom)]
+        format: Format,
+    },
+    /// Animates the temperatures of a simulation in a self-contained HTML page `{output}.html`
+    ///
+    /// Color limits and start date default to the `ui_parameters` of the proprietary format and
+    /// otherwise to the range of the results and hours since the start.
+    Animate {
+        directory: String,
+        output: String,
+        #[arg(long, value_enum, default_value_t = Format::Custom)]
+        format: Format,
+        /// Result of the simulation, by default the one written by `simulate`
+        #[arg(long)]
+        results: Option<String>,
+        /// Temperature at the blue end of the color scale
+        #[arg(long)]
+        min_temperature: Option<f64>,
+        /// Temperature at the red end of the color scale
+        #[arg(long)]
+        max_temperature: Option<f64>,
+        /// Date and time of the start of the simulation, e.g. "2023-04-22 08:00:00"
+        #[arg(long)]
+        start_date: Option<String>,
+    },
+    /// Fits a polynomial to two columns of a CSV table and prints its coefficients as constants
+    Fit {
+        table: String,
+        /// Header of the column with the argument of the polynomial
+        #[arg(long)]
+        x: String,
+        /// Header of the column with the values of the polynomial
+        #[arg(long)]
+        y: String,
+        #[arg(long)]
+        degree: usize,
+        /// Smallest argument to take into account
+        #[arg(long, default_value_t = f64::NEG_INFINITY)]
+        min: f64,
+        /// Largest argument to take into account
+        #[arg(long, default_value_t = f64::INFINITY)]
+        max: f64,
+        /// Prefix of the names of the constants
+        #[arg(long, default_value = "C")]
+        name: String,
+    },
 }
 
 fn main() -> Result<(), Error> {
     let cli = Cli::parse();
 
     match &cli.command {
-        Commands::Simulate { directory } => {
-            let network = load(directory)?;
+        Commands::Simulate { directory, format } => {
+            let network = format.load(directory)?;
+            for (name, signal) in &network.scenario.signals {
+                let num_gaps = signal.num_gaps();
+                if num_gaps > 0 {
+                    println!("filled {} gaps in signal '{}'", num_gaps, name);
+                }
+            }
+
             let settings = network.scenario.settings.clone();
             let network: Network<FixedVelocityPipeParameters> = network.try_into()?;
 
@@ -36,12 +203,245 @@ fn main() -> Result<(), Error> {
             write_temperatures(
                 &network,
                 &settings,
-                result,
-                format!("{}/result", directory).as_str(),
+                result.temperatures,
+                custom::output_path(directory, "result").as_str(),
             )?;
+
+            if !result.invalid.is_empty() {
+                println!(
+                    "{} results depend on invalid input data",
+                    result.invalid.len()
+                );
+                write_invalid_steps(
+                    &network,
+                    &result.invalid,
+                    custom::output_path(directory, "result_invalid").as_str(),
+                )?;
+            }
         }
-        Commands::Recover { directory } => {
-            todo!()
+        Commands::Validate { directory } => {
+            let problems = validate(directory);
+            for problem in &problems {
+                println!("{}", problem);
+            }
+
+            let num_errors = problems
+                .iter()
+                .filter(|problem| problem.severity == Severity::Error)
+                .count();
+            println!(
+                "{} errors, {} warnings",
+                num_errors,
+                problems.len() - num_errors
+            );
+            if num_errors > 0 {
+                return Err(anyhow!("network in '{}' is invalid", directory));
+            }
+        }
+        Commands::Convert {
+            input,
+            output,
+            from,
+            to,
+        } => {
+            let (network, losses) = from.load_lossy(input)?;
+            for loss in &losses {
+                println!("lossy mapping: {}", loss);
+            }
+
+            to.save(&network, output)?;
+            println!(
+                "converted {} nodes, {} pipes with {} parameter sets, {} consumers and {} sources",
+                network.topology.nodes.len(),
+                network.topology.pipes.len(),
+                network.parameters.parameters.len(),
+                network.topology.consumers.len(),
+                network.topology.sources.len(),
+            );
+        }
+        Commands::ExportGeojson {
+            directory,
+            output,
+            results,
+        } => {
+            let network = custom::load(directory)?;
+            let statistics: HashMap<_, _> = match results {
+                Some(results) => read_temperatures(results)?
+                    .into_iter()
+                    .filter_map(|(name, temperatures)| {
+                        TemperatureStatistics::of(&temperatures)
+                            .map(|statistics| (name, statistics))
+                    })
+                    .collect(),
+                None => HashMap::new(),
+            };
+
+            let collection = geojson::export(&network.topology, &network.parameters, &statistics)?;
+            geojson::save(&collection, output)?;
+            println!(
+                "exported {} features with temperatures of {} nodes",
+                collection.features.len(),
+                statistics.len()
+            );
+        }
+        Commands::ImportGeojson {
+            input,
+            directory,
+            mapping,
+        } => {
+            let mapping = match mapping {
+                Some(mapping) => PropertyMapping::load(mapping)?,
+                None => PropertyMapping::default(),
+            };
+            let (topology, parameters) = geojson::import(&geojson::load(input)?, &mapping)?;
+
+            custom::save_without_scenario(&topology, &parameters, directory)?;
+            println!(
+                "imported {} nodes, {} pipes with {} parameter sets, {} consumers and {} sources",
+                topology.nodes.len(),
+                topology.pipes.len(),
+                parameters.parameters.len(),
+                topology.consumers.len(),
+                topology.sources.len(),
+            );
+            println!("a scenario is needed before the network can be simulated");
+        }
+        Commands::ExportModelica {
+            directory,
+            output,
+            format,
+            name,
+            demand_unit,
+        } => {
+            let network = format.load(directory)?;
+            let (text, losses) = modelica::export(
+                &network,
+                &modelica::Options {
+                    model_name: name.clone(),
+                    demand_unit: *demand_unit,
+                    ..Default::default()
+                },
+            )?;
+            for loss in &losses {
+                println!("lossy mapping: {}", loss);
+            }
+
+            modelica::save(&text, output)?;
+        }
+        Commands::ImportShapefile {
+            input,
+            directory,
+            options,
+        } => {
+            let options = match options {
+                Some(options) => shapefile::Options::load(options)?,
+                None => shapefile::Options::default(),
+            };
+            let (topology, parameters, report) = shapefile::load(input, &options)?;
+            for line in &report {
+                println!("{}", line);
+            }
+
+            custom::save_without_scenario(&topology, &parameters, directory)?;
+            println!(
+                "imported {} nodes and {} pipes with {} parameter sets",
+                topology.nodes.len(),
+                topology.pipes.len(),
+                parameters.parameters.len(),
+            );
+            println!("a scenario is needed before the network can be simulated");
+        }
+        Commands::Render {
+            directory,
+            output,
+            format,
+        } => {
+            let network: Network<EmptyPipeParameters> = format.load(directory)?.try_into()?;
+
+            std::fs::write(format!("{}.dot", output), render::dot(&network))?;
+            std::fs::write(format!("{}.svg", output), render::svg(&network))?;
+            println!(
+                "drew {} nodes, {} spanning tree edges and {} cycle edges",
+                network.num_nodes(),
+                network.spanning_tree_edges.len(),
+                network.cycle_edges.len(),
+            );
+        }
+        Commands::Animate {
+            directory,
+            output,
+            format,
+            results,
+            min_temperature,
+            max_temperature,
+            start_date,
+        } => {
+            let network = format.load(directory)?;
+            let temperatures = read_temperatures(
+                results
+                    .clone()
+                    .unwrap_or(custom::output_path(directory, "result"))
+                    .as_str(),
+            )?;
+
+            let ui_parameters = match format {
+                Format::Proprietary => proprietary::load_configuration(directory)?.ui_parameters,
+                _ => None,
+            };
+            let [min, max] = match &ui_parameters {
+                Some(ui_parameters) => ui_parameters.color_limits_temperature,
+                None => animation::temperature_range(&temperatures)
+                    .ok_or(anyhow!("results do not contain any temperatures"))?,
+            };
+
+            let html = animation::html(
+                &network.topology,
+                &network.scenario.settings,
+                &temperatures,
+                &animation::Options {
+                    color_limits: [
+                        min_temperature.unwrap_or(min),
+                        max_temperature.unwrap_or(max),
+                    ],
+                    start_date: start_date
+                        .clone()
+                        .or(ui_parameters.map(|ui_parameters| ui_parameters.start_date)),
+                    title: directory.clone(),
+                },
+            )?;
+            std::fs::write(format!("{}.html", output), html)?;
+        }
+        Commands::Fit {
+            table,
+            x,
+            y,
+            degree,
+            min,
+            max,
+            name,
+        } => {
+            let [xs, ys]: [Vec<f64>; 2] = read_columns(
+                Path::new(table),
+                &[Column::Name(x.clone()), Column::Name(y.clone())],
+                true,
+            )?
+            .try_into()
+            .map_err(|_| anyhow!("expected two columns"))?;
+
+            let fit = fit(&xs, &ys, *degree, (*min, *max))?;
+            let residuals = &fit.residuals;
+
+            println!(
+                "/// {}: Coefficients fitted to {} points of {} in [{}, {}]",
+                name, residuals.num_points, table, min, max,
+            );
+            println!(
+                "/// max residual: {:e}, rms: {:e}, R^2: {}",
+                residuals.max, residuals.rms, residuals.r_squared
+            );
+            for (i, c) in fit.coefficients.iter().enumerate().rev() {
+                println!("const {}{}: f64 = {:e};", name, i, c);
+            }
         }
     }
 
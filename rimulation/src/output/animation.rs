@@ -0,0 +1,346 @@
+//! Self-contained HTML page animating the propagation of temperatures through a network
+//!
+//! Nodes are colored by their simulated temperature and pipes by the mean temperature of their
+//! ends. A slider selects the time step, a button plays the steps one after another. Nodes and
+//! pipes without results are drawn grey.
+
+use super::{
+    render::{escape_xml, scaled},
+    TemperatureStatistics,
+};
+use crate::types::formats::custom::{Settings, Topology};
+
+use anyhow::{anyhow, Error};
+use serde::Serialize;
+use std::{cmp::Ordering, collections::HashMap};
+
+/// Width and height of the largest side of the drawing and its margin \[px\]
+const SIZE: f64 = 800.;
+const MARGIN: f64 = 40.;
+const NODE_RADIUS: f64 = 6.;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Options {
+    /// Temperatures \[°C\] at the blue and the red end of the color scale
+    pub color_limits: [f64; 2],
+    /// Date and time of the first time step, e.g. `2023-04-22 08:00:00`, to label the steps
+    /// with dates instead of hours since the start
+    pub start_date: Option<String>,
+    pub title: String,
+}
+
+/// Lowest and highest temperature of all results, to be used as color limits
+pub fn temperature_range(temperatures: &[(String, Vec<f64>)]) -> Option<[f64; 2]> {
+    let statistics: Vec<TemperatureStatistics> = temperatures
+        .iter()
+        .filter_map(|(_, temperatures)| TemperatureStatistics::of(temperatures))
+        .collect();
+    let min = statistics.iter().map(|s| s.min).reduce(f64::min)?;
+    let max = statistics.iter().map(|s| s.max).reduce(f64::max)?;
+
+    Some(if min < max {
+        [min, max]
+    } else {
+        [min - 1., max + 1.]
+    })
+}
+
+/// Data of the animation that is read by the script of the page
+#[derive(Serialize)]
+struct Data<'a> {
+    limits: [f64; 2],
+    start_date: Option<&'a str>,
+    /// Minutes since the start of the simulation of the first step and between steps
+    time_start: f64,
+    time_step: f64,
+    num_steps: usize,
+    /// Temperatures of the nodes in the order of the topology, `None` for nodes without results
+    temperatures: Vec<Option<&'a [f64]>>,
+    /// Indices of the nodes connected by the pipes
+    pipes: Vec<[usize; 2]>,
+}
+
+/// Creates the page from the topology and the temperatures of the nodes, as written by
+/// [`super::write_temperatures`]
+///
+/// The results must start at the start of the simulation and advance by its time step.
+pub fn html(
+    topology: &Topology,
+    settings: &Settings,
+    temperatures: &[(String, Vec<f64>)],
+    options: &Options,
+) -> Result<String, Error> {
+    let [min, max] = options.color_limits;
+    if min.partial_cmp(&max) != Some(Ordering::Less) {
+        return Err(anyhow!(
+            "lower color limit {} is not below the upper color limit {}",
+            min,
+            max
+        ));
+    }
+
+    let indices: HashMap<&str, usize> = topology
+        .nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| (node.name.as_str(), i))
+        .collect();
+
+    let mut node_temperatures = vec![None; topology.nodes.len()];
+    for (name, values) in temperatures {
+        let i = indices.get(name.as_str()).ok_or(anyhow!(
+            "results contain node '{}' that is not in the topology",
+            name
+        ))?;
+        node_temperatures[*i] = Some(values.as_slice());
+    }
+    let num_steps = temperatures
+        .iter()
+        .map(|(_, values)| values.len())
+        .max()
+        .filter(|num_steps| *num_steps > 0)
+        .ok_or(anyhow!("results do not contain any time steps"))?;
+
+    let pipes = topology
+        .pipes
+        .iter()
+        .map(|pipe| {
+            let index = |name: &str| {
+                indices.get(name).copied().ok_or(anyhow!(
+                    "pipe '{}' connects to unknown node '{}'",
+                    pipe.name,
+                    name
+                ))
+            };
+            Ok([index(&pipe.src)?, index(&pipe.tgt)?])
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let data = Data {
+        limits: options.color_limits,
+        start_date: options.start_date.as_deref(),
+        time_start: settings.time_start * (24 * 60) as f64,
+        time_step: settings.time_step,
+        num_steps,
+        temperatures: node_temperatures,
+        pipes,
+    };
+    let positions: Vec<(f64, f64)> = topology
+        .nodes
+        .iter()
+        .map(|node| (node.position.x, node.position.y))
+        .collect();
+    let (positions, (width, height)) = scaled(&positions, SIZE);
+    // SVG coordinates grow downwards
+    let points: Vec<(f64, f64)> = positions
+        .iter()
+        .map(|(x, y)| (x + MARGIN, height - y + MARGIN))
+        .collect();
+
+    let mut elements = vec![];
+    for (i, pipe) in data.pipes.iter().enumerate() {
+        let ((x1, y1), (x2, y2)) = (points[pipe[0]], points[pipe[1]]);
+        elements.push(format!(
+            "<line id=\"p{}\" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\"><title>{}</title></line>",
+            i,
+            x1,
+            y1,
+            x2,
+            y2,
+            escape_xml(&topology.pipes[i].name)
+        ));
+    }
+    for (i, (node, (x, y))) in topology.nodes.iter().zip(&points).enumerate() {
+        elements.push(format!(
+            "<circle id=\"n{}\" cx=\"{}\" cy=\"{}\" r=\"{}\"{} data-name=\"{name}\"><title>{name}</title></circle>",
+            i,
+            x,
+            y,
+            NODE_RADIUS,
+            if node.feed { " class=\"feed\"" } else { "" },
+            name = escape_xml(&node.name)
+        ));
+    }
+
+    // a closing tag inside of the data would end the script early
+    let data = serde_json::to_string(&data)?.replace("</", "<\\/");
+
+    Ok(TEMPLATE
+        .replace("{title}", &escape_xml(&options.title))
+        .replace("{width}", &(width + 2. * MARGIN).to_string())
+        .replace("{height}", &(height + 2. * MARGIN).to_string())
+        .replace("{min}", &min.to_string())
+        .replace("{max}", &max.to_string())
+        .replace("{last_step}", &(num_steps - 1).to_string())
+        .replace("{data}", &data)
+        // names in the elements are not replaced any further
+        .replace("{elements}", &elements.join("\n")))
+}
+
+const TEMPLATE: &str = r##"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body { font-family: sans-serif; margin: 1em; }
+line { stroke-width: 4; stroke-linecap: round; }
+circle { stroke: #333; stroke-width: 1; }
+circle.feed { stroke-width: 3; }
+.controls { display: flex; align-items: center; gap: 1em; margin: 0.5em 0; }
+#step { width: 40em; }
+#scale { width: 20em; height: 1em; background: linear-gradient(to right, hsl(240, 80%, 50%), hsl(120, 80%, 50%), hsl(0, 80%, 50%)); }
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+<div class="controls">
+<button id="play">Play</button>
+<input id="step" type="range" min="0" max="{last_step}" value="0">
+<span id="time"></span>
+</div>
+<div class="controls">
+<span>{min} °C</span><div id="scale"></div><span>{max} °C</span>
+</div>
+<svg xmlns="http://www.w3.org/2000/svg" width="{width}" height="{height}" viewBox="0 0 {width} {height}">
+{elements}
+</svg>
+<script>
+const data = {data};
+const start = data.start_date === null ? NaN : Date.parse(data.start_date.replace(" ", "T") + "Z");
+
+function color(temperature) {
+    if (temperature === null || temperature === undefined) {
+        return "#bbb";
+    }
+    const [min, max] = data.limits;
+    const x = Math.min(Math.max((temperature - min) / (max - min), 0), 1);
+    return "hsl(" + (240 * (1 - x)) + ", 80%, 50%)";
+}
+
+function temperature(node, step) {
+    const values = data.temperatures[node];
+    return values === null ? null : values[step];
+}
+
+function label(step) {
+    const minutes = data.time_start + step * data.time_step;
+    if (isNaN(start)) {
+        return (minutes / 60).toFixed(2) + " h";
+    }
+    return new Date(start + minutes * 60000).toISOString().slice(0, 16).replace("T", " ");
+}
+
+function show(step) {
+    data.temperatures.forEach((_, i) => {
+        const value = temperature(i, step);
+        const node = document.getElementById("n" + i);
+        node.setAttribute("fill", color(value));
+        node.querySelector("title").textContent = node.dataset.name
+            + (value === null || value === undefined ? "" : ": " + value.toFixed(1) + " °C");
+    });
+    data.pipes.forEach(([src, tgt], i) => {
+        const values = [temperature(src, step), temperature(tgt, step)]
+            .filter(value => value !== null && value !== undefined);
+        const mean = values.length === 0 ? null : values.reduce((a, b) => a + b) / values.length;
+        document.getElementById("p" + i).setAttribute("stroke", color(mean));
+    });
+    document.getElementById("time").textContent = label(step);
+}
+
+const slider = document.getElementById("step");
+const button = document.getElementById("play");
+let timer = null;
+slider.addEventListener("input", () => show(Number(slider.value)));
+button.addEventListener("click", () => {
+    if (timer !== null) {
+        clearInterval(timer);
+        timer = null;
+        button.textContent = "Play";
+        return;
+    }
+    button.textContent = "Pause";
+    timer = setInterval(() => {
+        slider.value = (Number(slider.value) + 1) % data.num_steps;
+        show(Number(slider.value));
+    }, 100);
+});
+show(0);
+</script>
+</body>
+</html>
+"##;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{output::read_temperatures, types::formats::custom};
+
+    fn options() -> Options {
+        Options {
+            color_limits: [50., 70.],
+            start_date: Some(String::from("2023-04-22 08:00:00")),
+            title: String::from("<triangle>"),
+        }
+    }
+
+    #[test]
+    fn animate_results() {
+        let network = custom::load("data/fixed_velocity/triangle").expect("could not load network");
+        let temperatures = read_temperatures("data/fixed_velocity/triangle/result")
+            .expect("could not read results");
+
+        let html = html(
+            &network.topology,
+            &network.scenario.settings,
+            &temperatures,
+            &options(),
+        )
+        .expect("could not create animation");
+
+        assert_eq!(
+            html.matches("<circle id=").count(),
+            network.topology.nodes.len()
+        );
+        assert_eq!(
+            html.matches("<line id=").count(),
+            network.topology.pipes.len()
+        );
+        assert!(html.contains(&format!("max=\"{}\"", temperatures[0].1.len() - 1)));
+        assert!(html.contains("\"limits\":[50.0,70.0]"));
+        assert!(html.contains("<title>&lt;triangle&gt;</title>"));
+        std::fs::write("/tmp/rimulation_triangle.html", html).expect("could not write animation");
+    }
+
+    #[test]
+    fn report_unknown_nodes() {
+        let network = custom::load("data/fixed_velocity/triangle").expect("could not load network");
+        let temperatures = vec![(String::from("X"), vec![60.])];
+
+        assert_eq!(
+            html(
+                &network.topology,
+                &network.scenario.settings,
+                &temperatures,
+                &options()
+            )
+            .expect_err("node is unknown")
+            .to_string(),
+            "results contain node 'X' that is not in the topology"
+        );
+    }
+
+    #[test]
+    fn range_of_temperatures() {
+        let temperatures = vec![
+            (String::from("A"), vec![60., f64::NAN, 40.]),
+            (String::from("B"), vec![f64::NAN]),
+            (String::from("C"), vec![80.]),
+        ];
+        assert_eq!(temperature_range(&temperatures), Some([40., 80.]));
+
+        let constant = vec![(String::from("A"), vec![60., 60.])];
+        assert_eq!(temperature_range(&constant), Some([59., 61.]));
+        assert_eq!(temperature_range(&[]), None);
+    }
+}
@@ -1,4 +1,7 @@
-use csv::Writer;
+pub mod animation;
+pub mod render;
+
+use csv::{Reader, Writer};
 use std::{collections::HashSet, fs::File};
 
 use anyhow::{anyhow, Error};
@@ -9,8 +12,8 @@ use crate::types::{
     network::Network,
 };
 
-pub fn write_temperatures<EdgeParameters>(
-    network: &Network<EdgeParameters>,
+pub fn write_temperatures<EdgeParameters, Fluid>(
+    network: &Network<EdgeParameters, Fluid>,
     settings: &Settings,
     result: Vec<(usize, DVector<f64>)>,
     output_file_name: &str,
@@ -62,3 +65,82 @@ pub fn write_temperatures<EdgeParameters>(
 
     Ok(())
 }
+
+/// Writes the node names and time steps of results that depend on invalid input data
+pub fn write_invalid_steps<EdgeParameters, Fluid>(
+    network: &Network<EdgeParameters, Fluid>,
+    invalid: &[(usize, usize)],
+    output_file_name: &str,
+) -> Result<(), Error> {
+    let mut writer = Writer::from_writer(File::create(output_file_name)?);
+
+    writer.write_record(["node", "step"])?;
+
+    for (i, t) in invalid {
+        let name = network.get_node(*i)?.get_name();
+        writer.write_record([name, t.to_string()])?;
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+/// Reads the temperatures written by [`write_temperatures`] as node names and their values in
+/// the order of the time steps
+pub fn read_temperatures(file_name: &str) -> Result<Vec<(String, Vec<f64>)>, Error> {
+    let mut reader = Reader::from_path(file_name)
+        .map_err(|err| anyhow!("could not open results '{}': {}", file_name, err))?;
+
+    let mut temperatures: Vec<(String, Vec<f64>)> = reader
+        .headers()
+        .map_err(|err| anyhow!("could not read results '{}': {}", file_name, err))?
+        .iter()
+        .map(|name| (name.to_string(), vec![]))
+        .collect();
+
+    for record in reader.records() {
+        let record =
+            record.map_err(|err| anyhow!("could not read results '{}': {}", file_name, err))?;
+        for ((name, values), field) in temperatures.iter_mut().zip(&record) {
+            values.push(field.parse().map_err(|_| {
+                anyhow!(
+                    "could not read results '{}': temperature '{}' of node '{}' is not a number",
+                    file_name,
+                    field,
+                    name
+                )
+            })?);
+        }
+    }
+
+    Ok(temperatures)
+}
+
+/// Minimum, mean and maximum of the temperatures of a node over all time steps
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct TemperatureStatistics {
+    pub min: f64,
+    pub mean: f64,
+    pub max: f64,
+}
+
+impl TemperatureStatistics {
+    /// Computes the statistics of all temperatures that are not NaN, if there are any
+    pub fn of(temperatures: &[f64]) -> Option<Self> {
+        let valid: Vec<f64> = temperatures
+            .iter()
+            .copied()
+            .filter(|t| !t.is_nan())
+            .collect();
+        if valid.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            min: valid.iter().copied().fold(f64::INFINITY, f64::min),
+            mean: valid.iter().sum::<f64>() / valid.len() as f64,
+            max: valid.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+        })
+    }
+}
@@ -0,0 +1,452 @@
+//! Drawings of the internal network for debugging its spanning tree and feed extraction
+//!
+//! Edges of the spanning tree are drawn solid, cycle edges dashed and red. Pressure nodes are
+//! squares, demand nodes filled circles and zero nodes small hollow circles. The root of the
+//! spanning tree is drawn with a thick outline. Edges are labelled with their index.
+
+use crate::types::{
+    formats::NamedComponent,
+    network::{Network, Node},
+};
+
+use std::collections::{HashMap, HashSet};
+
+/// Width and height of the largest side of the DOT drawing \[inch\]
+const DOT_SIZE: f64 = 10.;
+/// Width and height of the largest side of the SVG drawing and its margin \[px\]
+const SVG_SIZE: f64 = 800.;
+const SVG_MARGIN: f64 = 60.;
+const NODE_RADIUS: f64 = 8.;
+
+/// Positions of all nodes in the order of [`Network::nodes`]
+///
+/// The positions of the nodes are used if they tell all nodes apart. Otherwise, the nodes are
+/// placed in columns by their depth in the spanning tree, starting with the root on the left.
+pub fn layout<T, F>(network: &Network<T, F>) -> Vec<(f64, f64)> {
+    let positions: Vec<(f64, f64)> = network
+        .nodes()
+        .map(|node| {
+            let position = node.get_position();
+            (position.x, position.y)
+        })
+        .collect();
+
+    let distinct: HashSet<(u64, u64)> = positions
+        .iter()
+        .map(|(x, y)| (x.to_bits(), y.to_bits()))
+        .collect();
+    if distinct.len() == positions.len() {
+        return positions;
+    }
+
+    let mut depths: HashMap<usize, usize> = HashMap::from([(network.root_node_index, 0)]);
+    fn depth(
+        node: usize,
+        pred_nodes: &HashMap<usize, usize>,
+        depths: &mut HashMap<usize, usize>,
+    ) -> Option<usize> {
+        if let Some(depth) = depths.get(&node) {
+            return Some(*depth);
+        }
+        let pred = *pred_nodes.get(&node)?;
+        // mark the node to stop at cycles of broken predecessor maps
+        depths.insert(node, usize::MAX);
+        let depth = depth(pred, pred_nodes, depths)?.checked_add(1)?;
+        depths.insert(node, depth);
+        Some(depth)
+    }
+
+    let node_depths: Vec<Option<usize>> = (0..positions.len())
+        .map(|i| depth(i, &network.pred_nodes, &mut depths).filter(|d| *d != usize::MAX))
+        .collect();
+    // nodes outside of the spanning tree get a column of their own
+    let unreachable = node_depths.iter().flatten().max().map_or(0, |max| max + 1);
+
+    let mut num_in_column: HashMap<usize, usize> = HashMap::new();
+    node_depths
+        .into_iter()
+        .map(|depth| {
+            let column = depth.unwrap_or(unreachable);
+            let row = num_in_column.entry(column).or_default();
+            *row += 1;
+            (column as f64, -((*row - 1) as f64))
+        })
+        .collect()
+}
+
+/// Scales positions into `[0, size]` in both directions while keeping their aspect ratio
+///
+/// Returns the scaled positions and the width and height they take.
+pub(crate) fn scaled(positions: &[(f64, f64)], size: f64) -> (Vec<(f64, f64)>, (f64, f64)) {
+    let (mut min, mut max) = (
+        (f64::INFINITY, f64::INFINITY),
+        (f64::NEG_INFINITY, f64::NEG_INFINITY),
+    );
+    for (x, y) in positions {
+        min = (min.0.min(*x), min.1.min(*y));
+        max = (max.0.max(*x), max.1.max(*y));
+    }
+    if positions.is_empty() {
+        return (vec![], (0., 0.));
+    }
+
+    let extent = (max.0 - min.0).max(max.1 - min.1);
+    let scale = if extent > 0. { size / extent } else { 1. };
+    (
+        positions
+            .iter()
+            .map(|(x, y)| ((x - min.0) * scale, (y - min.1) * scale))
+            .collect(),
+        ((max.0 - min.0) * scale, (max.1 - min.1) * scale),
+    )
+}
+
+enum Kind {
+    Pressure,
+    Demand,
+    Zero,
+}
+
+fn kind(node: &Node) -> Kind {
+    match node {
+        Node::Pressure { .. } => Kind::Pressure,
+        Node::Demand { .. } => Kind::Demand,
+        Node::Zero { .. } => Kind::Zero,
+    }
+}
+
+/// Edges with their index and whether they close a cycle
+fn edges<T, F>(network: &Network<T, F>) -> impl Iterator<Item = (usize, usize, usize, bool)> + '_ {
+    let num_tree_edges = network.spanning_tree_edges.len();
+    network
+        .edges()
+        .enumerate()
+        .map(move |(i, edge)| (i, edge.src, edge.tgt, i >= num_tree_edges))
+}
+
+/// Draws the network in the DOT language of GraphViz
+///
+/// Nodes are pinned to their [`layout`] in inches, so the drawing should be rendered with
+/// `neato`, e.g. `neato -Tpdf network.dot -o network.pdf`.
+pub fn dot<T, F>(network: &Network<T, F>) -> String {
+    let (positions, _) = scaled(&layout(network), DOT_SIZE);
+    let mut lines = vec![
+        String::from("digraph network {"),
+        String::from("  layout = neato;"),
+        String::from("  node [fontsize = 10];"),
+        String::from("  edge [fontsize = 8];"),
+    ];
+
+    for (i, (node, (x, y))) in network.nodes().zip(&positions).enumerate() {
+        let shape = match kind(node) {
+            Kind::Pressure => "shape = box",
+            Kind::Demand => "shape = circle, style = filled, fillcolor = lightblue",
+            Kind::Zero => "shape = circle, width = 0.2, fixedsize = true",
+        };
+        let root = if i == network.root_node_index {
+            ", penwidth = 3"
+        } else {
+            ""
+        };
+        lines.push(format!(
+            "  n{} [label = \"{}\", {}{}, pos = \"{},{}!\"];",
+            i,
+            node.get_name().replace('\\', "\\\\").replace('"', "\\\""),
+            shape,
+            root,
+            x,
+            y
+        ));
+    }
+
+    for (i, src, tgt, cycle) in edges(network) {
+        let style = if cycle {
+            ", style = dashed, color = red"
+        } else {
+            ""
+        };
+        lines.push(format!(
+            "  n{} -> n{} [label = \"{}\"{}];",
+            src, tgt, i, style
+        ));
+    }
+
+    lines.push(String::from("}"));
+    lines.join("\n") + "\n"
+}
+
+pub(crate) fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Draws the network as standalone SVG with a legend
+pub fn svg<T, F>(network: &Network<T, F>) -> String {
+    let (positions, (width, height)) = scaled(&layout(network), SVG_SIZE);
+    // SVG coordinates grow downwards
+    let points: Vec<(f64, f64)> = positions
+        .iter()
+        .map(|(x, y)| (x + SVG_MARGIN, height - y + SVG_MARGIN))
+        .collect();
+
+    let legend_height = 90.;
+    let mut elements = vec![
+        format!(
+            concat!(
+                "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" ",
+                "viewBox=\"0 0 {w} {h}\" font-family=\"sans-serif\">"
+            ),
+            w = width + 2. * SVG_MARGIN,
+            h = height + 2. * SVG_MARGIN + legend_height
+        ),
+        String::from(concat!(
+            "<style>",
+            ".tree{stroke:#333;stroke-width:2}",
+            ".cycle{stroke:#d62728;stroke-width:2;stroke-dasharray:6 4}",
+            ".pressure{fill:#fff;stroke:#333;stroke-width:2}",
+            ".demand{fill:#9ecae1;stroke:#333;stroke-width:1}",
+            ".zero{fill:#fff;stroke:#333;stroke-width:1}",
+            ".root{stroke-width:4}",
+            "text{font-size:11px}",
+            "</style>",
+        )),
+        String::from(concat!(
+            "<defs><marker id=\"arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" ",
+            "markerWidth=\"6\" markerHeight=\"6\" orient=\"auto\">",
+            "<path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"#333\"/></marker></defs>",
+        )),
+    ];
+
+    for (i, src, tgt, cycle) in edges(network) {
+        let ((x1, y1), (x2, y2)) = (points[src], points[tgt]);
+        let length = (x2 - x1).hypot(y2 - y1);
+        // end the line at the border of the target node so that the arrow stays visible
+        let shorten = if length > 0. {
+            (NODE_RADIUS + 2.) / length
+        } else {
+            0.
+        };
+        elements.push(format!(
+            concat!(
+                "<line class=\"{}\" x1=\"{}\" y1=\"{}\" x2=\"{}\" y2=\"{}\" ",
+                "marker-end=\"url(#arrow)\"/>"
+            ),
+            if cycle { "cycle" } else { "tree" },
+            x1,
+            y1,
+            x2 - (x2 - x1) * shorten,
+            y2 - (y2 - y1) * shorten
+        ));
+        elements.push(format!(
+            "<text x=\"{}\" y=\"{}\" fill=\"#666\" text-anchor=\"middle\">{}</text>",
+            (x1 + x2) / 2.,
+            (y1 + y2) / 2. - 4.,
+            i
+        ));
+    }
+
+    let shape = |kind: Kind, (x, y): (f64, f64), root: bool| {
+        let root = if root { " root" } else { "" };
+        match kind {
+            Kind::Pressure => format!(
+                "<rect class=\"pressure{}\" x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\"/>",
+                root,
+                x - NODE_RADIUS,
+                y - NODE_RADIUS,
+                2. * NODE_RADIUS,
+                2. * NODE_RADIUS
+            ),
+            Kind::Demand => format!(
+                "<circle class=\"demand{}\" cx=\"{}\" cy=\"{}\" r=\"{}\"/>",
+                root, x, y, NODE_RADIUS
+            ),
+            Kind::Zero => format!(
+                "<circle class=\"zero{}\" cx=\"{}\" cy=\"{}\" r=\"{}\"/>",
+                root,
+                x,
+                y,
+                NODE_RADIUS / 2.
+            ),
+        }
+    };
+
+    for (i, (node, point)) in network.nodes().zip(&points).enumerate() {
+        elements.push(shape(kind(node), *point, i == network.root_node_index));
+        elements.push(format!(
+            "<text x=\"{}\" y=\"{}\">{}</text>",
+            point.0 + NODE_RADIUS + 2.,
+            point.1 - NODE_RADIUS - 2.,
+            escape_xml(&node.get_name())
+        ));
+    }
+
+    let top = height + 2. * SVG_MARGIN;
+    let legend = [
+        (
+            shape(Kind::Pressure, (20., top + 10.), false),
+            "pressure node",
+        ),
+        (shape(Kind::Demand, (20., top + 35.), false), "demand node"),
+        (shape(Kind::Zero, (20., top + 60.), false), "zero node"),
+        (
+            format!(
+                "<line class=\"tree\" x1=\"160\" y1=\"{0}\" x2=\"200\" y2=\"{0}\"/>",
+                top + 10.
+            ),
+            "spanning tree edge",
+        ),
+        (
+            format!(
+                "<line class=\"cycle\" x1=\"160\" y1=\"{0}\" x2=\"200\" y2=\"{0}\"/>",
+                top + 35.
+            ),
+            "cycle edge",
+        ),
+        (
+            format!(
+                "<circle class=\"zero root\" cx=\"180\" cy=\"{}\" r=\"{}\"/>",
+                top + 60.,
+                NODE_RADIUS
+            ),
+            "root of the spanning tree",
+        ),
+    ];
+    for (i, (symbol, label)) in legend.into_iter().enumerate() {
+        let x = if i < 3 { 35. } else { 210. };
+        elements.push(symbol);
+        elements.push(format!(
+            "<text x=\"{}\" y=\"{}\">{}</text>",
+            x,
+            top + 14. + 25. * (i % 3) as f64,
+            label
+        ));
+    }
+
+    elements.push(String::from("</svg>"));
+    elements.join("\n") + "\n"
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        formats::custom::{self, Position},
+        network::{Edge, EmptyPipeParameters},
+        signal::Signal,
+    };
+
+    fn network(positions: &[(f64, f64)], edges: &[(usize, usize)]) -> Network<EmptyPipeParameters> {
+        let nodes = positions
+            .iter()
+            .enumerate()
+            .map(|(i, (x, y))| {
+                let position = Position {
+                    x: *x,
+                    y: *y,
+                    z: 0.,
+                };
+                let name = format!("N{}", i);
+                if i == 0 {
+                    Node::Pressure {
+                        name,
+                        pressure: Signal::Const { value: 1. },
+                        temperature: Signal::Const { value: 1. },
+                        position,
+                    }
+                } else if i == positions.len() - 1 {
+                    Node::Demand {
+                        name,
+                        demand: Signal::Const { value: 1. },
+                        position,
+                    }
+                } else {
+                    Node::Zero { name, position }
+                }
+            })
+            .collect();
+        let edges: Vec<Edge> = edges.iter().map(|&(src, tgt)| Edge { src, tgt }).collect();
+        let parameters = edges.iter().map(|_| EmptyPipeParameters {}).collect();
+
+        Network::try_from_feed(nodes, edges, parameters).expect("could not create network")
+    }
+
+    #[test]
+    fn layout_from_positions() {
+        let network = network(
+            &[(0., 0.), (1., 0.), (1., 1.), (0., 1.)],
+            &[(0, 1), (1, 2), (2, 3), (0, 3)],
+        );
+
+        let positions = layout(&network);
+
+        let expected: Vec<(f64, f64)> = network
+            .nodes()
+            .map(|node| (node.get_position().x, node.get_position().y))
+            .collect();
+        assert_eq!(positions, expected);
+    }
+
+    #[test]
+    fn layout_by_depth_without_distinct_positions() {
+        let network = network(&[(0., 0.); 4], &[(0, 1), (1, 2), (1, 3)]);
+
+        let positions = layout(&network);
+
+        let root = network.root_node_index;
+        assert_eq!(positions[root].0, 0.);
+        let mut columns: Vec<f64> = positions.iter().map(|(x, _)| *x).collect();
+        columns.sort_by(f64::total_cmp);
+        assert_eq!(columns, vec![0., 1., 2., 2.]);
+        let distinct: HashSet<(u64, u64)> = positions
+            .iter()
+            .map(|(x, y)| (x.to_bits(), y.to_bits()))
+            .collect();
+        assert_eq!(distinct.len(), 4);
+    }
+
+    #[test]
+    fn style_cycle_edges() {
+        let network = network(
+            &[(0., 0.), (1., 0.), (1., 1.), (0., 1.)],
+            &[(0, 1), (1, 2), (2, 3), (0, 3)],
+        );
+        assert_eq!(network.cycle_edges.len(), 1);
+
+        let dot = dot(&network);
+        assert_eq!(dot.matches("style = dashed, color = red").count(), 1);
+        assert_eq!(dot.matches(" -> ").count(), 4);
+        assert_eq!(dot.matches("shape = box").count(), 1);
+        assert_eq!(dot.matches("penwidth = 3").count(), 1);
+
+        let svg = svg(&network);
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        assert_eq!(
+            svg.matches("<line class=\"cycle\"").count(),
+            2,
+            "edge and legend"
+        );
+        assert_eq!(
+            svg.matches("<line class=\"tree\"").count(),
+            4,
+            "edges and legend"
+        );
+    }
+
+    #[test]
+    fn render_example() {
+        let network: Network<EmptyPipeParameters> = custom::load("data/running_example")
+            .expect("could not load network")
+            .try_into()
+            .expect("could not convert network");
+
+        let svg = svg(&network);
+
+        for node in network.nodes() {
+            assert!(svg.contains(&format!(">{}</text>", node.get_name())));
+        }
+        std::fs::write("/tmp/rimulation_running_example.svg", svg).expect("could not write SVG");
+    }
+}
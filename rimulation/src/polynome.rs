@@ -1,9 +1,17 @@
+use std::ops::Add;
+
+use anyhow::{anyhow, Error};
+use nalgebra::{DMatrix, DVector};
+
+use crate::scalar::Scalar;
+
 /// Evaluates a polynomial using Horner's method.
 ///
 /// # Arguments
 /// * `x` - The value at which to evaluate the polynomial
 /// * `coefficients` - Coefficients of the polynomial in ascending order of degree
-///   (i.e., [a, b, c, ...] for polynomial a + b x + c x^2 + ...)
+///   (i.e., [a, b, c, ...] for polynomial a + b x + c x^2 + ...),
+///   either constants or of the same [`Scalar`] type as `x`
 ///
 /// # Example
 /// ```
@@ -13,19 +21,415 @@
 /// let result = poly(x, &[1.0, 2.0, 3.0]);
 /// assert_eq!(result, 1. + 2. * x + 3. * x.powi(2))
 /// ```
-pub fn poly(x: f64, coefficients: &[f64]) -> f64 {
-    let mut y = 0.;
+pub fn poly<T, C>(x: T, coefficients: &[C]) -> T
+where
+    T: Scalar + Add<C, Output = T>,
+    C: Copy,
+{
+    let mut y = T::zero();
 
     for f in coefficients.iter().rev() {
+        y = y * x + *f;
+    }
+
+    y
+}
+
+/// Evaluates a polynomial and its derivative using Horner's method.
+///
+/// # Arguments
+/// * `x` - The value at which to evaluate the polynomial
+/// * `coefficients` - Coefficients of the polynomial in ascending order of degree
+///
+/// # Example
+/// ```
+/// use rimulation::polynome::poly_with_derivative;
+///
+/// let x = 2.0;
+/// let (y, dy) = poly_with_derivative(x, &[1.0, 2.0, 3.0]);
+/// assert_eq!(y, 1. + 2. * x + 3. * x.powi(2));
+/// assert_eq!(dy, 2. + 6. * x);
+/// ```
+pub fn poly_with_derivative<T, C>(x: T, coefficients: &[C]) -> (T, T)
+where
+    T: Scalar + Add<C, Output = T>,
+    C: Copy,
+{
+    let mut y = T::zero();
+    let mut dy = T::zero();
+
+    for f in coefficients.iter().rev() {
+        dy = dy * x + y;
+        y = y * x + *f;
+    }
+
+    (y, dy)
+}
+
+/// Evaluates the derivative of a polynomial.
+///
+/// See [`poly`] for the order of the coefficients.
+pub fn poly_derivative<T, C>(x: T, coefficients: &[C]) -> T
+where
+    T: Scalar + Add<C, Output = T>,
+    C: Copy,
+{
+    poly_with_derivative(x, coefficients).1
+}
+
+/// Evaluates the antiderivative of a polynomial which vanishes at 0.
+///
+/// See [`poly`] for the order of the coefficients.
+///
+/// # Example
+/// ```
+/// use rimulation::polynome::poly_antiderivative;
+///
+/// let x = 2.0;
+/// let result = poly_antiderivative(x, &[1.0, 2.0, 3.0]);
+/// assert_eq!(result, x + x.powi(2) + x.powi(3))
+/// ```
+pub fn poly_antiderivative<T, C>(x: T, coefficients: &[C]) -> T
+where
+    T: Scalar + Add<C, Output = T>,
+    C: Copy,
+{
+    let mut y = T::zero();
+
+    for (i, f) in coefficients.iter().enumerate().rev() {
+        y += (T::zero() + *f) / (i + 1) as f64;
         y *= x;
-        y += *f;
     }
 
     y
 }
 
+/// Coefficients of the derivative of a polynomial
+pub fn derivative(coefficients: &[f64]) -> Vec<f64> {
+    coefficients
+        .iter()
+        .enumerate()
+        .skip(1)
+        .map(|(i, f)| i as f64 * f)
+        .collect()
+}
+
+/// Coefficients of the antiderivative of a polynomial which vanishes at 0
+pub fn antiderivative(coefficients: &[f64]) -> Vec<f64> {
+    std::iter::once(0.)
+        .chain(
+            coefficients
+                .iter()
+                .enumerate()
+                .map(|(i, f)| f / (i + 1) as f64),
+        )
+        .collect()
+}
+
+const MAX_ITERATIONS: usize = 100;
+
+/// Finds all real roots of a polynomial in `[min, max]` in ascending order
+///
+/// The bounds may be infinite, they are then replaced by Cauchy's bound of the roots.
+/// The roots of the derivative split the range into intervals in which the polynomial is monotonic,
+/// every interval with a sign change contains exactly one root.
+/// It is found by Newton's method, falling back to bisection whenever a step leaves the interval.
+/// Multiple roots are returned once.
+/// A vanishing polynomial has no isolated roots, the result is empty.
+///
+/// # Example
+/// ```
+/// use rimulation::polynome::roots;
+///
+/// // (x + 2) (x - 1) (x - 3)
+/// let roots = roots(&[6., -5., -2., 1.], (f64::NEG_INFINITY, f64::INFINITY));
+/// assert_eq!(roots.len(), 3);
+/// assert!((roots[0] + 2.).abs() < 1e-12);
+/// assert!((roots[2] - 3.).abs() < 1e-12);
+/// ```
+pub fn roots(coefficients: &[f64], (min, max): (f64, f64)) -> Vec<f64> {
+    let Some(degree) = coefficients.iter().rposition(|f| *f != 0.) else {
+        return vec![];
+    };
+    let coefficients = &coefficients[..=degree];
+
+    let bound = 1.
+        + coefficients[..degree]
+            .iter()
+            .map(|f| (f / coefficients[degree]).abs())
+            .fold(0., f64::max);
+    let (min, max) = (min.max(-bound), max.min(bound));
+
+    if min > max {
+        return vec![];
+    }
+
+    roots_rec(coefficients, min, max)
+}
+
+/// Roots of a polynomial with non-vanishing leading coefficient in `[min, max]`
+fn roots_rec(coefficients: &[f64], min: f64, max: f64) -> Vec<f64> {
+    if coefficients.len() < 2 {
+        return vec![];
+    }
+
+    let mut points = vec![min];
+    points.extend(roots_rec(&derivative(coefficients), min, max));
+    points.push(max);
+
+    let mut roots: Vec<f64> = points
+        .windows(2)
+        .filter_map(|w| monotonic_root(coefficients, w[0], w[1]))
+        .collect();
+    roots.dedup();
+
+    roots
+}
+
+/// Whether `y = p(x)` cannot be distinguished from 0 due to rounding errors in Horner's method
+fn is_zero(x: f64, y: f64, coefficients: &[f64]) -> bool {
+    let magnitude = coefficients
+        .iter()
+        .rev()
+        .fold(0., |acc, f| acc * x.abs() + f.abs());
+
+    y.abs() <= 2. * coefficients.len() as f64 * f64::EPSILON * magnitude
+}
+
+/// Root of a polynomial which is monotonic in `[l, r]`
+fn monotonic_root(coefficients: &[f64], mut l: f64, mut r: f64) -> Option<f64> {
+    let y_l = poly(l, coefficients);
+    if is_zero(l, y_l, coefficients) {
+        return Some(l);
+    }
+    let y_r = poly(r, coefficients);
+    if is_zero(r, y_r, coefficients) {
+        return Some(r);
+    }
+    if y_l.signum() == y_r.signum() {
+        return None;
+    }
+
+    let mut x = (l + r) / 2.;
+    for _ in 0..MAX_ITERATIONS {
+        let (y, dy) = poly_with_derivative(x, coefficients);
+        if is_zero(x, y, coefficients) {
+            break;
+        }
+
+        if y.signum() == y_l.signum() {
+            l = x;
+        } else {
+            r = x;
+        }
+
+        let newton = x - y / dy;
+        let next = if l < newton && newton < r {
+            newton
+        } else {
+            (l + r) / 2.
+        };
+
+        if next == x {
+            break;
+        }
+        x = next;
+    }
+
+    Some(x)
+}
+
+/// Evaluates a Chebyshev series `c_0 T_0(u) + c_1 T_1(u) + ...` using Clenshaw's algorithm,
+/// where `u` is `x` mapped linearly from `[min, max]` to `[-1, 1]`.
+///
+/// Chebyshev series are better conditioned than monomials on wide ranges.
+///
+/// # Example
+/// ```
+/// use rimulation::polynome::chebyshev;
+///
+/// // T_2(u) = 2 u^2 - 1 with u = (x - 5) / 5
+/// let x = 7.0;
+/// let result = chebyshev(x, &[0.0, 0.0, 1.0], (0.0, 10.0));
+/// assert!((result - (2. * 0.4f64.powi(2) - 1.)).abs() < 1e-15)
+/// ```
+pub fn chebyshev<T, C>(x: T, coefficients: &[C], (min, max): (f64, f64)) -> T
+where
+    T: Scalar + Add<C, Output = T>,
+    C: Copy,
+{
+    let u = (x * 2. - min - max) / (max - min);
+
+    let mut b_1 = T::zero();
+    let mut b_2 = T::zero();
+
+    for f in coefficients.iter().skip(1).rev() {
+        (b_1, b_2) = (u * b_1 * 2. - b_2 + *f, b_1);
+    }
+
+    match coefficients.first() {
+        Some(f) => u * b_1 - b_2 + *f,
+        None => T::zero(),
+    }
+}
+
+/// Chebyshev coefficients of the polynomial of the given degree interpolating `f`
+/// at the Chebyshev nodes in `[min, max]`, to be evaluated with [`chebyshev`]
+///
+/// Exact for polynomials up to the given degree, close to the best approximation for smooth functions.
+pub fn chebyshev_interpolation(
+    f: impl Fn(f64) -> f64,
+    degree: usize,
+    (min, max): (f64, f64),
+) -> Vec<f64> {
+    let n = degree + 1;
+
+    let angles: Vec<f64> = (0..n)
+        .map(|i| std::f64::consts::PI * (i as f64 + 0.5) / n as f64)
+        .collect();
+    let values: Vec<f64> = angles
+        .iter()
+        .map(|angle| f((min + max) / 2. + (max - min) / 2. * angle.cos()))
+        .collect();
+
+    (0..n)
+        .map(|k| {
+            let sum: f64 = angles
+                .iter()
+                .zip(&values)
+                .map(|(angle, y)| y * (k as f64 * angle).cos())
+                .sum();
+
+            if k == 0 {
+                sum / n as f64
+            } else {
+                2. * sum / n as f64
+            }
+        })
+        .collect()
+}
+
+/// Statistics of the residuals `p(x_i) - y_i` of a fitted polynomial
+#[derive(Debug, PartialEq, Clone)]
+pub struct ResidualStatistics {
+    /// Number of fitted points
+    pub num_points: usize,
+    /// Largest absolute residual
+    pub max: f64,
+    /// Root mean square of the residuals
+    pub rms: f64,
+    /// Coefficient of determination
+    pub r_squared: f64,
+}
+
+/// Polynomial fitted to tabulated data with [`fit`]
+#[derive(Debug, PartialEq, Clone)]
+pub struct Fit {
+    /// Coefficients in ascending order of degree, as expected by [`poly`]
+    pub coefficients: Vec<f64>,
+    pub residuals: ResidualStatistics,
+}
+
+/// Fits a polynomial of the given degree to the points `(x_i, y_i)` with `x_i` in `[min, max]`
+/// by linear least squares
+///
+/// # Example
+/// ```
+/// use rimulation::polynome::{fit, poly};
+///
+/// let x = [0., 1., 2., 3., 4.];
+/// let y = x.map(|x| poly(x, &[1., -2., 0.5]));
+///
+/// let fit = fit(&x, &y, 2, (0., 4.)).unwrap();
+/// assert!((fit.coefficients[1] + 2.).abs() < 1e-10);
+/// assert!(fit.residuals.max < 1e-10);
+/// ```
+pub fn fit(x: &[f64], y: &[f64], degree: usize, (min, max): (f64, f64)) -> Result<Fit, Error> {
+    if x.len() != y.len() {
+        return Err(anyhow!(
+            "got {} x values, but {} y values",
+            x.len(),
+            y.len()
+        ));
+    }
+
+    let (x, y): (Vec<f64>, Vec<f64>) = x
+        .iter()
+        .zip(y)
+        .filter(|(x, _)| (min..=max).contains(*x))
+        .unzip();
+
+    let n = x.len();
+    if n <= degree {
+        return Err(anyhow!(
+            "{} points in [{}, {}] are not enough to fit a polynomial of degree {}",
+            n,
+            min,
+            max,
+            degree
+        ));
+    }
+
+    // scale the points to [-1, 1] for a better conditioned Vandermonde matrix,
+    // the range itself may be unbounded
+    let lo = x.iter().copied().fold(f64::INFINITY, f64::min);
+    let hi = x.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let center = (hi + lo) / 2.;
+    let half_width = ((hi - lo) / 2.).max(f64::EPSILON);
+
+    let vandermonde = DMatrix::from_fn(n, degree + 1, |i, j| {
+        ((x[i] - center) / half_width).powi(j as i32)
+    });
+    let scaled = vandermonde
+        .svd(true, true)
+        .solve(&DVector::from_column_slice(&y), f64::EPSILON)
+        .map_err(|err| anyhow!("could not solve least squares problem: {}", err))?;
+
+    let coefficients = unscale(scaled.as_slice(), center, half_width);
+
+    let residuals: Vec<f64> = x
+        .iter()
+        .zip(&y)
+        .map(|(x, y)| poly(*x, &coefficients) - y)
+        .collect();
+
+    let mean = y.iter().sum::<f64>() / n as f64;
+    let total: f64 = y.iter().map(|y| (y - mean).powi(2)).sum();
+    let squared: f64 = residuals.iter().map(|r| r * r).sum();
+
+    Ok(Fit {
+        coefficients,
+        residuals: ResidualStatistics {
+            num_points: n,
+            max: residuals.iter().fold(0., |max, r| r.abs().max(max)),
+            rms: (squared / n as f64).sqrt(),
+            r_squared: if total > 0. { 1. - squared / total } else { 1. },
+        },
+    })
+}
+
+/// Converts the coefficients of `p((x - center) / half_width)` to coefficients in `x`
+fn unscale(scaled: &[f64], center: f64, half_width: f64) -> Vec<f64> {
+    let mut coefficients = vec![0.; scaled.len()];
+
+    // (x - center)^j expanded with binomial coefficients
+    for (j, c) in scaled.iter().enumerate() {
+        let factor = c / half_width.powi(j as i32);
+        let mut binomial = 1.;
+
+        for (k, coefficient) in coefficients.iter_mut().enumerate().take(j + 1) {
+            *coefficient += factor * binomial * (-center).powi((j - k) as i32);
+            binomial = binomial * (j - k) as f64 / (k + 1) as f64;
+        }
+    }
+
+    coefficients
+}
+
 #[cfg(test)]
 mod tests {
+    use approx::assert_relative_eq;
+
     use super::*;
 
     const X: [f64; 6] = [0., -1., 1., 10., -10., 100.];
@@ -57,4 +461,160 @@ mod tests {
             assert_eq!(poly(x, &[4., 3., 2., 1.]), ((x + 2.) * x + 3.) * x + 4.)
         }
     }
+
+    #[test]
+    fn derivatives() {
+        let coefficients = [4., 3., 2., 1.];
+        for x in X {
+            let (y, dy) = poly_with_derivative(x, &coefficients);
+            assert_eq!(y, poly(x, &coefficients));
+            assert_eq!(dy, (3. * x + 4.) * x + 3.);
+            assert_eq!(poly_derivative(x, &coefficients), dy);
+            assert_eq!(poly(x, &derivative(&coefficients)), dy);
+        }
+    }
+
+    #[test]
+    fn antiderivatives() {
+        let coefficients = [4., 3., 2., 1.];
+        for x in X {
+            let expected = ((x / 4. + 2. / 3.) * x + 1.5) * x * x + 4. * x;
+            assert_relative_eq!(poly_antiderivative(x, &coefficients), expected);
+            assert_relative_eq!(poly(x, &antiderivative(&coefficients)), expected);
+        }
+        assert_eq!(derivative(&antiderivative(&coefficients)), coefficients);
+    }
+
+    const ALL: (f64, f64) = (f64::NEG_INFINITY, f64::INFINITY);
+
+    fn assert_roots(coefficients: &[f64], range: (f64, f64), expected: &[f64]) {
+        let roots = roots(coefficients, range);
+        assert_eq!(roots.len(), expected.len(), "roots: {:?}", roots);
+        for (root, expected) in roots.iter().zip(expected) {
+            assert_relative_eq!(*root, expected, epsilon = 1e-9);
+        }
+    }
+
+    #[test]
+    fn roots_of_low_degrees() {
+        assert_roots(&[1.], ALL, &[]);
+        assert_roots(&[0., 0.], ALL, &[]);
+        assert_roots(&[-2., 1.], ALL, &[2.]);
+        assert_roots(&[1., 0., 1.], ALL, &[]);
+        assert_roots(&[-1., 0., 1.], ALL, &[-1., 1.]);
+        // vanishing leading coefficients
+        assert_roots(&[-1., 0., 1., 0., 0.], ALL, &[-1., 1.]);
+    }
+
+    #[test]
+    fn roots_of_high_degrees() {
+        // (x - 1) (x - 2) ... (x - 7)
+        let mut coefficients = vec![1.];
+        for root in 1..=7 {
+            coefficients = std::iter::once(0.)
+                .chain(coefficients.iter().copied())
+                .zip(coefficients.iter().map(|f| -f * root as f64).chain([0.]))
+                .map(|(a, b)| a + b)
+                .collect();
+        }
+
+        assert_roots(&coefficients, ALL, &[1., 2., 3., 4., 5., 6., 7.]);
+        assert_roots(&coefficients, (2.5, 5.), &[3., 4., 5.]);
+    }
+
+    #[test]
+    fn multiple_roots() {
+        // (x - 1)^2 (x + 1)
+        assert_roots(&[1., -1., -1., 1.], ALL, &[-1., 1.]);
+        // x^4
+        assert_roots(&[0., 0., 0., 0., 1.], ALL, &[0.]);
+    }
+
+    #[test]
+    fn chebyshev_polynomials() {
+        for x in [-1., -0.5, 0., 0.3, 1.] {
+            assert_relative_eq!(chebyshev(x, &[1.], (-1., 1.)), 1.);
+            assert_relative_eq!(chebyshev(x, &[0., 1.], (-1., 1.)), x);
+            assert_relative_eq!(chebyshev(x, &[0., 0., 1.], (-1., 1.)), 2. * x * x - 1.);
+            assert_relative_eq!(
+                chebyshev(x, &[0., 0., 0., 1.], (-1., 1.)),
+                4. * x * x * x - 3. * x,
+                epsilon = 1e-15
+            );
+        }
+    }
+
+    #[test]
+    fn chebyshev_interpolation_of_polynomial() {
+        let coefficients = [1000., -0.02, -5e-3, 9e-6];
+        let range = (0., 150.);
+
+        let series = chebyshev_interpolation(|x| poly(x, &coefficients), 3, range);
+
+        for x in [0., 10., 75., 149.] {
+            assert_relative_eq!(
+                chebyshev(x, &series, range),
+                poly(x, &coefficients),
+                max_relative = 1e-14
+            );
+        }
+    }
+
+    #[test]
+    fn chebyshev_interpolation_of_function() {
+        let range = (0., 3.);
+        let series = chebyshev_interpolation(f64::exp, 15, range);
+
+        for x in [0., 0.5, 1.7, 3.] {
+            assert_relative_eq!(chebyshev(x, &series, range), x.exp(), max_relative = 1e-12);
+        }
+    }
+
+    #[test]
+    fn fit_recovers_polynomial() {
+        let coefficients = [1000., -0.02, -5e-3, 9e-6];
+        let x: Vec<f64> = (0..=30).map(|i| 5. * i as f64).collect();
+        let y: Vec<f64> = x.iter().map(|x| poly(*x, &coefficients)).collect();
+
+        let fit = fit(&x, &y, 3, (0., 150.)).expect("could not fit polynomial");
+
+        for (fitted, expected) in fit.coefficients.iter().zip(coefficients) {
+            assert_relative_eq!(*fitted, expected, max_relative = 1e-8);
+        }
+        assert_eq!(fit.residuals.num_points, 31);
+        assert!(fit.residuals.max < 1e-9);
+        assert_relative_eq!(fit.residuals.r_squared, 1.);
+    }
+
+    #[test]
+    fn fit_only_in_range() {
+        // a parabola, but linear in [0, 2]
+        let x = [-2., -1., 0., 1., 2.];
+        let y = [10., 10., 0., 1., 2.];
+
+        let fit = fit(&x, &y, 1, (0., 2.)).expect("could not fit polynomial");
+
+        assert_eq!(fit.residuals.num_points, 3);
+        assert_relative_eq!(fit.coefficients[0], 0., epsilon = 1e-12);
+        assert_relative_eq!(fit.coefficients[1], 1., epsilon = 1e-12);
+    }
+
+    #[test]
+    fn fit_residual_statistics() {
+        let x = [0., 1., 2., 3.];
+        let y = [1., -1., 1., -1.];
+
+        let fit = fit(&x, &y, 0, (0., 3.)).expect("could not fit polynomial");
+
+        assert_relative_eq!(fit.coefficients[0], 0., epsilon = 1e-12);
+        assert_relative_eq!(fit.residuals.max, 1.);
+        assert_relative_eq!(fit.residuals.rms, 1.);
+        assert_relative_eq!(fit.residuals.r_squared, 0., epsilon = 1e-12);
+    }
+
+    #[test]
+    fn fit_needs_enough_points() {
+        assert!(fit(&[0., 1.], &[0., 1.], 2, (0., 1.)).is_err());
+        assert!(fit(&[0., 1., 2.], &[0., 1.], 1, (0., 2.)).is_err());
+    }
 }
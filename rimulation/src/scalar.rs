@@ -0,0 +1,445 @@
+use std::{
+    f64::consts::{LN_10, LN_2},
+    fmt::Debug,
+    iter::Sum,
+    ops::{Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Sub, SubAssign},
+};
+
+use num_traits::{One, Zero};
+
+/// Numbers the physical models are generic over
+///
+/// Besides `f64`, [`Dual`] numbers flow through the models
+/// to compute exact sensitivities by forward-mode automatic differentiation.
+/// Constants of the models stay `f64`, hence the arithmetic with `f64` on the right-hand side.
+/// Comparisons and branches only look at [`Scalar::value`].
+pub trait Scalar:
+    Copy
+    + Debug
+    + PartialEq
+    + PartialOrd
+    + Zero
+    + One
+    + Sum
+    + Neg<Output = Self>
+    + Add<Output = Self>
+    + Sub<Output = Self>
+    + Mul<Output = Self>
+    + Div<Output = Self>
+    + AddAssign
+    + SubAssign
+    + MulAssign
+    + DivAssign
+    + Add<f64, Output = Self>
+    + Sub<f64, Output = Self>
+    + Mul<f64, Output = Self>
+    + Div<f64, Output = Self>
+    + 'static
+{
+    /// Converts a constant
+    fn from_f64(value: f64) -> Self;
+
+    /// The plain value, without any derivatives
+    fn value(&self) -> f64;
+
+    /// Whether the number carries a non-zero derivative
+    fn is_constant(&self) -> bool;
+
+    /// Applies a function to the number, given its value and derivative at [`Scalar::value`]
+    fn apply(self, value: f64, derivative: f64) -> Self;
+
+    fn abs(self) -> Self {
+        let x = self.value();
+        self.apply(x.abs(), x.signum())
+    }
+
+    fn sqrt(self) -> Self {
+        let y = self.value().sqrt();
+        self.apply(y, 0.5 / y)
+    }
+
+    fn powi(self, n: i32) -> Self {
+        let x = self.value();
+        if n == 0 {
+            // avoid 0 * x^-1, which is not a number for x = 0
+            return self.apply(1., 0.);
+        }
+        self.apply(x.powi(n), n as f64 * x.powi(n - 1))
+    }
+
+    fn powf(self, n: f64) -> Self {
+        let x = self.value();
+        self.apply(x.powf(n), n * x.powf(n - 1.))
+    }
+
+    fn exp(self) -> Self {
+        let y = self.value().exp();
+        self.apply(y, y)
+    }
+
+    fn ln(self) -> Self {
+        let x = self.value();
+        self.apply(x.ln(), 1. / x)
+    }
+
+    fn log10(self) -> Self {
+        let x = self.value();
+        self.apply(x.log10(), 1. / (x * LN_10))
+    }
+
+    fn log2(self) -> Self {
+        let x = self.value();
+        self.apply(x.log2(), 1. / (x * LN_2))
+    }
+
+    fn recip(self) -> Self {
+        let x = self.value();
+        self.apply(1. / x, -1. / (x * x))
+    }
+}
+
+impl Scalar for f64 {
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+
+    fn value(&self) -> f64 {
+        *self
+    }
+
+    fn is_constant(&self) -> bool {
+        true
+    }
+
+    fn apply(self, value: f64, _: f64) -> Self {
+        value
+    }
+
+    // avoid computing derivatives which are thrown away
+
+    fn abs(self) -> Self {
+        f64::abs(self)
+    }
+
+    fn sqrt(self) -> Self {
+        f64::sqrt(self)
+    }
+
+    fn powi(self, n: i32) -> Self {
+        f64::powi(self, n)
+    }
+
+    fn powf(self, n: f64) -> Self {
+        f64::powf(self, n)
+    }
+
+    fn exp(self) -> Self {
+        f64::exp(self)
+    }
+
+    fn ln(self) -> Self {
+        f64::ln(self)
+    }
+
+    fn log10(self) -> Self {
+        f64::log10(self)
+    }
+
+    fn log2(self) -> Self {
+        f64::log2(self)
+    }
+
+    fn recip(self) -> Self {
+        f64::recip(self)
+    }
+}
+
+/// Dual number `value + derivative ε` with `ε^2 = 0`
+///
+/// Seeding an input with [`Dual::variable`] yields the derivative of every result
+/// with respect to that input.
+///
+/// # Example
+/// ```
+/// use rimulation::{polynome::poly, scalar::Dual};
+///
+/// let y = poly(Dual::variable(2.), &[1., 2., 3.]);
+/// assert_eq!(y.value, 17.);
+/// assert_eq!(y.derivative, 14.);
+/// ```
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct Dual {
+    pub value: f64,
+    pub derivative: f64,
+}
+
+impl Dual {
+    /// The variable to differentiate with respect to
+    pub fn variable(value: f64) -> Self {
+        Self {
+            value,
+            derivative: 1.,
+        }
+    }
+
+    pub fn constant(value: f64) -> Self {
+        Self {
+            value,
+            derivative: 0.,
+        }
+    }
+}
+
+impl Scalar for Dual {
+    fn from_f64(value: f64) -> Self {
+        Self::constant(value)
+    }
+
+    fn value(&self) -> f64 {
+        self.value
+    }
+
+    fn is_constant(&self) -> bool {
+        self.derivative == 0.
+    }
+
+    fn apply(self, value: f64, derivative: f64) -> Self {
+        Self {
+            value,
+            derivative: derivative * self.derivative,
+        }
+    }
+}
+
+impl PartialOrd for Dual {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        self.value.partial_cmp(&other.value)
+    }
+}
+
+impl Zero for Dual {
+    fn zero() -> Self {
+        Self::constant(0.)
+    }
+
+    fn is_zero(&self) -> bool {
+        self.value == 0. && self.derivative == 0.
+    }
+}
+
+impl One for Dual {
+    fn one() -> Self {
+        Self::constant(1.)
+    }
+}
+
+impl Sum for Dual {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Self::zero(), |acc, x| acc + x)
+    }
+}
+
+impl Neg for Dual {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self {
+            value: -self.value,
+            derivative: -self.derivative,
+        }
+    }
+}
+
+impl Add for Dual {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self {
+            value: self.value + rhs.value,
+            derivative: self.derivative + rhs.derivative,
+        }
+    }
+}
+
+impl Sub for Dual {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self {
+            value: self.value - rhs.value,
+            derivative: self.derivative - rhs.derivative,
+        }
+    }
+}
+
+impl Mul for Dual {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self {
+            value: self.value * rhs.value,
+            derivative: self.derivative * rhs.value + self.value * rhs.derivative,
+        }
+    }
+}
+
+impl Div for Dual {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        Self {
+            value: self.value / rhs.value,
+            derivative: (self.derivative * rhs.value - self.value * rhs.derivative)
+                / (rhs.value * rhs.value),
+        }
+    }
+}
+
+impl Add<f64> for Dual {
+    type Output = Self;
+
+    fn add(self, rhs: f64) -> Self {
+        Self {
+            value: self.value + rhs,
+            derivative: self.derivative,
+        }
+    }
+}
+
+impl Sub<f64> for Dual {
+    type Output = Self;
+
+    fn sub(self, rhs: f64) -> Self {
+        Self {
+            value: self.value - rhs,
+            derivative: self.derivative,
+        }
+    }
+}
+
+impl Mul<f64> for Dual {
+    type Output = Self;
+
+    fn mul(self, rhs: f64) -> Self {
+        Self {
+            value: self.value * rhs,
+            derivative: self.derivative * rhs,
+        }
+    }
+}
+
+impl Div<f64> for Dual {
+    type Output = Self;
+
+    fn div(self, rhs: f64) -> Self {
+        Self {
+            value: self.value / rhs,
+            derivative: self.derivative / rhs,
+        }
+    }
+}
+
+impl AddAssign for Dual {
+    fn add_assign(&mut self, rhs: Self) {
+        *self = *self + rhs;
+    }
+}
+
+impl SubAssign for Dual {
+    fn sub_assign(&mut self, rhs: Self) {
+        *self = *self - rhs;
+    }
+}
+
+impl MulAssign for Dual {
+    fn mul_assign(&mut self, rhs: Self) {
+        *self = *self * rhs;
+    }
+}
+
+impl DivAssign for Dual {
+    fn div_assign(&mut self, rhs: Self) {
+        *self = *self / rhs;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    fn assert_derivative(f: impl Fn(Dual) -> Dual, df: impl Fn(f64) -> f64, x: f64) {
+        let y = f(Dual::variable(x));
+        assert_relative_eq!(y.derivative, df(x), max_relative = 1e-12);
+    }
+
+    #[test]
+    fn arithmetic() {
+        let x = Dual::variable(3.);
+        let c = Dual::constant(2.);
+
+        assert_eq!(
+            x + c,
+            Dual {
+                value: 5.,
+                derivative: 1.
+            }
+        );
+        assert_eq!(
+            x - c * x,
+            Dual {
+                value: -3.,
+                derivative: -1.
+            }
+        );
+        assert_eq!(
+            x * x,
+            Dual {
+                value: 9.,
+                derivative: 6.
+            }
+        );
+        assert_eq!(
+            c / x,
+            Dual {
+                value: 2. / 3.,
+                derivative: -2. / 9.
+            }
+        );
+        assert_eq!(
+            -x * 2. + 1.,
+            Dual {
+                value: -5.,
+                derivative: -2.
+            }
+        );
+        assert!(c < x);
+    }
+
+    #[test]
+    fn elementary_functions() {
+        for x in [0.3, 1., 2.5] {
+            assert_derivative(|x| x.sqrt(), |x| 0.5 / x.sqrt(), x);
+            assert_derivative(|x| x.powi(3), |x| 3. * x * x, x);
+            assert_derivative(|x| x.powi(-2), |x| -2. / (x * x * x), x);
+            assert_derivative(|x| x.powf(1.11), |x| 1.11 * x.powf(0.11), x);
+            assert_derivative(|x| x.exp(), |x| x.exp(), x);
+            assert_derivative(|x| x.ln(), |x| 1. / x, x);
+            assert_derivative(|x| x.log10(), |x| 1. / (x * LN_10), x);
+            assert_derivative(|x| x.log2(), |x| 1. / (x * LN_2), x);
+            assert_derivative(|x| x.recip(), |x| -1. / (x * x), x);
+            assert_derivative(|x| (-x).abs(), |_| 1., x);
+        }
+    }
+
+    #[test]
+    fn chain_rule() {
+        // d/dx sqrt(ln(x^2 + 1)) = x / ((x^2 + 1) sqrt(ln(x^2 + 1)))
+        assert_derivative(
+            |x| (x * x + 1.).ln().sqrt(),
+            |x| x / ((x * x + 1.) * (x * x + 1.).ln().sqrt()),
+            1.7,
+        );
+    }
+}
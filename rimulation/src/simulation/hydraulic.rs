@@ -1,7 +1,504 @@
+//! Hydraulic equations of a network, their residual and sparse Jacobian
+//!
+//! The functions are generic over [`Scalar`], so that the residual can also be differentiated
+//! with dual numbers, e.g. with respect to pipe parameters.
+
+use anyhow::{anyhow, Error};
 use nalgebra::DVector;
+use nalgebra_sparse::{factorization::CscCholesky, CooMatrix, CscMatrix, CsrMatrix};
+
+use super::matrices::{mul, mul_transpose, Matrices};
+use crate::{
+    constants::G,
+    fluid::FluidProperties,
+    friction::{self, LAMINAR_BOUNDARY},
+    scalar::Scalar,
+    types::{
+        formats::custom::Settings,
+        network::{HydraulicPipeParameters, Network},
+    },
+};
+
+/// Computes the head loss h \[m\] along a pipe with velocity v \[m/s\]
+/// and kinematic viscosity nu \[m^2/s\]
+///
+/// `h = (lambda L / D + zeta) v |v| / (2 g)`, with the Darcy friction factor lambda.
+/// The loss is negative for negative velocities.
+/// Laminar flow is computed in closed form, so that there is no division by zero at v = 0.
+pub fn head_loss<T: Scalar>(pipe: &impl HydraulicPipeParameters<T>, nu: T, v: T) -> T {
+    let re = pipe.reynolds(v, nu);
+
+    let friction = if re.value() < LAMINAR_BOUNDARY {
+        // lambda v |v| = 64 nu / (|v| D) v |v| = 64 nu v / D
+        nu * v / pipe.diameter() * pipe.length() / pipe.diameter() * 64.
+    } else {
+        friction::darcy_friction(&pipe.friction_model(), re, pipe.relative_roughness())
+            * pipe.length()
+            / pipe.diameter()
+            * v
+            * v.abs()
+    };
+
+    (friction + pipe.zeta() * v * v.abs()) / (2. * G)
+}
+
+/// Computes the derivative of [`head_loss`] with respect to the velocity
+pub fn head_loss_derivative<T: Scalar>(pipe: &impl HydraulicPipeParameters<T>, nu: T, v: T) -> T {
+    let re = pipe.reynolds(v, nu);
+
+    let friction = if re.value() < LAMINAR_BOUNDARY {
+        nu / pipe.diameter() * pipe.length() / pipe.diameter() * 64.
+    } else {
+        let model = pipe.friction_model();
+        let lambda = friction::darcy_friction(&model, re, pipe.relative_roughness());
+        let dlambda = friction::darcy_friction_derivative(&model, re, pipe.relative_roughness());
+
+        // d/dv (lambda(Re(v)) v |v|) = dlambda/dRe * D / nu * v^2 + 2 lambda |v|
+        pipe.length() / pipe.diameter()
+            * (dlambda * pipe.diameter() / nu * v * v + lambda * v.abs() * 2.)
+    };
+
+    (friction + pipe.zeta() * v.abs() * 2.) / (2. * G)
+}
+
+/// Kinematic viscosity of every edge at the mean energy density of its nodes
+fn edge_viscosities<T, PipeParameters, Fluid>(
+    network: &Network<PipeParameters, Fluid>,
+    e: &DVector<T>,
+) -> Vec<T>
+where
+    T: Scalar,
+    Fluid: FluidProperties,
+{
+    network
+        .edges()
+        .map(|edge| network.fluid.viscosity((e[edge.src] + e[edge.tgt]) / 2.))
+        .collect()
+}
+
+/// Computes the residual of the hydraulic equations
+///
+/// The unknowns are the velocities v \[m/s\] of all edges and the heads h \[m\] of the demand nodes.
+/// The heads `h_pressure` of the pressure nodes
+/// and the volume flows q \[m^3/s\] drawn at the demand nodes are given.
+/// The first rows are the momentum balances `h_loss(v) + A_I h = 0` of the edges,
+/// the following rows the mass balances `A_R^T (A v) - q = 0` of the demand nodes,
+/// where A are the cross-sections.
+/// Energy densities e \[GJ/m^3\] of all nodes determine the viscosities.
+pub fn residual<T, PipeParameters, Fluid>(
+    network: &Network<PipeParameters, Fluid>,
+    matrices: &Matrices,
+    e: &DVector<T>,
+    v: &DVector<T>,
+    h: &DVector<T>,
+    h_pressure: &DVector<T>,
+    q: &DVector<T>,
+) -> DVector<T>
+where
+    T: Scalar,
+    PipeParameters: HydraulicPipeParameters<T>,
+    Fluid: FluidProperties,
+{
+    let nus = edge_viscosities(network, e);
+
+    let losses = DVector::from_iterator(
+        network.num_edges(),
+        network
+            .edge_parameters()
+            .zip(&nus)
+            .zip(v.iter())
+            .map(|((pipe, nu), v)| head_loss(pipe, *nu, *v)),
+    );
+    let flows = DVector::from_iterator(
+        network.num_edges(),
+        network
+            .edge_parameters()
+            .zip(v.iter())
+            .map(|(pipe, v)| pipe.cross_section() * *v),
+    );
+
+    let momentum = losses + mul(&matrices.ar, h) + mul(&matrices.arp, h_pressure);
+    let mass = mul_transpose(&matrices.ar, &flows) - q;
+
+    concat(&momentum, &mass)
+}
+
+/// Stacks two vectors on top of each other
+fn concat<T: Scalar>(top: &DVector<T>, bottom: &DVector<T>) -> DVector<T> {
+    DVector::from_iterator(
+        top.len() + bottom.len(),
+        top.iter().chain(bottom.iter()).copied(),
+    )
+}
+
+/// Derivatives of the head losses with respect to the velocities, the diagonal of `dh_loss/dv`
+fn head_loss_derivatives<T, PipeParameters, Fluid>(
+    network: &Network<PipeParameters, Fluid>,
+    e: &DVector<T>,
+    v: &DVector<T>,
+) -> Vec<T>
+where
+    T: Scalar,
+    PipeParameters: HydraulicPipeParameters<T>,
+    Fluid: FluidProperties,
+{
+    network
+        .edge_parameters()
+        .zip(edge_viscosities(network, e))
+        .zip(v.iter())
+        .map(|((pipe, nu), v)| head_loss_derivative(pipe, nu, *v))
+        .collect()
+}
+
+/// Computes the sparse Jacobian of [`residual`] with respect to the velocities
+/// and the heads of the demand nodes
+///
+/// ```text
+/// | dh_loss/dv   A_R |
+/// | A_R^T A      0   |
+/// ```
+pub fn jacobian<T, PipeParameters, Fluid>(
+    network: &Network<PipeParameters, Fluid>,
+    matrices: &Matrices,
+    e: &DVector<T>,
+    v: &DVector<T>,
+) -> CsrMatrix<T>
+where
+    T: Scalar,
+    PipeParameters: HydraulicPipeParameters<T>,
+    Fluid: FluidProperties,
+{
+    let num_edges = network.num_edges();
+    let size = num_edges + network.demand_nodes.len();
+
+    let mut jacobian = CooMatrix::new(size, size);
+    for (i, dloss) in head_loss_derivatives(network, e, v).into_iter().enumerate() {
+        jacobian.push(i, i, dloss);
+    }
+
+    let cross_sections: Vec<_> = network
+        .edge_parameters()
+        .map(|pipe| pipe.cross_section())
+        .collect();
+    for (i, j, value) in matrices.ar.triplet_iter() {
+        jacobian.push(i, num_edges + j, T::from_f64(*value));
+        jacobian.push(num_edges + j, i, cross_sections[i] * *value);
+    }
+
+    CsrMatrix::from(&jacobian)
+}
+
+/// Solves `J x = r` for the [`jacobian`] J, e.g. to compute a Newton step
+///
+/// Eliminating the velocities leaves the heads of the demand nodes as unknowns of
+/// `S x_h = A_R^T A D^-1 r_v - r_h` with the Schur complement `S = A_R^T A D^-1 A_R`,
+/// where `D = dh_loss/dv`. The velocities follow as `x_v = D^-1 (r_v - A_R x_h)`.
+/// Head losses grow with the velocity, so S is symmetric positive definite
+/// and is factorised by a sparse Cholesky decomposition.
+pub fn solve_jacobian<PipeParameters, Fluid>(
+    network: &Network<PipeParameters, Fluid>,
+    matrices: &Matrices,
+    e: &DVector<f64>,
+    v: &DVector<f64>,
+    r: &DVector<f64>,
+) -> Result<DVector<f64>, Error>
+where
+    PipeParameters: HydraulicPipeParameters,
+    Fluid: FluidProperties,
+{
+    let num_edges = network.num_edges();
+    let num_demand_nodes = network.demand_nodes.len();
+
+    let r_v = r.rows(0, num_edges).into_owned();
+    let r_h = r.rows(num_edges, num_demand_nodes).into_owned();
+
+    let dlosses = DVector::from_vec(head_loss_derivatives(network, e, v));
+    // A D^-1
+    let weights = DVector::from_iterator(
+        num_edges,
+        network
+            .edge_parameters()
+            .zip(dlosses.iter())
+            .map(|(pipe, dloss)| pipe.cross_section() / dloss),
+    );
+
+    // every edge couples the demand nodes it connects
+    let mut schur = CooMatrix::new(num_demand_nodes, num_demand_nodes);
+    for (i, row) in matrices.ar.row_iter().enumerate() {
+        for (j, a) in row.col_indices().iter().zip(row.values()) {
+            for (k, b) in row.col_indices().iter().zip(row.values()) {
+                schur.push(*j, *k, a * weights[i] * b);
+            }
+        }
+    }
+
+    let rhs = mul_transpose(&matrices.ar, &weights.component_mul(&r_v)) - r_h;
+    let x_h = if num_demand_nodes == 0 {
+        rhs
+    } else {
+        CscCholesky::factor(&CscMatrix::from(&schur))
+            .map_err(|error| anyhow!("could not factorise hydraulic equations: {}", error))?
+            .solve(&rhs)
+            .column(0)
+            .into_owned()
+    };
+    let x_v = (r_v - mul(&matrices.ar, &x_h)).component_div(&dlosses);
+
+    Ok(concat(&x_v, &x_h))
+}
+
+/// Solves the hydraulic equations of [`residual`] by Newton's method
+///
+/// Starts from the velocities and heads in `initial`
+/// and iterates until the largest residual is below the tolerance of the settings.
+/// Returns the velocities \[m/s\] of the edges and the heads \[m\] of the demand nodes.
+pub fn solve<PipeParameters, Fluid>(
+    network: &Network<PipeParameters, Fluid>,
+    matrices: &Matrices,
+    e: &DVector<f64>,
+    h_pressure: &DVector<f64>,
+    q: &DVector<f64>,
+    initial: (DVector<f64>, DVector<f64>),
+    settings: &Settings,
+) -> Result<(DVector<f64>, DVector<f64>), Error>
+where
+    PipeParameters: HydraulicPipeParameters,
+    Fluid: FluidProperties,
+{
+    let (mut v, mut h) = initial;
+
+    for _ in 0..settings.num_iterations {
+        let r = residual(network, matrices, e, &v, &h, h_pressure, q);
+        if r.amax() < settings.tolerance {
+            return Ok((v, h));
+        }
+
+        let step = solve_jacobian(network, matrices, e, &v, &r)?;
+        v -= step.rows(0, v.len());
+        h -= step.rows(v.len(), h.len());
+    }
+
+    Err(anyhow!(
+        "hydraulic equations did not converge within {} iterations",
+        settings.num_iterations
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+    use nalgebra::DMatrix;
+
+    use super::*;
+    use crate::{
+        friction::Friction,
+        scalar::Dual,
+        types::{
+            formats::custom::test_util::DUMMY_CUSTOM_POSITION,
+            network::{test::DUMMY_CONST_SIGNAL, Edge, FullPipeParameters, Node},
+        },
+        water::Water,
+    };
+
+    fn pipe(diameter: f64, friction_model: Friction) -> FullPipeParameters {
+        FullPipeParameters {
+            length: 100.,
+            diameter,
+            transmittance: 1.,
+            roughness: 1e-4,
+            zeta: 0.5,
+            friction_model,
+        }
+    }
+
+    #[test]
+    fn head_loss_derivative_matches_finite_differences() {
+        let nu = 4.7e-7;
+
+        for friction_model in [Friction::Colebrook, Friction::Serghides] {
+            let pipe = pipe(0.1, friction_model);
+
+            // laminar, transition and turbulent flow in both directions
+            for v in [1e-3f64, 5e-3, 1.2e-2, 1.5e-2, 0.5, 2., -5e-3, -1.5e-2, -2.] {
+                let dv = v.abs() * 1e-6;
+                let slope =
+                    (head_loss(&pipe, nu, v + dv) - head_loss(&pipe, nu, v - dv)) / (2. * dv);
+
+                assert_relative_eq!(
+                    head_loss_derivative(&pipe, nu, v),
+                    slope,
+                    max_relative = 1e-5
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sensitivity_to_diameter() {
+        let nu = 4.7e-7;
+
+        for friction_model in [Friction::Colebrook, Friction::Haaland] {
+            for v in [5e-3, 1.5e-2, 2.] {
+                let diameter = 0.1;
+                let dual = FullPipeParameters {
+                    length: Dual::constant(100.),
+                    diameter: Dual::variable(diameter),
+                    transmittance: Dual::constant(1.),
+                    roughness: Dual::constant(1e-4),
+                    zeta: Dual::constant(0.5),
+                    friction_model,
+                };
+                let loss = head_loss(&dual, Dual::constant(nu), Dual::constant(v));
+
+                let dd = 1e-7;
+                let slope = (head_loss(&pipe(diameter + dd, friction_model), nu, v)
+                    - head_loss(&pipe(diameter - dd, friction_model), nu, v))
+                    / (2. * dd);
+
+                assert_eq!(
+                    loss.value,
+                    head_loss(&pipe(diameter, friction_model), nu, v)
+                );
+                assert_relative_eq!(loss.derivative, slope, max_relative = 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn head_loss_is_continuous_at_zero() {
+        let pipe = pipe(0.1, Friction::default());
+
+        assert_eq!(head_loss(&pipe, 4.7e-7, 0.), 0.);
+        assert!(head_loss_derivative(&pipe, 4.7e-7, 0.) > 0.);
+    }
+
+    /// A source feeding a cycle of three demand nodes
+    fn create_test_net() -> Network<FullPipeParameters> {
+        let nodes = (0..3)
+            .map(|i| Node::Zero {
+                name: format!("N{}", i),
+                position: DUMMY_CUSTOM_POSITION,
+            })
+            .chain([Node::Pressure {
+                name: String::from("N3"),
+                pressure: DUMMY_CONST_SIGNAL,
+                temperature: DUMMY_CONST_SIGNAL,
+                position: DUMMY_CUSTOM_POSITION,
+            }])
+            .collect();
+        let edges = [(3, 0), (0, 1), (1, 2), (0, 2)]
+            .map(|(src, tgt)| Edge { src, tgt })
+            .to_vec();
+        let edge_parameters = vec![
+            pipe(0.2, Friction::Colebrook),
+            pipe(0.1, Friction::Serghides),
+            pipe(0.05, Friction::Haaland),
+            pipe(0.08, Friction::SwameeJain),
+        ];
+
+        Network::try_from_feed(nodes, edges, edge_parameters)
+            .expect("could not compute network from feed nodes and edges")
+    }
+
+    fn energy_densities(network: &Network<FullPipeParameters>) -> DVector<f64> {
+        DVector::from_fn(network.num_nodes(), |i, _| {
+            Water
+                .energy_density(60. + i as f64)
+                .expect("could not compute energy density")
+        })
+    }
+
+    #[test]
+    fn jacobian_matches_finite_differences() {
+        let network = create_test_net();
+        let matrices = Matrices::try_from(&network).expect("could not compute matrices");
+        let e = energy_densities(&network);
+
+        // laminar, transition, turbulent and reversed flow
+        let v = DVector::from_vec(vec![1.5, 4e-3, 3e-2, -0.8]);
+        let h = DVector::from_vec(vec![40., 38., 35.]);
+        let h_pressure = DVector::from_vec(vec![50.]);
+        let q = DVector::from_vec(vec![1e-3, 2e-3, 3e-3]);
+
+        let x = concat(&v, &h);
+        let f = |x: &DVector<f64>| {
+            let v = x.rows(0, network.num_edges()).into_owned();
+            let h = x.rows(network.num_edges(), 3).into_owned();
+            residual(&network, &matrices, &e, &v, &h, &h_pressure, &q)
+        };
+
+        let jacobian = DMatrix::from(&jacobian(&network, &matrices, &e, &v));
+        assert_eq!(jacobian.shape(), (7, 7));
+
+        for j in 0..x.len() {
+            let dx = x[j].abs() * 1e-6;
+            let mut right = x.clone();
+            right[j] += dx;
+            let mut left = x.clone();
+            left[j] -= dx;
+
+            let slope = (f(&right) - f(&left)) / (2. * dx);
+            for i in 0..x.len() {
+                assert_relative_eq!(
+                    jacobian[(i, j)],
+                    slope[i],
+                    epsilon = 1e-8,
+                    max_relative = 1e-5
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sparse_solve_matches_dense_solve() {
+        let network = create_test_net();
+        let matrices = Matrices::try_from(&network).expect("could not compute matrices");
+        let e = energy_densities(&network);
+
+        let v = DVector::from_vec(vec![1.5, 4e-3, 3e-2, -0.8]);
+        let r = DVector::from_vec(vec![1., -2., 0.5, 3., 1e-3, -2e-3, 4e-3]);
+
+        let x = solve_jacobian(&network, &matrices, &e, &v, &r).expect("could not solve");
+        let dense = DMatrix::from(&jacobian(&network, &matrices, &e, &v))
+            .lu()
+            .solve(&r)
+            .expect("could not solve dense system");
+
+        for (sparse, dense) in x.iter().zip(dense.iter()) {
+            assert_relative_eq!(sparse, dense, epsilon = 1e-12, max_relative = 1e-9);
+        }
+    }
+
+    #[test]
+    fn newton_converges() {
+        let network = create_test_net();
+        let matrices = Matrices::try_from(&network).expect("could not compute matrices");
+        let e = energy_densities(&network);
+
+        let h_pressure = DVector::from_vec(vec![50.]);
+        let q = DVector::from_vec(vec![1e-3, 2e-3, 3e-3]);
+        let settings = Settings {
+            feed_temperature: 60.,
+            return_temperature: 40.,
+            ground_temperature: 10.,
+            time_start: 0.,
+            time_end: 1.,
+            time_step: 10.,
+            ramp_time: 0.,
+            num_iterations: 50,
+            tolerance: 1e-10,
+        };
+
+        let initial = (DVector::from_element(4, 0.1), DVector::from_element(3, 50.));
+        let (v, h) = solve(&network, &matrices, &e, &h_pressure, &q, initial, &settings)
+            .expect("could not solve hydraulic equations");
 
-use super::matrices::Matrices;
+        let r = residual(&network, &matrices, &e, &v, &h, &h_pressure, &q);
+        assert!(r.amax() < 1e-10);
 
-pub fn get_velocities(q: DVector<f64>, e: DVector<f64>, matrices: &Matrices) -> DVector<f64> {
-    todo!()
+        // the source delivers all demands and the heads fall along the flow
+        let source_flow = v[0] * network.edge_parameters[0].cross_section();
+        assert_relative_eq!(source_flow, q.sum(), max_relative = 1e-8);
+        assert!(h.iter().all(|h| *h < 50.));
+    }
 }
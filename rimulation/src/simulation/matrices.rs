@@ -1,145 +1,54 @@
-use anyhow::Error;
-use nalgebra::{stack, DMatrix, DVector};
-
-use crate::{
-    transition::transition_cubic,
-    types::network::{HydraulicPipeParameters, Network},
-    water,
-};
-
-fn reynold(edge: &impl HydraulicPipeParameters, e: f64, v: f64) -> f64 {
-    v.abs() * edge.length() / water::viscosity(e)
-}
-
-// Reynolds number transition boundaries for laminar to turbulent flow
-const DARCY_LEFT_BOUNDARY: f64 = 2_000.;
-const DARCY_RIGHT_BOUNDARY: f64 = 4_000.;
-
-/// Calculates the Darcy friction factor using:
-/// - Laminar flow (Re < 2000): f = 64/Re
-/// - Turbulent flow (Re > 4000): Serghide's solution
-/// - Transition (2000 <= Re <= 4000): Cubic interpolation
-fn darcy_friction(edge: &impl HydraulicPipeParameters, re: f64) -> f64 {
-    let laminar = |re| 64. / re;
-
-    // serghides's solution
-    let turbulent = |re: f64| {
-        let summand = edge.roughness() / (3.7 * edge.diameter());
+//! Sparse incidence matrices of a network, see [`Matrices`]
 
-        let a = -(summand + 12. / re).log2();
-        let b = -(summand + 2.51 * a / re).log2();
-        let c = -(summand + 2.51 * b / re).log2();
+use std::ops::Range;
 
-        1. / (a - ((b - a).powi(2) / (c - 2. * b + a))).powi(2)
-    };
-
-    if re > DARCY_RIGHT_BOUNDARY {
-        turbulent(re)
-    } else if re < DARCY_LEFT_BOUNDARY {
-        laminar(re)
-    } else {
-        let dre = 1e-3;
-
-        let left_boundary_value = laminar(DARCY_LEFT_BOUNDARY);
-        let left_boundary_slope = (laminar(DARCY_LEFT_BOUNDARY + dre) - left_boundary_value) / dre;
-
-        let right_boundary_value = turbulent(DARCY_RIGHT_BOUNDARY);
-        let right_boundary_slope =
-            (turbulent(DARCY_RIGHT_BOUNDARY + dre) - right_boundary_value) / dre;
-
-        transition_cubic(
-            re,
-            DARCY_LEFT_BOUNDARY,
-            DARCY_RIGHT_BOUNDARY,
-            left_boundary_value,
-            left_boundary_slope,
-            right_boundary_value,
-            right_boundary_slope,
-        )
+use anyhow::Error;
+use nalgebra::{DMatrix, DVector};
+use nalgebra_sparse::{CooMatrix, CsrMatrix};
+
+use crate::{scalar::Scalar, types::network::Network};
+
+/// Incidence matrix of all edges with the nodes in `nodes`,
+/// the first node of the range is the first column
+fn incidence<PipeParameters, Fluid>(
+    network: &Network<PipeParameters, Fluid>,
+    nodes: Range<usize>,
+) -> CsrMatrix<f64> {
+    let mut incidence = CooMatrix::new(network.num_edges(), nodes.len());
+
+    for (i, edge) in network.edges().enumerate() {
+        if nodes.contains(&edge.src) {
+            incidence.push(i, edge.src - nodes.start, -1.);
+        }
+        if edge.tgt != edge.src && nodes.contains(&edge.tgt) {
+            incidence.push(i, edge.tgt - nodes.start, 1.);
+        }
     }
-}
 
-fn lambda<PipeParameters>(
-    network: &Network<PipeParameters>,
-    e: DVector<f64>,
-    v: DVector<f64>,
-) -> DMatrix<f64>
-where
-    PipeParameters: HydraulicPipeParameters,
-{
-    let lambda = DVector::from_iterator(
-        network.num_edges(),
-        network
-            .edges()
-            .zip(network.edge_parameters())
-            .enumerate()
-            .map(|(i, (edge, edge_parameters))| {
-                let v = v[i];
-                let e = (e[edge.src] + e[edge.tgt]) / 2.;
-
-                darcy_friction(edge_parameters, reynold(edge_parameters, e, v))
-            }),
-    );
-
-    DMatrix::from_diagonal(&lambda)
+    CsrMatrix::from(&incidence)
 }
 
-fn ar<PipeParameters>(network: &Network<PipeParameters>) -> DMatrix<f64> {
-    DMatrix::from_iterator(
-        network.num_edges(),
-        network.demand_nodes.len(),
-        (0..network.demand_nodes.len())
-            .flat_map(|i| {
-                network.edges().map(move |edge| {
-                    if edge.src == i {
-                        -1.
-                    } else if edge.tgt == i {
-                        1.
-                    } else {
-                        0.
-                    }
-                })
-            }),
-    )
+fn ar<PipeParameters, Fluid>(network: &Network<PipeParameters, Fluid>) -> CsrMatrix<f64> {
+    incidence(network, 0..network.demand_nodes.len())
 }
 
-fn arp<PipeParameters>(network: &Network<PipeParameters>) -> DMatrix<f64> {
-    let num_pressure_nodes = network.pressure_nodes.len();
-    let num_demand_nodes = network.demand_nodes.len();
+fn arp<PipeParameters, Fluid>(network: &Network<PipeParameters, Fluid>) -> CsrMatrix<f64> {
+    incidence(network, network.demand_nodes.len()..network.num_nodes())
+}
 
-    DMatrix::from_iterator(
-        network.num_edges(),
-        num_pressure_nodes,
-        (0..num_pressure_nodes)
-            .flat_map(|i| {
-                let i = num_demand_nodes + i;
-                network.edges().map(move |edge| {
-                    if edge.src == i {
-                        -1.
-                    } else if edge.tgt == i {
-                        1.
-                    } else {
-                        0.
-                    }
-                })
-            }),
-    )
+fn ai<PipeParameters, Fluid>(network: &Network<PipeParameters, Fluid>) -> CsrMatrix<f64> {
+    incidence(network, 0..network.num_nodes())
 }
 
-fn ai<PipeParameters>(network: &Network<PipeParameters>) -> DMatrix<f64> {
-    let ar = ar(network);
-    let arp = arp(network);
+fn at<PipeParameters, Fluid>(network: &Network<PipeParameters, Fluid>) -> CsrMatrix<f64> {
+    let num_demand_nodes = network.demand_nodes.len();
+    let mut at = CooMatrix::new(num_demand_nodes, network.num_edges());
 
-    stack![ar, arp]
-}
+    for i in 0..num_demand_nodes.min(network.num_edges()) {
+        at.push(i, i, 1.);
+    }
 
-fn at<PipeParameters>(network: &Network<PipeParameters>) -> DMatrix<f64> {
-    DMatrix::from_iterator(
-        network.demand_nodes.len(),
-        network.num_edges(),
-        (0..network.num_edges())
-            .flat_map(|i| (0..network.demand_nodes.len()).map(move |j| if i == j { 1. } else { 0. })),
-    )
+    CsrMatrix::from(&at)
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -152,17 +61,19 @@ enum MatrixError {
     MissingEdge(usize, usize),
 }
 
-fn ac<PipeParameters>(network: &Network<PipeParameters>) -> Result<DMatrix<f64>, Error> {
-    let mut ac = DMatrix::from_element(network.num_cycles(), network.num_nodes(), 0.);
-    let mut set_matrix_element = |i, j, v| {
+fn ac<PipeParameters, Fluid>(
+    network: &Network<PipeParameters, Fluid>,
+) -> Result<CsrMatrix<f64>, Error> {
+    let mut ac = CooMatrix::new(network.num_cycles(), network.num_edges());
+    let mut push = |i, j, v| {
         (i < ac.nrows() && j < ac.ncols())
-            .then(|| ac[(i, j)] = v)
+            .then(|| ac.push(i, j, v))
             .ok_or(MatrixError::IndexOutOfBounds { i, j })
     };
 
     for (i, cycle_edge) in network.cycle_edges.iter().enumerate() {
         let j = network.spanning_tree_edges.len() + i;
-        set_matrix_element(i, j, 1.)?;
+        push(i, j, 1.)?;
 
         let mut walk_cycle = |c: &usize, invert| -> Result<(), Error> {
             let mut c = c;
@@ -177,7 +88,7 @@ fn ac<PipeParameters>(network: &Network<PipeParameters>) -> Result<DMatrix<f64>,
                     .get(&(*p, *c))
                     .ok_or(MatrixError::MissingEdge(*p, *c))?;
 
-                set_matrix_element(i, *j, if *reversed != invert { -1. } else { 1. })?;
+                push(i, *j, if *reversed != invert { -1. } else { 1. })?;
 
                 c = p;
             }
@@ -189,40 +100,66 @@ fn ac<PipeParameters>(network: &Network<PipeParameters>) -> Result<DMatrix<f64>,
         walk_cycle(&cycle_edge.tgt, true)?;
     }
 
-    Ok(ac)
+    // edges on both paths to the root are walked in opposite directions and cancel
+    Ok(CsrMatrix::from(&ac).filter(|_, _, v| *v != 0.))
 }
 
-fn dinv<PipeParameters>(network: &Network<PipeParameters>) -> DMatrix<f64>
-where
-    PipeParameters: HydraulicPipeParameters,
-{
-    DMatrix::from_diagonal(&DVector::from_iterator(
-        network.num_edges(),
-        network
-            .edge_parameters()
-            .map(|edge_parameters| 1. / edge_parameters.diameter()),
-    ))
+/// Computes the product `matrix x` of a sparse matrix with a vector of any scalar type
+pub fn mul<T: Scalar>(matrix: &CsrMatrix<f64>, x: &DVector<T>) -> DVector<T> {
+    let mut product = DVector::zeros(matrix.nrows());
+    for (i, j, value) in matrix.triplet_iter() {
+        product[i] += x[j] * *value;
+    }
+
+    product
 }
 
-/// Stores the matrices used in hydraulic and thermal network calculations
+/// Computes the product `matrix^T x` of a transposed sparse matrix with a vector of any scalar type
+pub fn mul_transpose<T: Scalar>(matrix: &CsrMatrix<f64>, x: &DVector<T>) -> DVector<T> {
+    let mut product = DVector::zeros(matrix.ncols());
+    for (i, j, value) in matrix.triplet_iter() {
+        product[j] += x[i] * *value;
+    }
+
+    product
+}
+
+/// Stores the sparse matrices used in hydraulic and thermal network calculations
+///
+/// All matrices are built in a single pass over the edges.
+/// Use [`Matrices::dense`] to inspect them.
 pub struct Matrices {
     /// Incidence matrix for the entire network
-    pub ai: DMatrix<f64>,
+    pub ai: CsrMatrix<f64>,
     /// Reduced incidence matrix for demand nodes
-    pub ar: DMatrix<f64>,
+    pub ar: CsrMatrix<f64>,
     /// Reduced incidence matrix for pressure nodes
-    pub arp: DMatrix<f64>,
+    pub arp: CsrMatrix<f64>,
     /// Identity matrix of all demand nodes
     /// concatenated with 0s to have as much columns as the network has edges
-    pub at: DMatrix<f64>,
+    pub at: CsrMatrix<f64>,
     /// Cycle incidence matrix (includes information about orientation)
-    pub ac: DMatrix<f64>,
+    pub ac: CsrMatrix<f64>,
+}
+
+impl Matrices {
+    /// Dense copies of all matrices by name, for debugging only
+    pub fn dense(&self) -> [(&'static str, DMatrix<f64>); 5] {
+        [
+            ("ai", &self.ai),
+            ("ar", &self.ar),
+            ("arp", &self.arp),
+            ("at", &self.at),
+            ("ac", &self.ac),
+        ]
+        .map(|(name, matrix)| (name, DMatrix::from(matrix)))
+    }
 }
 
-impl<T> TryFrom<&Network<T>> for Matrices {
+impl<T, F> TryFrom<&Network<T, F>> for Matrices {
     type Error = Error;
 
-    fn try_from(network: &Network<T>) -> Result<Self, Self::Error> {
+    fn try_from(network: &Network<T, F>) -> Result<Self, Self::Error> {
         let ar = ar(network);
         let arp = arp(network);
         let ai = ai(network);
@@ -245,7 +182,8 @@ mod tests {
 
     use super::*;
 
-    use crate::types::network::{EmptyPipeParameters, FullPipeParameters};
+    use crate::friction;
+    use crate::types::network::{EmptyPipeParameters, FullPipeParameters, HydraulicPipeParameters};
     use crate::types::{
         formats::custom::test_util::DUMMY_CUSTOM_POSITION,
         network::{
@@ -301,7 +239,43 @@ mod tests {
             .expect("could not compute network from feed nodes and edges");
 
         let ac = ac(&network).expect("could not compute A_C matrix");
-        assert_eq!(ac, DMatrix::from_vec(1, 5, vec![-1., 1., -1., 1., 1.]));
+        assert_eq!(
+            DMatrix::from(&ac),
+            DMatrix::from_vec(1, 5, vec![-1., 1., -1., 1., 1.])
+        );
+    }
+
+    #[test]
+    fn compute_ac_with_more_edges_than_nodes() {
+        // two cycles that do not contain the root node 0 and share the edge (1, 2)
+        let nodes = (0..4)
+            .map(|i| Node::Zero {
+                name: format!("N{}", i),
+                position: DUMMY_CUSTOM_POSITION,
+            })
+            .collect();
+        let edges = [(0, 1), (1, 2), (2, 3), (3, 1), (1, 3), (2, 1)]
+            .map(|(src, tgt)| Edge { src, tgt })
+            .to_vec();
+
+        let edge_parameters = (0..edges.len()).map(|_| DUMMY_PIPE_PARAMETERS).collect();
+
+        let network = Network::try_from_feed(nodes, edges, edge_parameters)
+            .expect("could not compute network from feed nodes and edges");
+
+        let ac = DMatrix::from(&ac(&network).expect("could not compute A_C matrix"));
+        assert_eq!(ac.shape(), (network.num_cycles(), network.num_edges()));
+
+        // every cycle enters each of its nodes as often as it leaves it
+        let ai = DMatrix::from(&ai(&network));
+        assert_eq!(
+            &ac * ai,
+            DMatrix::zeros(network.num_cycles(), network.num_nodes())
+        );
+
+        // the edge to the root is on both paths of every cycle and cancels
+        let root_edge = network.edge_indices_by_connected_nodes[&(0, 1)].0;
+        assert!(ac.column(root_edge).iter().all(|v| *v == 0.));
     }
 
     #[test]
@@ -310,7 +284,7 @@ mod tests {
 
         let ar = ar(&network);
         assert_eq!(
-            ar,
+            DMatrix::from(&ar),
             DMatrix::from_row_slice(
                 5,
                 4,
@@ -331,7 +305,10 @@ mod tests {
         let network = create_test_net();
 
         let arp = arp(&network);
-        assert_eq!(arp, DMatrix::from_row_slice(5, 1, &[1., 0., 0., 1., 0.]))
+        assert_eq!(
+            DMatrix::from(&arp),
+            DMatrix::from_row_slice(5, 1, &[1., 0., 0., 1., 0.])
+        )
     }
 
     #[test]
@@ -340,7 +317,7 @@ mod tests {
 
         let ai = ai(&network);
         assert_eq!(
-            ai,
+            DMatrix::from(&ai),
             DMatrix::from_row_slice(
                 5,
                 5,
@@ -375,7 +352,7 @@ mod tests {
 
         let at = at(&network);
         assert_eq!(
-            at,
+            DMatrix::from(&at),
             DMatrix::from_row_slice(
                 4,
                 6,
@@ -405,13 +382,18 @@ mod tests {
             transmittance: 1.,
             roughness: 1e-2,
             zeta: 1.,
+            friction_model: Default::default(),
         };
 
         for i in 0..n {
             let re = l + i as f64 * (r - l) / n as f64;
-            let lambda = darcy_friction(edge_parameters, re);
+            let lambda = friction::darcy_friction(
+                &edge_parameters.friction_model(),
+                re,
+                edge_parameters.relative_roughness(),
+            );
 
-            file.write(format!("{} {}\n", re, lambda).as_bytes())
+            file.write_all(format!("{} {}\n", re, lambda).as_bytes())
                 .expect("could not write to file");
         }
     }
@@ -1,27 +1,29 @@
-mod hydraulic;
-mod matrices;
+pub mod hydraulic;
+pub mod matrices;
 
 
 use anyhow::{anyhow, Error};
 use matrices::Matrices;
-use nalgebra::DVector;
+use nalgebra::{DMatrix, DVector};
 
 use crate::{
+    constants::G,
+    fluid::FluidProperties,
+    scalar::Scalar,
     types::{
         formats::custom::Settings,
-        network::{FixedVelocityPipeParameters, Network, Node},
+        network::{FixedVelocityPipeParameters, HydraulicPipeParameters, Network, Node},
     },
-    water,
 };
 
-fn initial_energy_densities<T>(
-    network: &Network<T>,
+fn initial_energy_densities<T, F: FluidProperties>(
+    network: &Network<T, F>,
     settings: &Settings,
 ) -> Result<Vec<f64>, Error> {
     network
         .nodes()
         .map(|node| -> Result<f64, anyhow::Error> {
-            water::energy_density(match node {
+            network.fluid.energy_density(match node {
                 Node::Pressure { temperature, .. } => temperature.value_at(0.)?,
                 _ => settings.feed_temperature,
             })
@@ -29,55 +31,161 @@ fn initial_energy_densities<T>(
         .collect()
 }
 
-pub fn simulate<PipeParameters>(
-    network: Network<PipeParameters>,
-    settings: Settings,
-) -> Result<(), Error>
+/// Samples the demands of all demand nodes at every time step of the simulation
+///
+/// Row `i` contains the demands of the demand node `i`, zero nodes have no demand.
+fn sample_demands<T, F>(
+    network: &Network<T, F>,
+    settings: &Settings,
+) -> Result<DMatrix<f64>, Error> {
+    let n = settings.num_steps();
+
+    let rows = network
+        .demand_nodes
+        .iter()
+        .map(|node| match node {
+            Node::Pressure { .. } => {
+                unreachable!("there should be no pressure node included here")
+            }
+            Node::Demand { demand, .. } => Ok(demand
+                .values_on_grid(0., settings.time_step, n)?
+                .transpose()),
+            Node::Zero { .. } => Ok(DVector::zeros(n).transpose()),
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    if rows.is_empty() {
+        return Ok(DMatrix::zeros(0, n));
+    }
+
+    Ok(DMatrix::from_rows(&rows))
+}
+
+/// Samples the heads \[m\] of all pressure nodes at every time step of the simulation
+///
+/// Row `i` contains the heads of the pressure node `i`,
+/// the pressures are converted with the density at the energy densities e \[GJ/m^3\] of the nodes.
+fn sample_pressure_heads<T, F: FluidProperties>(
+    network: &Network<T, F>,
+    settings: &Settings,
+    e: &DVector<f64>,
+) -> Result<DMatrix<f64>, Error> {
+    let n = settings.num_steps();
+    let num_demand_nodes = network.demand_nodes.len();
+
+    let rows = network
+        .pressure_nodes
+        .iter()
+        .enumerate()
+        .map(|(i, node)| match node {
+            Node::Pressure { pressure, .. } => {
+                let density = network
+                    .fluid
+                    .density(network.fluid.temperature(e[num_demand_nodes + i]));
+                let pressures = pressure.values_on_grid(0., settings.time_step, n)?;
+                Ok(pressures.transpose() / (density * G))
+            }
+            _ => unreachable!("there should be only pressure nodes included here"),
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    if rows.is_empty() {
+        return Ok(DMatrix::zeros(0, n));
+    }
+
+    Ok(DMatrix::from_rows(&rows))
+}
+
+/// Result of [`simulate`]
+pub struct HydraulicResult {
+    /// Velocities \[m/s\] of the edges, one column per time step
+    pub velocities: DMatrix<f64>,
+    /// Heads \[m\] of the demand nodes, one column per time step
+    pub heads: DMatrix<f64>,
+}
+
+/// Solves the hydraulic equations at every time step of the simulation
+///
+/// The heat demands \[kW\] of the demand nodes are drawn as volume flows that are cooled down
+/// from the feed to the return temperature of the settings.
+/// The energy densities stay at their initial values.
+/// Newton's method starts from the solution of the previous time step.
+pub fn simulate<PipeParameters, Fluid>(
+    network: &Network<PipeParameters, Fluid>,
+    settings: &Settings,
+) -> Result<HydraulicResult, Error>
 where
-    PipeParameters: std::fmt::Debug,
+    PipeParameters: HydraulicPipeParameters,
+    Fluid: FluidProperties,
 {
-    let e = DVector::from_vec(initial_energy_densities(&network, &settings)?);
+    let e = DVector::from_vec(initial_energy_densities(network, settings)?);
 
-    let matrices = Matrices::try_from(&network)?;
+    let matrices = Matrices::try_from(network)?;
 
-    let q = DVector::from_vec(
-        network
-            .demand_nodes
-            .iter()
-            .map(|node| match node {
-                Node::Pressure { .. } => {
-                    unreachable!("there should be no pressure node included here")
-                }
-                Node::Demand { demand, .. } => demand.value_at(0.), // TODO: transform to velocity
-                Node::Zero { .. } => Ok(0.),
-            })
-            .collect::<Result<Vec<f64>, Error>>()?,
-    );
+    // kW / (GJ/m^3) = 1e-6 m^3/s
+    let cooling = network.fluid.energy_density(settings.feed_temperature)?
+        - network.fluid.energy_density(settings.return_temperature)?;
+    let q = sample_demands(network, settings)? * (1e-6 / cooling);
+    let h_pressure = sample_pressure_heads(network, settings, &e)?;
 
-    let m1 = (matrices.ar.transpose() * matrices.at.transpose())
-        .lu()
-        .solve(&q)
-        .expect("could not solve system of equations for m1");
+    let n = settings.num_steps();
+    let mut velocities = DMatrix::zeros(network.num_edges(), n);
+    let mut heads = DMatrix::zeros(network.demand_nodes.len(), n);
 
-    let v = hydraulic::get_velocities(q, e, &matrices);
+    let mut initial = (
+        DVector::zeros(network.num_edges()),
+        DVector::from_element(network.demand_nodes.len(), h_pressure.max()),
+    );
+    for t in 0..n {
+        let (v, h) = hydraulic::solve(
+            network,
+            &matrices,
+            &e,
+            &h_pressure.column(t).into_owned(),
+            &q.column(t).into_owned(),
+            initial,
+            settings,
+        )
+        .map_err(|err| anyhow!("could not solve time step {}: {}", t, err))?;
 
-    dbg!(v);
+        velocities.set_column(t, &v);
+        heads.set_column(t, &h);
+        initial = (v, h);
+    }
 
-    todo!();
+    Ok(HydraulicResult { velocities, heads })
 }
 
-pub fn simulate_delay(
-    network: &Network<FixedVelocityPipeParameters>,
+/// Result of [`simulate_delay`]
+pub struct DelayResult<T = f64> {
+    /// Temperatures of the demand nodes by node index
+    pub temperatures: Vec<(usize, DVector<T>)>,
+    /// Node indices and time steps whose temperatures depend on invalid input data
+    pub invalid: Vec<(usize, usize)>,
+}
+
+/// Computes the temperatures of the demand nodes by tracing the delays along the pipes
+///
+/// The paths from every demand node upstream to the pressure nodes are traced once,
+/// the temperature of a pressure node is sampled once per path on the time grid shifted by the
+/// delay of the path.
+///
+/// With [`Dual`](crate::scalar::Dual) pipe parameters, the derivatives of the temperatures
+/// with respect to the seeded lengths or velocities are computed alongside.
+pub fn simulate_delay<T, Fluid>(
+    network: &Network<FixedVelocityPipeParameters<T>, Fluid>,
     settings: &Settings,
-) -> Result<Vec<(usize, DVector<f64>)>, Error> {
+) -> Result<DelayResult<T>, Error>
+where
+    T: Scalar,
+{
     let delays = DVector::from_iterator(
         network.num_edges(),
         network
             .edge_parameters()
-            .map(|FixedVelocityPipeParameters { length, velocity }| length / velocity),
+            .map(|FixedVelocityPipeParameters { length, velocity }| *length / *velocity),
     );
 
-    let dt = settings.time_step * 60.;
     let n = settings.num_steps();
 
     let mut result = network
@@ -85,39 +193,91 @@ pub fn simulate_delay(
         .iter()
         .enumerate()
         .filter(|(_, node)| matches!(node, Node::Demand { .. }))
-        .map(|(i, _)| (i, DVector::from_element(n, 0 as f64)))
+        .map(|(i, _)| (i, DVector::from_element(n, T::zero())))
         .collect::<Vec<_>>();
 
+    let mut invalid = vec![];
+
     for (i, result) in result.iter_mut() {
-        for t in 0..n {
-            let visited_counter = DVector::from_element(network.num_nodes(), 0);
-
-            result[t] = compute_temperature_rec(
-                network,
-                settings,
-                &delays,
-                *i,
-                t as f64 * settings.time_step,
-                visited_counter,
-            )?;
+        let mut paths = vec![];
+        trace_paths_rec(
+            network,
+            &delays,
+            *i,
+            (T::zero(), T::one()),
+            DVector::from_element(network.num_nodes(), 0),
+            &mut paths,
+        )?;
+
+        let mut valid = vec![true; n];
+        for path in paths {
+            let Node::Pressure { temperature, .. } = network.get_node(path.pressure_node_index)?
+            else {
+                unreachable!("paths start at pressure nodes");
+            };
+
+            let start = -path.delay.value();
+            let temperatures = temperature.values_on_grid(start, settings.time_step, n)?;
+
+            for (t, value) in temperatures.iter().enumerate() {
+                let time = T::from_f64(t as f64 * settings.time_step) - path.delay;
+                let derivative = if time.is_constant() {
+                    0.
+                } else {
+                    temperature.derivative_at(time.value())?
+                };
+
+                result[t] += path.weight * time.apply(*value, derivative);
+                valid[t] &= temperature.is_valid_at(time.value());
+            }
         }
+
+        invalid.extend(
+            valid
+                .iter()
+                .enumerate()
+                .filter(|(_, valid)| !**valid)
+                .map(|(t, _)| (*i, t)),
+        );
     }
 
-    Ok(result)
+    Ok(DelayResult {
+        temperatures: result,
+        invalid,
+    })
+}
+
+/// Path of the fluid from a pressure node to a demand node
+struct Path<T> {
+    pressure_node_index: usize,
+    /// Sum of the delays of the pipes along the path
+    delay: T,
+    /// Share of the fluid arriving at the demand node that took the path
+    weight: T,
 }
 
 const VISITED_COUNT_THRESHOLD: usize = 3;
 
-fn compute_temperature_rec(
-    network: &Network<FixedVelocityPipeParameters>,
-    settings: &Settings,
-    delays: &DVector<f64>,
+/// Collects the paths upstream of the node `current_node_index`,
+/// `(delay, weight)` of the path from the node to the demand node
+fn trace_paths_rec<T, Fluid>(
+    network: &Network<FixedVelocityPipeParameters<T>, Fluid>,
+    delays: &DVector<T>,
     current_node_index: usize,
-    time: f64,
+    (delay, weight): (T, T),
     mut visited_counter: DVector<usize>,
-) -> Result<f64, Error> {
-    if let Node::Pressure { temperature, .. } = network.get_node(current_node_index)? {
-        return temperature.value_at(time);
+    paths: &mut Vec<Path<T>>,
+) -> Result<(), Error>
+where
+    T: Scalar,
+{
+    if let Node::Pressure { .. } = network.get_node(current_node_index)? {
+        paths.push(Path {
+            pressure_node_index: current_node_index,
+            delay,
+            weight,
+        });
+        return Ok(());
     }
 
     let count = visited_counter.get_mut(current_node_index).ok_or(anyhow!(
@@ -163,7 +323,7 @@ fn compute_temperature_rec(
         .collect::<Result<Vec<_>, Error>>()?
         .iter()
         .filter_map(|(edge_index, next_node_index, velocity, reverse)| {
-            ((*velocity < 0.) == *reverse).then_some((
+            ((velocity.value() < 0.) == *reverse).then_some((
                 *next_node_index,
                 delays[*edge_index],
                 velocity.abs(),
@@ -171,22 +331,186 @@ fn compute_temperature_rec(
         })
         .collect::<Vec<_>>();
 
-    let total_weight: f64 = calls.iter().map(|(_, _, weight)| weight).sum();
+    let total_weight: T = calls.iter().map(|(_, _, weight)| *weight).sum();
+
+    for (next_node_index, time_delay, next_weight) in calls.into_iter() {
+        trace_paths_rec(
+            network,
+            delays,
+            next_node_index,
+            (delay + time_delay, weight * next_weight / total_weight),
+            visited_counter.clone(),
+            paths,
+        )?;
+    }
+
+    Ok(())
+}
 
-    let mut temperature = 0.;
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::{
+        friction::Friction,
+        scalar::Dual,
+        types::{
+            formats::custom::test_util::DUMMY_CUSTOM_POSITION,
+            network::{test::DUMMY_CONST_SIGNAL, Edge, FullPipeParameters},
+            signal::Signal,
+        },
+        water::Water,
+    };
+
+    /// A source with a linearly rising temperature feeding a consumer through a single pipe
+    fn create_test_net<T: Scalar>(
+        length: T,
+        velocity: T,
+    ) -> Network<FixedVelocityPipeParameters<T>> {
+        let nodes = vec![
+            Node::Demand {
+                name: String::from("consumer"),
+                demand: DUMMY_CONST_SIGNAL,
+                position: DUMMY_CUSTOM_POSITION,
+            },
+            Node::Pressure {
+                name: String::from("source"),
+                pressure: DUMMY_CONST_SIGNAL,
+                temperature: Signal::Linear {
+                    h: 2e4,
+                    a: -1e4,
+                    b: 1e4,
+                    y: vec![50.],
+                    dy: vec![0.01],
+                },
+                position: DUMMY_CUSTOM_POSITION,
+            },
+        ];
+
+        Network::try_from_feed(
+            nodes,
+            vec![Edge { src: 1, tgt: 0 }],
+            vec![FixedVelocityPipeParameters { length, velocity }],
+        )
+        .expect("could not compute network from feed nodes and edges")
+    }
 
-    for (next_node_index, time_delay, weight) in calls.into_iter() {
-        temperature += weight / total_weight * {
-            compute_temperature_rec(
-                network,
-                settings,
-                delays,
-                next_node_index,
-                time - time_delay,
-                visited_counter.clone(),
-            )?
+    /// A source supplying two consumers in a row
+    fn create_hydraulic_test_net() -> Network<FullPipeParameters> {
+        let consumer = |name: &str, demand| Node::Demand {
+            name: String::from(name),
+            demand: Signal::Const { value: demand },
+            position: DUMMY_CUSTOM_POSITION,
         };
+        let nodes = vec![
+            consumer("C1", 200.),
+            consumer("C2", 100.),
+            Node::Pressure {
+                name: String::from("source"),
+                pressure: Signal::Const { value: 5e5 },
+                temperature: Signal::Const { value: 80. },
+                position: DUMMY_CUSTOM_POSITION,
+            },
+        ];
+        let pipe = |diameter| FullPipeParameters {
+            length: 100.,
+            diameter,
+            transmittance: 1.,
+            roughness: 1e-4,
+            zeta: 0.5,
+            friction_model: Friction::default(),
+        };
+
+        Network::try_from_feed(
+            nodes,
+            vec![Edge { src: 2, tgt: 0 }, Edge { src: 0, tgt: 1 }],
+            vec![pipe(0.05), pipe(0.04)],
+        )
+        .expect("could not compute network from feed nodes and edges")
+    }
+
+    #[test]
+    fn hydraulic_steps_deliver_demands() {
+        let network = create_hydraulic_test_net();
+        let settings = Settings {
+            feed_temperature: 80.,
+            return_temperature: 50.,
+            ground_temperature: 10.,
+            time_start: 0.,
+            time_end: 1. / 24.,
+            time_step: 10.,
+            ramp_time: 0.,
+            num_iterations: 50,
+            tolerance: 1e-10,
+        };
+
+        let result = simulate(&network, &settings).expect("could not simulate");
+        assert_eq!(result.velocities.ncols(), settings.num_steps());
+        assert_eq!(result.heads.ncols(), settings.num_steps());
+
+        // 1 kW cooled down from 80 °C to 50 °C
+        let energy_density = |t| {
+            Water
+                .energy_density(t)
+                .expect("could not compute energy density")
+        };
+        let flow_per_kw = 1e-6 / (energy_density(80.) - energy_density(50.));
+        let source_head = 5e5 / (Water.density(80.) * G);
+
+        for (v, h) in result
+            .velocities
+            .column_iter()
+            .zip(result.heads.column_iter())
+        {
+            let flows: Vec<f64> = v
+                .iter()
+                .zip(network.edge_parameters())
+                .map(|(v, pipe)| v * pipe.cross_section())
+                .collect();
+            assert_relative_eq!(flows[0], 300. * flow_per_kw, max_relative = 1e-8);
+            assert_relative_eq!(flows[1], 100. * flow_per_kw, max_relative = 1e-8);
+
+            assert!(h[1] < h[0] && h[0] < source_head);
+        }
     }
 
-    Ok(temperature)
+    #[test]
+    fn sensitivity_to_pipe_length() {
+        let settings = Settings {
+            feed_temperature: 80.,
+            return_temperature: 40.,
+            ground_temperature: 10.,
+            time_start: 0.,
+            time_end: 1. / 24.,
+            time_step: 10.,
+            ramp_time: 0.,
+            num_iterations: 1,
+            tolerance: 1e-6,
+        };
+        let (length, velocity) = (100., 0.5);
+
+        let temperatures = |length: f64| {
+            simulate_delay(&create_test_net(length, velocity), &settings)
+                .expect("could not simulate")
+                .temperatures[0]
+                .1
+                .clone()
+        };
+        let dual = simulate_delay(
+            &create_test_net(Dual::variable(length), Dual::constant(velocity)),
+            &settings,
+        )
+        .expect("could not simulate");
+
+        let dl = 1e-3;
+        let slopes = (temperatures(length + dl) - temperatures(length - dl)) / (2. * dl);
+
+        let (_, result) = &dual.temperatures[0];
+        for (t, temperature) in result.iter().enumerate() {
+            assert_eq!(temperature.value, temperatures(length)[t]);
+            assert_relative_eq!(temperature.derivative, -0.01 / velocity);
+            assert_relative_eq!(temperature.derivative, slopes[t], max_relative = 1e-6);
+        }
+    }
 }
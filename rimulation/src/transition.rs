@@ -1,26 +1,186 @@
+use crate::{
+    polynome::{poly, poly_derivative},
+    scalar::Scalar,
+};
 
-use crate::polynome::poly;
+/// Coefficients of the cubic Hermite polynomial in `t` \[0, 1\] from `(p_0, m_0)` to `(p_1, m_1)`,
+/// with the slopes scaled to `t`
+fn cubic_coefficients<T: Scalar>(width: f64, p_0: T, m_0: T, p_1: T, m_1: T) -> [T; 4] {
+    let m_0 = m_0 * width;
+    let m_1 = m_1 * width;
+
+    let cubic_term = p_0 * 2. + m_0 - p_1 * 2. + m_1;
+    let quadratic_term = -p_0 * 3. - m_0 * 2. + p_1 * 3. - m_1;
+
+    [p_0, m_0, quadratic_term, cubic_term]
+}
 
-pub fn transition_cubic(
-    x: f64,
+pub fn transition_cubic<T: Scalar>(
+    x: T,
     left: f64,
     right: f64,
-    p_0: f64,
-    m_0: f64,
-    p_1: f64,
-    m_1: f64,
-) -> f64 {
+    p_0: T,
+    m_0: T,
+    p_1: T,
+    m_1: T,
+) -> T {
     let width = right - left;
 
-    let t = ((x - left) / width).clamp(0., 1.);
+    let t = (x - left) / width;
+    let t = if t.value() < 0. {
+        T::zero()
+    } else if t.value() > 1. {
+        T::one()
+    } else {
+        t
+    };
 
-    let m_0 = m_0 * width;
-    let m_1 = m_1 * width;
+    poly(t, &cubic_coefficients(width, p_0, m_0, p_1, m_1))
+}
+
+/// Derivative of [`transition_cubic`] with respect to `x`, which is 0 outside of `[left, right]`
+pub fn transition_cubic_derivative<T: Scalar>(
+    x: T,
+    left: f64,
+    right: f64,
+    p_0: T,
+    m_0: T,
+    p_1: T,
+    m_1: T,
+) -> T {
+    let width = right - left;
+
+    let t = (x - left) / width;
+    if !(0. ..=1.).contains(&t.value()) {
+        return T::zero();
+    }
+
+    poly_derivative(t, &cubic_coefficients(width, p_0, m_0, p_1, m_1)) / width
+}
+
+/// Shape of the blend between two regimes of a [`Transition`]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Blend {
+    /// Cubic Hermite bridge from the value and slope of the lower regime at the left boundary
+    /// to the value and slope of the upper regime at the right boundary
+    ///
+    /// Continuously differentiable (C1). The regimes are only evaluated outside of the bridge,
+    /// so they do not need to be valid within it.
+    Hermite,
+    /// Mean of both regimes weighted with the quintic smoothstep `6 t^5 - 15 t^4 + 10 t^3`
+    ///
+    /// Twice continuously differentiable (C2) if the regimes are.
+    /// Both regimes have to be valid between the boundaries.
+    Smoothstep,
+    /// Mean of both regimes weighted with a logistic function centered between the boundaries
+    ///
+    /// Infinitely differentiable, but both regimes are mixed everywhere.
+    /// The weight of the other regime at a boundary is `1 / (1 + exp(steepness))`.
+    Logistic { steepness: f64 },
+}
+
+/// Smooth transition from a lower regime below `left` to an upper regime above `right`
+///
+/// Regimes are closures returning their value and derivative at `x`,
+/// see [`Transition::evaluate`].
+///
+/// # Example
+/// ```
+/// use rimulation::transition::{Blend, Transition};
+///
+/// let transition = Transition::new(1., 2., Blend::Smoothstep);
+/// let lower = |x: f64| (x, 1.);
+/// let upper = |x: f64| (x * x, 2. * x);
+///
+/// assert_eq!(transition.evaluate(0.5, lower, upper), (0.5, 1.));
+/// assert_eq!(transition.evaluate(3., lower, upper), (9., 6.));
+/// ```
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Transition {
+    pub left: f64,
+    pub right: f64,
+    pub blend: Blend,
+}
+
+impl Transition {
+    pub const fn new(left: f64, right: f64, blend: Blend) -> Self {
+        Self { left, right, blend }
+    }
+
+    fn width(&self) -> f64 {
+        self.right - self.left
+    }
+
+    /// Weight of the upper regime at `x` and its derivative with respect to `x`
+    ///
+    /// The Hermite blend uses the cubic smoothstep `3 t^2 - 2 t^3`,
+    /// i.e. the Hermite bridge between two constant regimes.
+    pub fn weight<T: Scalar>(&self, x: T) -> (T, T) {
+        let width = self.width();
+        let t = (x - self.left) / width;
+
+        if let Blend::Logistic { steepness } = self.blend {
+            let w = (((t * 2. - 1.) * -steepness).exp() + 1.).recip();
+            let dw = w * (T::one() - w) * (2. * steepness / width);
+
+            return (w, dw);
+        }
+
+        if t.value() <= 0. {
+            return (T::zero(), T::zero());
+        } else if t.value() >= 1. {
+            return (T::one(), T::zero());
+        }
+
+        let u = T::one() - t;
+        match self.blend {
+            Blend::Hermite => (t * t * (T::one() + u * 2.), t * u * (6. / width)),
+            Blend::Smoothstep => (
+                poly(t, &[0., 0., 0., 10., -15., 6.]),
+                t * t * u * u * (30. / width),
+            ),
+            Blend::Logistic { .. } => unreachable!("logistic weights are computed above"),
+        }
+    }
+
+    /// Evaluates the transition at `x`
+    ///
+    /// The regimes `lower` and `upper` return their value and their derivative at a point,
+    /// the transition returns its value and its derivative with respect to `x`.
+    pub fn evaluate<T: Scalar>(
+        &self,
+        x: T,
+        lower: impl Fn(T) -> (T, T),
+        upper: impl Fn(T) -> (T, T),
+    ) -> (T, T) {
+        if let Blend::Hermite = self.blend {
+            if x.value() < self.left {
+                return lower(x);
+            } else if x.value() > self.right {
+                return upper(x);
+            }
 
-    let cubic_term = 2. * p_0 + m_0 - 2. * p_1 + m_1;
-    let quadratic_term = -3. * p_0 - 2. * m_0 + 3. * p_1 - m_1;
+            let (p_0, m_0) = lower(T::from_f64(self.left));
+            let (p_1, m_1) = upper(T::from_f64(self.right));
 
-    poly(t, &[p_0, m_0, quadratic_term, cubic_term])
+            return (
+                transition_cubic(x, self.left, self.right, p_0, m_0, p_1, m_1),
+                transition_cubic_derivative(x, self.left, self.right, p_0, m_0, p_1, m_1),
+            );
+        }
+
+        let (w, dw) = self.weight(x);
+        if w.is_zero() {
+            return lower(x);
+        } else if w.is_one() {
+            return upper(x);
+        }
+
+        let (f, df) = lower(x);
+        let (g, dg) = upper(x);
+
+        (f + (g - f) * w, df + (dg - df) * w + (g - f) * dw)
+    }
 }
 
 #[cfg(test)]
@@ -94,4 +254,86 @@ mod tests {
         assert_slope(-5., 0.2);
         assert_slope(5., 0.3);
     }
+
+    #[test]
+    fn cubic_derivative() {
+        let left = -5.;
+        let right = 5.;
+
+        let (p_0, m_0) = (-1., 0.2);
+        let (p_1, m_1) = (5., 0.3);
+
+        assert_eq!(
+            transition_cubic_derivative(-5., left, right, p_0, m_0, p_1, m_1),
+            0.2
+        );
+        assert_eq!(
+            transition_cubic_derivative(5., left, right, p_0, m_0, p_1, m_1),
+            0.3
+        );
+        assert_eq!(
+            transition_cubic_derivative(6., left, right, p_0, m_0, p_1, m_1),
+            0.
+        );
+
+        let dx = 1e-6;
+        for x in [-4., -1., 0., 2.5] {
+            let slope = (transition_cubic(x + dx, left, right, p_0, m_0, p_1, m_1)
+                - transition_cubic(x - dx, left, right, p_0, m_0, p_1, m_1))
+                / (2. * dx);
+            let derivative = transition_cubic_derivative(x, left, right, p_0, m_0, p_1, m_1);
+
+            assert!((slope - derivative).abs() < 1e-6);
+        }
+    }
+
+    #[test]
+    fn blends_between_regimes() {
+        let lower = |x: f64| (x.sin(), x.cos());
+        let upper = |x: f64| (x * x, 2. * x);
+
+        let blends = [
+            Blend::Hermite,
+            Blend::Smoothstep,
+            Blend::Logistic { steepness: 8. },
+        ];
+        for blend in blends {
+            let transition = Transition::new(1., 2., blend);
+            let evaluate = |x: f64| transition.evaluate(x, lower, upper);
+
+            let dx = 1e-6;
+            for x in [0.5, 1., 1.2, 1.5, 1.9, 2., 3.] {
+                let slope = (evaluate(x + dx).0 - evaluate(x - dx).0) / (2. * dx);
+                assert!((evaluate(x).1 - slope).abs() < 1e-4, "{:?} at {}", blend, x);
+            }
+
+            let (w_left, _) = transition.weight(1.);
+            let (w_right, _) = transition.weight(2.);
+            assert!(w_left < 1e-3 && w_right > 1. - 1e-3);
+        }
+
+        let hermite = Transition::new(1., 2., Blend::Hermite);
+        assert_eq!(hermite.evaluate(0.5, lower, upper), lower(0.5));
+        assert_eq!(hermite.evaluate(1., lower, upper), lower(1.));
+        assert_eq!(hermite.evaluate(2., lower, upper), upper(2.));
+    }
+
+    #[test]
+    fn smoothstep_is_twice_differentiable() {
+        let transition = Transition::new(-1., 1., Blend::Smoothstep);
+
+        let dx = 1e-4;
+        for x in [-1., 1.] {
+            let curvature =
+                |x: f64| (transition.weight(x + dx).1 - transition.weight(x - dx).1) / (2. * dx);
+
+            assert_eq!(transition.weight(x).1, 0.);
+            assert!(curvature(x).abs() < 1e-3);
+        }
+
+        // the cubic smoothstep of the Hermite blend has a kink in its slope
+        let hermite = Transition::new(-1., 1., Blend::Hermite);
+        let curvature = (hermite.weight(-1. + dx).1 - hermite.weight(-1.).1) / dx;
+        assert!(curvature > 1.);
+    }
 }
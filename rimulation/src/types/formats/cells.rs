@@ -0,0 +1,63 @@
+//! Grid index to find the nodes near the end of a line when connecting it
+
+use std::collections::HashMap;
+
+/// Indices of points by the square grid cell containing them, whose side is the tolerance
+///
+/// All points within the tolerance of a point lie in its cell or one of the eight neighbors.
+pub struct CellIndex {
+    size: f64,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+}
+
+impl CellIndex {
+    pub fn new(tolerance: f64) -> Self {
+        Self {
+            size: if tolerance > 0. { tolerance } else { 1. },
+            cells: HashMap::new(),
+        }
+    }
+
+    fn cell(&self, x: f64, y: f64) -> (i64, i64) {
+        (
+            (x / self.size).floor() as i64,
+            (y / self.size).floor() as i64,
+        )
+    }
+
+    pub fn insert(&mut self, index: usize, x: f64, y: f64) {
+        self.cells.entry(self.cell(x, y)).or_default().push(index);
+    }
+
+    /// Indices of the points in the cell of (x, y) and its neighbors
+    pub fn near(&self, x: f64, y: f64) -> impl Iterator<Item = usize> + '_ {
+        let (i, j) = self.cell(x, y);
+        (i.saturating_sub(1)..=i.saturating_add(1))
+            .flat_map(move |i| (j.saturating_sub(1)..=j.saturating_add(1)).map(move |j| (i, j)))
+            .filter_map(|cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_within_tolerance_are_near() {
+        let mut index = CellIndex::new(1.);
+        for (i, (x, y)) in [(0.5, 0.5), (1.4, -0.3), (2.6, 0.5), (-10., 3.)]
+            .into_iter()
+            .enumerate()
+        {
+            index.insert(i, x, y);
+        }
+
+        let mut near: Vec<_> = index.near(0.9, 0.).collect();
+        near.sort();
+        assert_eq!(near, vec![0, 1]);
+        assert_eq!(index.near(-10., 3.).collect::<Vec<_>>(), vec![3]);
+        assert_eq!(index.near(5., 5.).count(), 0);
+    }
+}
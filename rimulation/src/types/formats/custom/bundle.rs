@@ -0,0 +1,103 @@
+//! Single file holding the topology, scenario and parameters of a network
+//!
+//! A bundle has the keys `topology`, `scenario` and `parameters` with the contents of the files
+//! of the same names. It is encoded in JSON, YAML or TOML, selected by the extension of the file.
+//! Paths of CSV signals are relative to the directory of the bundle.
+//!
+//! YAML bundles have the same structure as the JSON files, enums are maps with a single key
+//! instead of YAML tags.
+
+use anyhow::{anyhow, Error};
+use serde::{de::DeserializeOwned, Serialize};
+use std::{fs, path::Path};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Encoding {
+    Json,
+    Yaml,
+    Toml,
+}
+
+impl Encoding {
+    /// Encoding of the file `path` by its extension
+    pub fn of(path: &str) -> Option<Self> {
+        match Path::new(path)
+            .extension()?
+            .to_str()?
+            .to_lowercase()
+            .as_str()
+        {
+            "json" => Some(Encoding::Json),
+            "yaml" | "yml" => Some(Encoding::Yaml),
+            "toml" => Some(Encoding::Toml),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `path` names a bundle instead of a directory with one file per part of the network
+pub fn is_bundle(path: &str) -> bool {
+    Encoding::of(path).is_some() && !Path::new(path).is_dir()
+}
+
+/// Directory of the bundle `path`, which relative paths in the bundle start from
+pub fn directory(path: &str) -> &Path {
+    Path::new(path).parent().unwrap_or(Path::new(""))
+}
+
+fn encoding(path: &str) -> Result<Encoding, Error> {
+    Encoding::of(path).ok_or(anyhow!(
+        "bundle '{}' does not end with .json, .yaml, .yml or .toml",
+        path
+    ))
+}
+
+pub fn read<T: DeserializeOwned>(path: &str) -> Result<T, Error> {
+    let text = fs::read_to_string(path)
+        .map_err(|err| anyhow!("could not open bundle '{}': {}", path, err))?;
+
+    match encoding(path)? {
+        Encoding::Json => serde_json::from_str(&text).map_err(Error::from),
+        Encoding::Yaml => serde_yaml_ng::from_str::<serde_json::Value>(&text)
+            .map_err(Error::from)
+            .and_then(|value| serde_json::from_value(value).map_err(Error::from)),
+        Encoding::Toml => toml::from_str(&text).map_err(Error::from),
+    }
+    .map_err(|err| anyhow!("could not decode bundle '{}': {}", path, err))
+}
+
+/// Writes a bundle, creating its directory if it does not exist
+pub fn write(value: &impl Serialize, path: &str) -> Result<(), Error> {
+    let text = match encoding(path)? {
+        Encoding::Json => serde_json::to_string_pretty(value).map_err(Error::from),
+        Encoding::Yaml => serde_json::to_value(value)
+            .map_err(Error::from)
+            .and_then(|value| serde_yaml_ng::to_string(&value).map_err(Error::from)),
+        Encoding::Toml => toml::to_string_pretty(value).map_err(Error::from),
+    }
+    .map_err(|err| anyhow!("could not encode bundle '{}': {}", path, err))?;
+
+    let directory = directory(path);
+    fs::create_dir_all(directory).map_err(|err| {
+        anyhow!(
+            "could not create directory '{}': {}",
+            directory.display(),
+            err
+        )
+    })?;
+    fs::write(path, text).map_err(|err| anyhow!("could not write bundle '{}': {}", path, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn encoding_by_extension() {
+        assert_eq!(Encoding::of("cases/triangle.json"), Some(Encoding::Json));
+        assert_eq!(Encoding::of("triangle.YML"), Some(Encoding::Yaml));
+        assert_eq!(Encoding::of("triangle.toml"), Some(Encoding::Toml));
+        assert_eq!(Encoding::of("data/running_example"), None);
+        assert_eq!(Encoding::of("triangle.csv"), None);
+    }
+}
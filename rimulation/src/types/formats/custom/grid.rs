@@ -0,0 +1,76 @@
+//! Placement of the data points of a signal on an equidistant time grid
+
+use super::DataPoint;
+
+use anyhow::{anyhow, Error};
+
+/// Relative tolerance when checking that time steps are multiples of the grid width
+const GRID_TOLERANCE: f64 = 1e-6;
+
+/// Places the data points on an equidistant grid
+///
+/// Returns the grid width and the values on the grid.
+/// Time steps missing from the grid are inserted as NaN values.
+pub fn equidistant(data: &[DataPoint]) -> Result<(f64, Vec<f64>), Error> {
+    let h = data
+        .windows(2)
+        .map(|w| w[1].t - w[0].t)
+        .filter(|dt| *dt > 0.)
+        .fold(f64::INFINITY, f64::min);
+
+    let first = data
+        .first()
+        .ok_or(anyhow!("data does not have any points"))?;
+
+    let mut values = Vec::with_capacity(data.len());
+    values.push(first.v);
+
+    for (i, w) in data.windows(2).enumerate() {
+        let steps = (w[1].t - w[0].t) / h;
+        if steps <= 0. || (steps - steps.round()).abs() > GRID_TOLERANCE {
+            return Err(anyhow!("data has inconsistent dt at index {}", i));
+        }
+
+        for _ in 1..steps.round() as usize {
+            values.push(f64::NAN);
+        }
+        values.push(w[1].v);
+    }
+
+    Ok((h, values))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points(data: &[(f64, f64)]) -> Vec<DataPoint> {
+        data.iter()
+            .map(|(t, v)| DataPoint { t: *t, v: *v })
+            .collect()
+    }
+
+    #[test]
+    fn equidistant_inserts_missing_steps() {
+        let (h, values) = equidistant(&points(&[(0., 1.), (2., 2.), (8., 3.), (10., 4.)]))
+            .expect("could not place data on grid");
+
+        assert_eq!(h, 2.);
+        assert_eq!(values.len(), 6);
+        assert!(values[2].is_nan() && values[3].is_nan());
+        assert_eq!(
+            [values[0], values[1], values[4], values[5]],
+            [1., 2., 3., 4.]
+        );
+    }
+
+    #[test]
+    fn equidistant_rejects_off_grid_steps() {
+        let result = equidistant(&points(&[(0., 1.), (2., 2.), (5., 3.)]));
+
+        assert!(result
+            .expect_err("time 5 is not on the grid")
+            .to_string()
+            .contains("inconsistent dt at index 1"));
+    }
+}
@@ -1,9 +1,19 @@
 use super::NamedComponent;
+use crate::friction::Friction;
 
 use anyhow::{anyhow, Error};
 use serde::{Deserialize, Serialize};
-use serde_json::from_reader;
-use std::{collections::HashMap, fs};
+use serde_json::{from_reader, to_writer_pretty};
+use std::{collections::HashMap, fs, path::Path};
+
+pub mod bundle;
+pub mod grid;
+mod time_series;
+mod units;
+pub mod validation;
+
+pub use time_series::{read_columns, read_time_series, Column, TimeUnit};
+pub use units::{GeometryUnits, LengthUnit};
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Position {
@@ -67,6 +77,9 @@ pub enum PipeParameters {
         transmittance: f64,
         roughness: f64,
         zeta: f64,
+        /// Overrides the friction model of the network for pipes with these parameters
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        friction_model: Option<Friction>,
     },
     FixedVelocity {
         length: f64,
@@ -74,6 +87,56 @@ pub enum PipeParameters {
     },
 }
 
+/// Hashable form of [`PipeParameters`], which are equal if and only if their keys are
+#[derive(PartialEq, Eq, Hash)]
+struct PipeParametersKey(Vec<u64>, Option<Friction>);
+
+impl PipeParameters {
+    fn key(&self) -> PipeParametersKey {
+        // adding 0 turns -0 into 0, which compare equal
+        let bits = |values: &[f64]| values.iter().map(|value| (value + 0.).to_bits()).collect();
+
+        match self {
+            PipeParameters::Full {
+                length,
+                diameter,
+                transmittance,
+                roughness,
+                zeta,
+                friction_model,
+            } => PipeParametersKey(
+                bits(&[*length, *diameter, *transmittance, *roughness, *zeta]),
+                *friction_model,
+            ),
+            PipeParameters::FixedVelocity { length, velocity } => {
+                PipeParametersKey(bits(&[*length, *velocity]), None)
+            }
+        }
+    }
+
+    /// Uses the given friction model unless the parameters choose one themselves
+    pub fn or_friction_model(self, default: Friction) -> Self {
+        match self {
+            PipeParameters::Full {
+                length,
+                diameter,
+                transmittance,
+                roughness,
+                zeta,
+                friction_model,
+            } => PipeParameters::Full {
+                length,
+                diameter,
+                transmittance,
+                roughness,
+                zeta,
+                friction_model: friction_model.or(Some(default)),
+            },
+            fixed_velocity => fixed_velocity,
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Topology {
     pub nodes: Vec<Node>,
@@ -104,51 +167,149 @@ impl Settings {
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub struct DataPoint {
     pub t: f64,
+    /// Missing values are stored as `null` and read as NaN
+    #[serde(deserialize_with = "deserialize_missing_as_nan")]
     pub v: f64,
 }
 
+fn deserialize_missing_as_nan<'de, D>(deserializer: D) -> Result<f64, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    Ok(Option::<f64>::deserialize(deserializer)?.unwrap_or(f64::NAN))
+}
+
+/// How gaps in the data of a signal are filled
+///
+/// Gaps are missing or NaN values and missing time steps.
+#[derive(Debug, PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GapPolicy {
+    /// Interpolate linearly between the neighbouring values
+    #[default]
+    Linear,
+    /// Repeat the last valid value
+    HoldLast,
+    /// Bridge linearly, but mark the bridged time span as invalid
+    Invalid,
+}
+
 #[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
 pub enum Signal {
     #[serde(rename = "const")]
     Const { scale: f64, data: f64 },
     #[serde(rename = "poly")]
     Poly {
-        // TODO: make lookup cheaper (hashmap of interpolated times?)
         degree: usize,
         scale: f64,
         data: Vec<DataPoint>,
+        #[serde(default)]
+        gaps: GapPolicy,
     },
     #[serde(rename = "step")]
     Step { low: f64, high: f64, time: f64 },
+    /// Time series stored in a CSV file, the path is relative to the scenario directory
+    #[serde(rename = "csv")]
+    Csv {
+        path: String,
+        #[serde(default = "time_series::default_time_column")]
+        time_column: Column,
+        #[serde(default = "time_series::default_value_column")]
+        value_column: Column,
+        #[serde(default = "time_series::default_true")]
+        has_headers: bool,
+        #[serde(default)]
+        time_unit: TimeUnit,
+        #[serde(default = "time_series::default_scale")]
+        scale: f64,
+        #[serde(default = "time_series::default_degree")]
+        degree: usize,
+        #[serde(default)]
+        gaps: GapPolicy,
+    },
+    /// Sum of the named signals
+    #[serde(rename = "sum")]
+    Sum { signals: Vec<String> },
+    /// Product of the named signals
+    #[serde(rename = "product")]
+    Product { signals: Vec<String> },
+    /// The named signal delayed by `time`
+    #[serde(rename = "shift")]
+    Shift { signal: String, time: f64 },
+    /// The named signal multiplied by `factor`
+    #[serde(rename = "scale")]
+    Scale { signal: String, factor: f64 },
+    /// The named signal plus `value`
+    #[serde(rename = "offset")]
+    Offset { signal: String, value: f64 },
+    /// The named signal limited to `[min, max]`, both bounds are optional
+    #[serde(rename = "clamp")]
+    Clamp {
+        signal: String,
+        min: Option<f64>,
+        max: Option<f64>,
+    },
 }
 
 impl Signal {
-    pub fn scale_data(&self, factor: f64) -> Self {
+    /// Names of the signals this signal refers to
+    pub fn references(&self) -> Vec<&String> {
         match self {
-            Signal::Const { scale, data } => Signal::Const {
-                scale: *scale,
-                data: data * factor,
-            },
-            Signal::Poly {
+            Signal::Const { .. }
+            | Signal::Poly { .. }
+            | Signal::Step { .. }
+            | Signal::Csv { .. } => vec![],
+            Signal::Sum { signals } | Signal::Product { signals } => signals.iter().collect(),
+            Signal::Shift { signal, .. }
+            | Signal::Scale { signal, .. }
+            | Signal::Offset { signal, .. }
+            | Signal::Clamp { signal, .. } => vec![signal],
+        }
+    }
+
+    /// Replaces a CSV signal by the polynomial signal through its data points
+    ///
+    /// `directory` is the directory relative to which the path of the file is interpreted.
+    /// All other signals are returned unchanged.
+    pub fn load_data(self, directory: &Path) -> Result<Self, Error> {
+        match self {
+            Signal::Csv {
+                path,
+                time_column,
+                value_column,
+                has_headers,
+                time_unit,
+                scale,
+                degree,
+                gaps,
+            } => Ok(Signal::Poly {
                 degree,
                 scale,
-                data,
-            } => Signal::Poly {
-                degree: *degree,
-                scale: *scale,
-                data: data
-                    .iter()
-                    .map(|DataPoint { t, v }| DataPoint {
-                        t: *t,
-                        v: v * factor,
-                    })
-                    .collect(),
-            },
-            Signal::Step { low, high, time } => Signal::Step {
-                low: low * factor,
-                high: high * factor,
-                time: *time,
-            },
+                data: read_time_series(
+                    &directory.join(path),
+                    &time_column,
+                    &value_column,
+                    has_headers,
+                    time_unit,
+                )?,
+                gaps,
+            }),
+            signal => Ok(signal),
+        }
+    }
+
+    /// Number of gaps in the data of the signal
+    ///
+    /// Counts missing or NaN values and time steps missing from an otherwise equidistant grid.
+    pub fn num_gaps(&self) -> usize {
+        let Signal::Poly { data, .. } = self else {
+            return 0;
+        };
+
+        match grid::equidistant(data) {
+            Ok((_, values)) => values.iter().filter(|v| v.is_nan()).count(),
+            // data off an equidistant grid is rejected when the signal is converted
+            Err(_) => data.iter().filter(|point| point.v.is_nan()).count(),
         }
     }
 }
@@ -190,18 +351,80 @@ pub struct Scenario {
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Parameters {
+    /// Friction model of all pipes whose parameters do not choose one
+    #[serde(default)]
+    pub friction_model: Friction,
+    /// Units of the lengths, diameters and roughnesses of all parameters
+    #[serde(default)]
+    pub units: GeometryUnits,
     pub parameters: HashMap<String, PipeParameters>,
     pub pipes: HashMap<String, String>,
 }
 
-#[derive(Debug)]
+impl Parameters {
+    /// Creates the parameters of pipes in [`GeometryUnits::nominal`], merging identical
+    /// parameters of several pipes into one set
+    ///
+    /// The sets are named `parameters_1`, `parameters_2`, ... in the order of the pipes.
+    pub fn deduplicated(pipes: impl IntoIterator<Item = (String, PipeParameters)>) -> Self {
+        let set_name = |index: usize| format!("parameters_{}", index + 1);
+
+        let mut sets: Vec<PipeParameters> = vec![];
+        let mut indices = HashMap::new();
+        let mut pipe_sets = HashMap::new();
+        for (name, parameters) in pipes {
+            let index = *indices.entry(parameters.key()).or_insert_with(|| {
+                sets.push(parameters);
+                sets.len() - 1
+            });
+            pipe_sets.insert(name, set_name(index));
+        }
+
+        Parameters {
+            friction_model: Default::default(),
+            units: GeometryUnits::nominal(),
+            parameters: sets
+                .into_iter()
+                .enumerate()
+                .map(|(i, set)| (set_name(i), set))
+                .collect(),
+            pipes: pipe_sets,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
 pub struct Network {
     pub topology: Topology,
     pub scenario: Scenario,
     pub parameters: Parameters,
 }
 
+/// Reads the data of CSV signals, whose paths are relative to `directory`
+fn load_signal_data(mut scenario: Scenario, directory: &Path) -> Result<Scenario, Error> {
+    scenario.signals = scenario
+        .signals
+        .into_iter()
+        .map(|(name, signal)| {
+            signal
+                .load_data(directory)
+                .map(|signal| (name.clone(), signal))
+                .map_err(|err| anyhow!("could not load data of signal '{}': {}", name, err))
+        })
+        .collect::<Result<_, Error>>()?;
+    Ok(scenario)
+}
+
+/// Loads a network from a directory with one file per part or from a [`bundle`]
 pub fn load(path: &str) -> Result<Network, Error> {
+    if bundle::is_bundle(path) {
+        let network: Network = bundle::read(path)?;
+        return Ok(Network {
+            scenario: load_signal_data(network.scenario, bundle::directory(path))?,
+            ..network
+        });
+    }
+
     let topology_file = fs::File::open(format!("{}/topology.json", path))
         .map_err(|err| anyhow!("could not open topology file: {}", err))?;
     let topology: Topology =
@@ -211,6 +434,7 @@ pub fn load(path: &str) -> Result<Network, Error> {
         .map_err(|err| anyhow!("could not open scenario file: {}", err))?;
     let scenario: Scenario =
         from_reader(scenario_file).map_err(|err| anyhow!("could not decode scenario: {}", err))?;
+    let scenario = load_signal_data(scenario, Path::new(path))?;
 
     let parameters_file = fs::File::open(format!("{}/parameters.json", path))
         .map_err(|err| anyhow!("could not open parameters file: {}", err))?;
@@ -224,6 +448,80 @@ pub fn load(path: &str) -> Result<Network, Error> {
     })
 }
 
+/// Writes the topology, scenario and parameters files of the network into the directory `path`,
+/// or all of them into a [`bundle`] if `path` has its extension
+///
+/// The directory is created if it does not exist.
+pub fn save(network: &Network, path: &str) -> Result<(), Error> {
+    if bundle::is_bundle(path) {
+        return bundle::write(network, path);
+    }
+
+    save_file(&network.topology, path, "topology")?;
+    save_file(&network.scenario, path, "scenario")?;
+    save_file(&network.parameters, path, "parameters")
+}
+
+/// Writes the topology and parameters of an imported network like [`save`]
+///
+/// The scenario has to be added before the network can be loaded, as a file into the directory
+/// or as the `scenario` key of the bundle.
+pub fn save_without_scenario(
+    topology: &Topology,
+    parameters: &Parameters,
+    path: &str,
+) -> Result<(), Error> {
+    if bundle::is_bundle(path) {
+        #[derive(Serialize)]
+        struct Parts<'a> {
+            topology: &'a Topology,
+            parameters: &'a Parameters,
+        }
+
+        return bundle::write(
+            &Parts {
+                topology,
+                parameters,
+            },
+            path,
+        );
+    }
+
+    save_file(topology, path, "topology")?;
+    save_file(parameters, path, "parameters")
+}
+
+/// Writes one file of the custom format, e.g. the `topology`, into the directory `path`
+///
+/// The directory is created if it does not exist.
+pub fn save_file(value: &impl Serialize, path: &str, file: &str) -> Result<(), Error> {
+    fs::create_dir_all(path)
+        .map_err(|err| anyhow!("could not create directory '{}': {}", path, err))?;
+
+    let writer = fs::File::create(format!("{}/{}.json", path, file))
+        .map_err(|err| anyhow!("could not create {} file: {}", file, err))?;
+    to_writer_pretty(writer, value).map_err(|err| anyhow!("could not encode {}: {}", file, err))
+}
+
+/// Path of an output file, e.g. the `result` of a simulation, of the network at `path`
+///
+/// Outputs are written into the directory of the network, or next to a [`bundle`] with the name
+/// of the bundle as prefix.
+pub fn output_path(path: &str, name: &str) -> String {
+    if bundle::is_bundle(path) {
+        let stem = Path::new(path)
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+        bundle::directory(path)
+            .join(format!("{}_{}", stem, name))
+            .to_string_lossy()
+            .to_string()
+    } else {
+        format!("{}/{}", path, name)
+    }
+}
+
 #[cfg(test)]
 pub mod test {
     use super::*;
@@ -249,6 +547,260 @@ pub mod test {
             fs::File::open("data/custom_format/parameters.json").expect("could not open file");
         let _: Parameters = from_reader(file).expect("could not parse parameters json");
     }
+
+    #[test]
+    fn deduplicate_identical_parameters() {
+        let full = |zeta, friction_model| PipeParameters::Full {
+            length: 10.,
+            diameter: 100.,
+            transmittance: 1.,
+            roughness: 0.1,
+            zeta,
+            friction_model,
+        };
+        let parameters = Parameters::deduplicated([
+            (String::from("A"), full(0., None)),
+            (String::from("B"), full(-0., None)),
+            (String::from("C"), full(0., Some(Friction::Haaland))),
+            (
+                String::from("D"),
+                PipeParameters::FixedVelocity {
+                    length: 10.,
+                    velocity: 1.,
+                },
+            ),
+            (String::from("E"), full(0., None)),
+        ]);
+
+        assert_eq!(parameters.parameters.len(), 3);
+        let sets: Vec<_> = ["A", "B", "C", "D", "E"]
+            .iter()
+            .map(|pipe| parameters.pipes[*pipe].as_str())
+            .collect();
+        assert_eq!(
+            sets,
+            [
+                "parameters_1",
+                "parameters_1",
+                "parameters_2",
+                "parameters_3",
+                "parameters_1"
+            ]
+        );
+    }
+
+    #[test]
+    fn parameters_without_units_in_meters() {
+        let parameters: Parameters = serde_json::from_str(
+            r#"{ "parameters": { "p": { "length": 1, "velocity": 1 } }, "pipes": { "A": "p" } }"#,
+        )
+        .expect("could not parse parameters");
+        assert_eq!(
+            parameters.units,
+            GeometryUnits {
+                length: LengthUnit::Meters,
+                diameter: LengthUnit::Meters,
+                roughness: LengthUnit::Meters,
+            }
+        );
+
+        let file =
+            fs::File::open("data/custom_format/parameters.json").expect("could not open file");
+        let parameters: Parameters = from_reader(file).expect("could not parse parameters json");
+        assert_eq!(parameters.units, GeometryUnits::nominal());
+    }
+
+    #[test]
+    fn select_friction_model_per_parameter_set() {
+        let file = fs::File::open("data/custom_format/parameters_friction_model.json")
+            .expect("could not open file");
+        let parameters: Parameters = from_reader(file).expect("could not parse parameters json");
+
+        assert_eq!(parameters.friction_model, Friction::Colebrook);
+        let friction_model = |name: &str| match &parameters.parameters[name] {
+            PipeParameters::Full { friction_model, .. } => *friction_model,
+            PipeParameters::FixedVelocity { .. } => panic!("parameters are full"),
+        };
+        assert_eq!(friction_model("default_pipe"), None);
+        assert_eq!(friction_model("smooth_pipe"), Some(Friction::Haaland));
+    }
+
+    #[test]
+    fn load_csv_signal() {
+        let network = load("data/fixed_velocity/csv_signal").expect("could not load network");
+
+        let Signal::Poly {
+            degree,
+            scale,
+            data,
+            gaps,
+        } = &network.scenario.signals["S1_temperature"]
+        else {
+            panic!("csv signal was not converted to a polynomial signal");
+        };
+
+        assert_eq!(*degree, 1);
+        assert_eq!(*gaps, GapPolicy::Linear);
+        assert_eq!(*scale, 1.);
+        assert_eq!(data.len(), 10);
+        assert_eq!(data[0], DataPoint { t: -15., v: 60. });
+        assert_eq!(data[9], DataPoint { t: 120., v: 105. });
+    }
+
+    #[test]
+    fn save_and_load_bundles() {
+        let network = load("data/running_example").expect("could not load network");
+        let expected = serde_json::to_value(&network).expect("could not encode network");
+
+        for extension in ["json", "yaml", "toml"] {
+            let path = format!("/tmp/rimulation_running_example.{}", extension);
+            save(&network, &path).expect("could not save bundle");
+
+            let bundle = load(&path).expect("could not load bundle");
+
+            assert_eq!(
+                serde_json::to_value(&bundle).expect("could not encode bundle"),
+                expected,
+                "bundle in {}",
+                extension
+            );
+        }
+    }
+
+    #[test]
+    fn save_imported_network_without_scenario() {
+        let network = load("data/running_example").expect("could not load network");
+        let directory = "/tmp/rimulation_imported";
+        let path = format!("{}/network.toml", directory);
+        let _ = fs::remove_dir_all(directory);
+
+        save_without_scenario(&network.topology, &network.parameters, &path)
+            .expect("could not save bundle");
+
+        assert!(Path::new(&path).is_file());
+        let mut bundle: serde_json::Value = bundle::read(&path).expect("could not read bundle");
+        assert!(bundle.get("scenario").is_none());
+        bundle["scenario"] = serde_json::to_value(&network.scenario).expect("could not encode");
+        bundle::write(&bundle, &path).expect("could not write bundle");
+        let loaded = load(&path).expect("could not load bundle");
+        assert_eq!(loaded.topology.pipes.len(), network.topology.pipes.len());
+        assert_eq!(loaded.parameters.pipes, network.parameters.pipes);
+    }
+
+    #[test]
+    fn load_csv_signal_relative_to_bundle() {
+        let directory = "/tmp/rimulation_csv_signal_bundle";
+        fs::create_dir_all(directory).expect("could not create directory");
+        fs::copy(
+            "data/fixed_velocity/csv_signal/source_temperature.csv",
+            format!("{}/source_temperature.csv", directory),
+        )
+        .expect("could not copy time series");
+
+        let part = |file: &str| -> serde_json::Value {
+            let file = fs::File::open(format!("data/fixed_velocity/csv_signal/{}.json", file))
+                .expect("could not open file");
+            from_reader(file).expect("could not parse file")
+        };
+        let bundle = serde_json::json!({
+            "topology": part("topology"),
+            "scenario": part("scenario"),
+            "parameters": part("parameters"),
+        });
+        let path = format!("{}/network.yaml", directory);
+        bundle::write(&bundle, &path).expect("could not write bundle");
+
+        let network = load(&path).expect("could not load bundle");
+
+        let Signal::Poly { data, .. } = &network.scenario.signals["S1_temperature"] else {
+            panic!("csv signal was not converted to a polynomial signal");
+        };
+        assert_eq!(data.len(), 10);
+    }
+
+    #[test]
+    fn output_next_to_bundle() {
+        assert_eq!(
+            output_path("data/running_example", "result"),
+            "data/running_example/result"
+        );
+        assert_eq!(
+            output_path("cases/triangle.toml", "result"),
+            "cases/triangle_result"
+        );
+    }
+
+    #[test]
+    fn read_time_series_without_headers() {
+        let path = Path::new("/tmp/rimulation_time_series.csv");
+        fs::write(path, "1;0;10\n2;7;11\n3;8;12\n").expect("could not write time series");
+
+        let signal = Signal::Csv {
+            path: String::from("rimulation_time_series.csv"),
+            time_column: Column::Index(0),
+            value_column: Column::Index(2),
+            has_headers: false,
+            time_unit: TimeUnit::Seconds,
+            scale: 2.,
+            degree: 3,
+            gaps: GapPolicy::HoldLast,
+        };
+
+        assert!(
+            signal.clone().load_data(Path::new("/tmp")).is_err(),
+            "semicolons are not a valid delimiter"
+        );
+
+        fs::write(path, "60,0,10\n120,7,11\n180,8,12\n").expect("could not write time series");
+
+        assert_eq!(
+            signal
+                .load_data(Path::new("/tmp"))
+                .expect("could not load time series"),
+            Signal::Poly {
+                degree: 3,
+                scale: 2.,
+                data: vec![
+                    DataPoint { t: 1., v: 10. },
+                    DataPoint { t: 2., v: 11. },
+                    DataPoint { t: 3., v: 12. },
+                ],
+                gaps: GapPolicy::HoldLast,
+            }
+        );
+    }
+
+    #[test]
+    fn save_and_load() {
+        let path = "/tmp/rimulation_custom_format";
+        let network = load("data/custom_format").expect("could not load network");
+        save(&network, path).expect("could not save network");
+        let reloaded = load(path).expect("could not load saved network");
+
+        assert_eq!(reloaded.topology.nodes.len(), network.topology.nodes.len());
+        assert_eq!(reloaded.scenario.signals, network.scenario.signals);
+        assert_eq!(
+            reloaded.parameters.parameters,
+            network.parameters.parameters
+        );
+        assert_eq!(reloaded.parameters.pipes, network.parameters.pipes);
+    }
+
+    #[test]
+    fn read_time_series_unknown_column() {
+        let result = read_time_series(
+            Path::new("data/fixed_velocity/csv_signal/source_temperature.csv"),
+            &Column::Name(String::from("time")),
+            &Column::Name(String::from("pressure")),
+            true,
+            TimeUnit::Minutes,
+        );
+
+        assert!(result
+            .expect_err("column does not exist")
+            .to_string()
+            .contains("no column with header 'pressure'"));
+    }
 }
 
 #[cfg(test)]
@@ -133,6 +133,8 @@ fn create_test_scenario(num_consumers: usize, num_sources: usize) -> Scenario {
 
 fn create_test_parameters(pipes: &[Pipe]) -> Parameters {
     Parameters {
+        friction_model: Default::default(),
+        units: GeometryUnits::nominal(),
         parameters: [(
             String::from("dummy_pipe_parameters"),
             DUMMY_PARSED_PIPE_PARAMETERS,
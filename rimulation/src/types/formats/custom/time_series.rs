@@ -0,0 +1,164 @@
+use super::DataPoint;
+
+use anyhow::{anyhow, Error};
+use csv::{ReaderBuilder, StringRecord};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Column of a CSV file, selected by its position or by its header
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Column {
+    Index(usize),
+    Name(String),
+}
+
+impl Column {
+    fn index(&self, headers: Option<&StringRecord>) -> Result<usize, Error> {
+        match self {
+            Column::Index(i) => Ok(*i),
+            Column::Name(name) => headers
+                .ok_or(anyhow!(
+                    "column '{}' selected by name, but file has no headers",
+                    name
+                ))?
+                .iter()
+                .position(|header| header.trim() == name)
+                .ok_or(anyhow!("no column with header '{}'", name)),
+        }
+    }
+}
+
+/// Unit of the time column of a time series
+///
+/// Signals are evaluated in minutes, so times are converted to minutes while loading.
+#[derive(Debug, PartialEq, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TimeUnit {
+    Seconds,
+    #[default]
+    Minutes,
+    Hours,
+    Days,
+}
+
+impl TimeUnit {
+    pub fn in_minutes(&self) -> f64 {
+        match self {
+            TimeUnit::Seconds => 1. / 60.,
+            TimeUnit::Minutes => 1.,
+            TimeUnit::Hours => 60.,
+            TimeUnit::Days => 24. * 60.,
+        }
+    }
+}
+
+pub fn default_time_column() -> Column {
+    Column::Index(0)
+}
+
+pub fn default_value_column() -> Column {
+    Column::Index(1)
+}
+
+pub fn default_true() -> bool {
+    true
+}
+
+pub fn default_scale() -> f64 {
+    1.
+}
+
+pub fn default_degree() -> usize {
+    1
+}
+
+/// Reads the selected columns of a CSV file
+///
+/// Returns one vector of values per selected column. Empty values are read as NaN.
+pub fn read_columns(
+    path: &Path,
+    columns: &[Column],
+    has_headers: bool,
+) -> Result<Vec<Vec<f64>>, Error> {
+    let mut reader = ReaderBuilder::new()
+        .has_headers(has_headers)
+        .trim(csv::Trim::All)
+        .from_path(path)
+        .map_err(|err| anyhow!("could not open {}: {}", path.display(), err))?;
+
+    let headers = has_headers.then(|| reader.headers().cloned()).transpose()?;
+    let indices = columns
+        .iter()
+        .map(|column| column.index(headers.as_ref()))
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    let parse = |record: &StringRecord, i: usize, line: usize| -> Result<f64, Error> {
+        let field = record
+            .get(i)
+            .ok_or(anyhow!("line {} has no column {}", line, i))?;
+
+        if field.is_empty() {
+            return Ok(f64::NAN);
+        }
+
+        field
+            .parse()
+            .map_err(|err| anyhow!("could not parse '{}' in line {}: {}", field, line, err))
+    };
+
+    let mut values = vec![vec![]; columns.len()];
+    for record in reader.records() {
+        let record = record.map_err(|err| anyhow!("could not read {}: {}", path.display(), err))?;
+        let line = record.position().map(|p| p.line()).unwrap_or_default() as usize;
+
+        for (column, i) in values.iter_mut().zip(&indices) {
+            column.push(
+                parse(&record, *i, line)
+                    .map_err(|err| anyhow!("could not read {}: {}", path.display(), err))?,
+            );
+        }
+    }
+
+    Ok(values)
+}
+
+/// Reads the data points of a time series from a CSV file
+///
+/// Times are converted to minutes, values are not scaled.
+/// Empty values are read as NaN and have to be filled according to the gap policy of the signal.
+pub fn read_time_series(
+    path: &Path,
+    time_column: &Column,
+    value_column: &Column,
+    has_headers: bool,
+    time_unit: TimeUnit,
+) -> Result<Vec<DataPoint>, Error> {
+    let [times, values]: [Vec<f64>; 2] = read_columns(
+        path,
+        &[time_column.clone(), value_column.clone()],
+        has_headers,
+    )?
+    .try_into()
+    .expect("read two columns");
+
+    times
+        .into_iter()
+        .zip(values)
+        .enumerate()
+        .map(|(i, (t, v))| {
+            if t.is_nan() {
+                return Err(anyhow!(
+                    "could not read time series {}: row {} has no time",
+                    path.display(),
+                    i + 1
+                ));
+            }
+
+            Ok(DataPoint {
+                t: t * time_unit.in_minutes(),
+                v,
+            })
+        })
+        .collect()
+}
@@ -0,0 +1,122 @@
+use super::PipeParameters;
+
+use serde::{Deserialize, Serialize};
+
+/// Unit of a length in the pipe parameters
+///
+/// Pipe geometry is stored in meters internally, so lengths are converted to meters while loading.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub enum LengthUnit {
+    #[serde(rename = "mm")]
+    Millimeters,
+    #[serde(rename = "cm")]
+    Centimeters,
+    #[serde(rename = "m")]
+    Meters,
+    #[serde(rename = "km")]
+    Kilometers,
+}
+
+impl LengthUnit {
+    pub fn in_meters(&self) -> f64 {
+        match self {
+            LengthUnit::Millimeters => 1e-3,
+            LengthUnit::Centimeters => 1e-2,
+            LengthUnit::Meters => 1.,
+            LengthUnit::Kilometers => 1e3,
+        }
+    }
+}
+
+/// Units of the pipe geometry in the parameters file
+///
+/// Without units, all geometry is given in meters, so parameter files from before units were
+/// stated keep their meaning.
+#[derive(Debug, PartialEq, Clone, Copy, Serialize, Deserialize)]
+pub struct GeometryUnits {
+    #[serde(default = "default_length_unit")]
+    pub length: LengthUnit,
+    #[serde(default = "default_diameter_unit")]
+    pub diameter: LengthUnit,
+    #[serde(default = "default_roughness_unit")]
+    pub roughness: LengthUnit,
+}
+
+impl GeometryUnits {
+    /// Lengths in meters, diameters and roughnesses in millimeters, as in pipe catalogs
+    ///
+    /// The importers write parameters in these units.
+    pub fn nominal() -> Self {
+        Self {
+            length: LengthUnit::Meters,
+            diameter: LengthUnit::Millimeters,
+            roughness: LengthUnit::Millimeters,
+        }
+    }
+}
+
+impl Default for GeometryUnits {
+    fn default() -> Self {
+        Self {
+            length: default_length_unit(),
+            diameter: default_diameter_unit(),
+            roughness: default_roughness_unit(),
+        }
+    }
+}
+
+pub fn default_length_unit() -> LengthUnit {
+    LengthUnit::Meters
+}
+
+pub fn default_diameter_unit() -> LengthUnit {
+    LengthUnit::Meters
+}
+
+pub fn default_roughness_unit() -> LengthUnit {
+    LengthUnit::Meters
+}
+
+impl PipeParameters {
+    /// Converts the geometry given in `units` to meters
+    pub fn in_meters(self, units: &GeometryUnits) -> Self {
+        self.scaled(
+            units.length.in_meters(),
+            units.diameter.in_meters(),
+            units.roughness.in_meters(),
+        )
+    }
+
+    /// Converts the geometry given in meters to `units`
+    pub fn in_units(self, units: &GeometryUnits) -> Self {
+        self.scaled(
+            1. / units.length.in_meters(),
+            1. / units.diameter.in_meters(),
+            1. / units.roughness.in_meters(),
+        )
+    }
+
+    fn scaled(self, length_factor: f64, diameter_factor: f64, roughness_factor: f64) -> Self {
+        match self {
+            PipeParameters::Full {
+                length,
+                diameter,
+                transmittance,
+                roughness,
+                zeta,
+                friction_model,
+            } => PipeParameters::Full {
+                length: length * length_factor,
+                diameter: diameter * diameter_factor,
+                transmittance,
+                roughness: roughness * roughness_factor,
+                zeta,
+                friction_model,
+            },
+            PipeParameters::FixedVelocity { length, velocity } => PipeParameters::FixedVelocity {
+                length: length * length_factor,
+                velocity,
+            },
+        }
+    }
+}
@@ -0,0 +1,1105 @@
+//! Validation of networks in the custom format
+//!
+//! In contrast to [`load`](super::load), which stops at the first problem, every component is
+//! read on its own and all problems are collected, each with the file, the path of the offending
+//! value in the file and the name of the component.
+
+use super::{
+    bundle, Consumer, ConsumerInput, GeometryUnits, Input, Node, Pipe, PipeParameters, Settings,
+    Signal, Source,
+};
+use crate::{friction::Friction, types::signal::resolve};
+
+use serde::de::DeserializeOwned;
+use serde_json::{from_reader, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::Path,
+};
+
+pub const TOPOLOGY: &str = "topology.json";
+pub const SCENARIO: &str = "scenario.json";
+pub const PARAMETERS: &str = "parameters.json";
+pub const BUNDLE: &str = "bundle";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Severity {
+    /// The network cannot be simulated
+    Error,
+    /// The network can be simulated, but probably not as intended
+    Warning,
+}
+
+#[derive(Debug, Clone)]
+pub struct Problem {
+    pub severity: Severity,
+    pub file: &'static str,
+    /// Path of the offending value in the file, e.g. `pipes[3].src`, empty for the whole file
+    pub path: String,
+    /// Kind and name of the component, e.g. `pipe 'P1'`
+    pub component: Option<String>,
+    pub message: String,
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.severity {
+            Severity::Error => write!(f, "error: {}", self.file)?,
+            Severity::Warning => write!(f, "warning: {}", self.file)?,
+        }
+        if !self.path.is_empty() {
+            write!(f, " at {}", self.path)?;
+        }
+        if let Some(component) = &self.component {
+            write!(f, " ({})", component)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+/// Component read from a file
+struct Located<T> {
+    path: String,
+    kind: &'static str,
+    name: String,
+    value: T,
+}
+
+impl<T> Located<T> {
+    fn component(&self) -> Option<String> {
+        (!self.name.is_empty()).then(|| format!("{} '{}'", self.kind, self.name))
+    }
+}
+
+#[derive(Default)]
+struct Problems(Vec<Problem>);
+
+impl Problems {
+    fn push(
+        &mut self,
+        severity: Severity,
+        file: &'static str,
+        path: &str,
+        component: Option<String>,
+        message: String,
+    ) {
+        self.0.push(Problem {
+            severity,
+            file,
+            path: path.to_string(),
+            component,
+            message,
+        });
+    }
+
+    fn error<T>(&mut self, file: &'static str, located: &Located<T>, path: &str, message: String) {
+        self.push(Severity::Error, file, path, located.component(), message);
+    }
+
+    fn warning<T>(&mut self, file: &'static str, located: &Located<T>, message: String) {
+        self.push(
+            Severity::Warning,
+            file,
+            &located.path,
+            located.component(),
+            message,
+        );
+    }
+}
+
+/// Appends the path reported by serde to the path of the deserialized value
+fn join(path: &str, inner: &serde_path_to_error::Path) -> String {
+    let inner = inner.to_string();
+    if inner == "." {
+        path.to_string()
+    } else if path.is_empty() || inner.starts_with('[') {
+        format!("{}{}", path, inner)
+    } else {
+        format!("{}.{}", path, inner)
+    }
+}
+
+fn read(directory: &Path, file: &'static str, problems: &mut Problems) -> Option<Value> {
+    let reader = match fs::File::open(directory.join(file)) {
+        Ok(reader) => reader,
+        Err(err) => {
+            problems.push(
+                Severity::Error,
+                file,
+                "",
+                None,
+                format!("could not open file: {}", err),
+            );
+            return None;
+        }
+    };
+
+    match from_reader(reader) {
+        Ok(Value::Object(object)) => Some(Value::Object(object)),
+        Ok(_) => {
+            problems.push(
+                Severity::Error,
+                file,
+                "",
+                None,
+                String::from("expected an object"),
+            );
+            None
+        }
+        Err(err) => {
+            problems.push(
+                Severity::Error,
+                file,
+                "",
+                None,
+                format!("invalid JSON: {}", err),
+            );
+            None
+        }
+    }
+}
+
+fn parse<T: DeserializeOwned>(
+    value: &Value,
+    file: &'static str,
+    path: &str,
+    component: Option<String>,
+    problems: &mut Problems,
+) -> Option<T> {
+    serde_path_to_error::deserialize(value)
+        .map_err(|err| {
+            problems.push(
+                Severity::Error,
+                file,
+                &join(path, err.path()),
+                component,
+                err.inner().to_string(),
+            )
+        })
+        .ok()
+}
+
+/// Looks up a field of the top level object of a file, reports it as missing if `required`
+fn field<'a>(
+    contents: Option<&'a Value>,
+    file: &'static str,
+    key: &str,
+    required: bool,
+    problems: &mut Problems,
+) -> Option<&'a Value> {
+    let value = contents?.get(key);
+    if value.is_none() && required {
+        problems.push(
+            Severity::Error,
+            file,
+            key,
+            None,
+            format!("missing field `{}`", key),
+        );
+    }
+    value
+}
+
+/// Reads every element of an array of named components
+fn parse_array<T: DeserializeOwned>(
+    contents: Option<&Value>,
+    file: &'static str,
+    key: &str,
+    kind: &'static str,
+    problems: &mut Problems,
+) -> Vec<Located<T>> {
+    let Some(value) = field(contents, file, key, true, problems) else {
+        return vec![];
+    };
+    let Some(elements) = value.as_array() else {
+        problems.push(
+            Severity::Error,
+            file,
+            key,
+            None,
+            String::from("expected an array"),
+        );
+        return vec![];
+    };
+
+    elements
+        .iter()
+        .enumerate()
+        .filter_map(|(i, element)| {
+            let path = format!("{}[{}]", key, i);
+            let name = element
+                .get("name")
+                .and_then(Value::as_str)
+                .unwrap_or_default()
+                .to_string();
+            let located = Located {
+                path,
+                kind,
+                name,
+                value: (),
+            };
+
+            parse(element, file, &located.path, located.component(), problems).map(|value| {
+                Located {
+                    path: located.path,
+                    kind,
+                    name: located.name,
+                    value,
+                }
+            })
+        })
+        .collect()
+}
+
+/// Reads every entry of an object of components keyed by their names
+fn parse_map<T: DeserializeOwned>(
+    contents: Option<&Value>,
+    file: &'static str,
+    key: &str,
+    kind: &'static str,
+    problems: &mut Problems,
+) -> Vec<Located<T>> {
+    let Some(value) = field(contents, file, key, true, problems) else {
+        return vec![];
+    };
+    let Some(entries) = value.as_object() else {
+        problems.push(
+            Severity::Error,
+            file,
+            key,
+            None,
+            String::from("expected an object"),
+        );
+        return vec![];
+    };
+
+    entries
+        .iter()
+        .filter_map(|(name, entry)| {
+            let path = format!("{}.{}", key, name);
+            let component = Some(format!("{} '{}'", kind, name));
+
+            parse(entry, file, &path, component, problems).map(|value| Located {
+                path,
+                kind,
+                name: name.clone(),
+                value,
+            })
+        })
+        .collect()
+}
+
+/// Names of all components in an array or object, including those that could not be read
+fn names(contents: Option<&Value>, key: &str) -> HashSet<String> {
+    match contents.and_then(|contents| contents.get(key)) {
+        Some(Value::Array(elements)) => elements
+            .iter()
+            .filter_map(|element| element.get("name")?.as_str())
+            .map(String::from)
+            .collect(),
+        Some(Value::Object(entries)) => entries.keys().cloned().collect(),
+        _ => HashSet::new(),
+    }
+}
+
+/// Reports components of the same kind sharing a name
+fn check_unique_names<T>(file: &'static str, components: &[Located<T>], problems: &mut Problems) {
+    let mut names = HashSet::new();
+    for component in components {
+        if !names.insert(&component.name) {
+            problems.error(
+                file,
+                component,
+                &component.path,
+                format!("another {} has the same name", component.kind),
+            );
+        }
+    }
+}
+
+fn check_topology(
+    nodes: &[Located<Node>],
+    node_names: &HashSet<String>,
+    edges: &[Located<(String, String)>],
+    problems: &mut Problems,
+) {
+    check_unique_names(TOPOLOGY, nodes, problems);
+
+    let mut connected = HashSet::new();
+    for edge in edges {
+        let (src, tgt) = &edge.value;
+        // only the feed side of consumers and sources is simulated, so their return nodes
+        // may be omitted
+        let endpoints = match edge.kind {
+            "consumer" => vec![("src", src)],
+            "source" => vec![("tgt", tgt)],
+            _ => vec![("src", src), ("tgt", tgt)],
+        };
+        for (key, node) in endpoints {
+            if !node_names.contains(node) {
+                problems.error(
+                    TOPOLOGY,
+                    edge,
+                    &format!("{}.{}", edge.path, key),
+                    format!("node '{}' does not exist", node),
+                );
+            }
+        }
+
+        if src == tgt {
+            problems.error(
+                TOPOLOGY,
+                edge,
+                &edge.path,
+                format!("connects node '{}' to itself", src),
+            );
+        }
+
+        if edge.kind == "pipe" {
+            connected.insert(src);
+            connected.insert(tgt);
+        }
+    }
+
+    for node in nodes {
+        if !connected.contains(&node.name) {
+            problems.error(
+                TOPOLOGY,
+                node,
+                &node.path,
+                String::from("not connected to any pipe"),
+            );
+        }
+    }
+
+    if !edges.iter().any(|edge| edge.kind == "source") {
+        problems.push(
+            Severity::Error,
+            TOPOLOGY,
+            "sources",
+            None,
+            String::from("network does not have any sources"),
+        );
+    }
+
+    let source_nodes: HashSet<&String> = edges
+        .iter()
+        .filter(|edge| edge.kind == "source")
+        .map(|edge| &edge.value.1)
+        .collect();
+    if source_nodes.len() > 1 {
+        problems.push(
+            Severity::Error,
+            TOPOLOGY,
+            "sources",
+            None,
+            format!(
+                "network has sources at {} nodes, multiple sources are not supported yet",
+                source_nodes.len()
+            ),
+        );
+    }
+}
+
+fn check_parameters(
+    pipes: &[Located<Pipe>],
+    pipe_names: &HashSet<String>,
+    parameters: &[Located<PipeParameters>],
+    parameter_names: &HashSet<String>,
+    pipe_parameters: &[Located<String>],
+    problems: &mut Problems,
+) {
+    let assigned: HashSet<&String> = pipe_parameters.iter().map(|pipe| &pipe.name).collect();
+    for pipe in pipes {
+        if !assigned.contains(&pipe.name) {
+            problems.error(
+                TOPOLOGY,
+                pipe,
+                &pipe.path,
+                format!("no parameters assigned in {}", PARAMETERS),
+            );
+        }
+    }
+
+    for pipe in pipe_parameters {
+        if !parameter_names.contains(&pipe.value) {
+            problems.error(
+                PARAMETERS,
+                pipe,
+                &pipe.path,
+                format!("parameters '{}' do not exist", pipe.value),
+            );
+        }
+        if !pipe_names.contains(&pipe.name) {
+            problems.warning(PARAMETERS, pipe, String::from("pipe does not exist"));
+        }
+    }
+
+    let used: HashSet<&String> = pipe_parameters.iter().map(|pipe| &pipe.value).collect();
+    for set in parameters {
+        if !used.contains(&set.name) {
+            problems.warning(PARAMETERS, set, String::from("not used by any pipe"));
+        }
+    }
+}
+
+fn check_settings(settings: &Located<Settings>, problems: &mut Problems) {
+    let Settings {
+        time_start,
+        time_end,
+        time_step,
+        tolerance,
+        ..
+    } = settings.value;
+
+    if time_end <= time_start {
+        problems.error(
+            SCENARIO,
+            settings,
+            "settings.time_end",
+            format!("ends at {} before it starts at {}", time_end, time_start),
+        );
+    }
+    if time_step <= 0. {
+        problems.error(
+            SCENARIO,
+            settings,
+            "settings.time_step",
+            String::from("must be positive"),
+        );
+    }
+    if tolerance <= 0. {
+        problems.error(
+            SCENARIO,
+            settings,
+            "settings.tolerance",
+            String::from("must be positive"),
+        );
+    }
+}
+
+/// Checks that every consumer or source has an input of the right kind
+fn check_component_inputs<T>(
+    components: &[Located<T>],
+    names: &HashSet<String>,
+    component_inputs: &[Located<String>],
+    inputs: &HashMap<&String, &Input>,
+    input_names: &HashSet<String>,
+    is_kind: fn(&Input) -> bool,
+    problems: &mut Problems,
+) {
+    let key = format!("{}_inputs", components.first().map_or("", |c| c.kind));
+    let assigned: HashSet<&String> = component_inputs.iter().map(|c| &c.name).collect();
+
+    for component in components {
+        if !assigned.contains(&component.name) {
+            problems.error(
+                SCENARIO,
+                component,
+                &key,
+                format!("{} has no inputs", component.kind),
+            );
+        }
+    }
+
+    for component_input in component_inputs {
+        match inputs.get(&component_input.value) {
+            None if !input_names.contains(&component_input.value) => problems.error(
+                SCENARIO,
+                component_input,
+                &component_input.path,
+                format!("input '{}' does not exist", component_input.value),
+            ),
+            Some(input) if !is_kind(input) => problems.error(
+                SCENARIO,
+                component_input,
+                &component_input.path,
+                format!(
+                    "input '{}' is not an input of a {}",
+                    component_input.value, component_input.kind
+                ),
+            ),
+            _ => {}
+        }
+
+        if !names.contains(&component_input.name) {
+            problems.warning(
+                SCENARIO,
+                component_input,
+                format!("{} does not exist", component_input.kind),
+            );
+        }
+    }
+}
+
+fn input_signals(input: &Input) -> Vec<(&'static str, &String)> {
+    match input {
+        Input::Consumer {
+            demand,
+            return_temperature,
+        } => vec![
+            ("demand", demand),
+            ("return_temperature", return_temperature),
+        ],
+        Input::Source {
+            base_pressure,
+            pressure_lift,
+            temperature,
+        } => vec![
+            ("base_pressure", base_pressure),
+            ("pressure_lift", pressure_lift),
+            ("temperature", temperature),
+        ],
+    }
+}
+
+fn check_signals(
+    directory: &Path,
+    signals: Vec<Located<Signal>>,
+    signal_names: &HashSet<String>,
+    inputs: &[Located<Input>],
+    used_inputs: &HashSet<&String>,
+    problems: &mut Problems,
+) {
+    // time series are read from their files, so that broken files are reported
+    let signals: Vec<Located<Signal>> = signals
+        .into_iter()
+        .filter_map(|signal| match signal.value.clone().load_data(directory) {
+            Ok(value) => Some(Located { value, ..signal }),
+            Err(err) => {
+                problems.error(SCENARIO, &signal, &signal.path, err.to_string());
+                None
+            }
+        })
+        .collect();
+
+    let by_name: HashMap<String, Signal> = signals
+        .iter()
+        .map(|signal| (signal.name.clone(), signal.value.clone()))
+        .collect();
+    for signal in &signals {
+        // signals referencing signals that could not be read have been reported already
+        let references = signal.value.references();
+        if references
+            .iter()
+            .any(|name| signal_names.contains(*name) && !by_name.contains_key(*name))
+        {
+            continue;
+        }
+
+        if let Err(err) = resolve(&signal.name, &by_name) {
+            problems.error(SCENARIO, signal, &signal.path, err.to_string());
+        }
+    }
+
+    let mut referenced: HashSet<&String> = HashSet::new();
+    for input in inputs {
+        for (key, signal) in input_signals(&input.value) {
+            referenced.insert(signal);
+            if !signal_names.contains(signal) {
+                problems.error(
+                    SCENARIO,
+                    input,
+                    &format!("{}.{}", input.path, key),
+                    format!("signal '{}' does not exist", signal),
+                );
+            }
+        }
+
+        if !used_inputs.contains(&input.name) {
+            problems.warning(
+                SCENARIO,
+                input,
+                String::from("not used by any consumer or source"),
+            );
+        }
+    }
+
+    for signal in &signals {
+        referenced.extend(signal.value.references());
+    }
+    for signal in &signals {
+        if !referenced.contains(&signal.name) {
+            problems.warning(
+                SCENARIO,
+                signal,
+                String::from("not used by any input or signal"),
+            );
+        }
+    }
+}
+
+/// Reads the topology, scenario and parameters of a [`bundle`]
+fn read_bundle(path: &str, problems: &mut Problems) -> [Option<Value>; 3] {
+    let mut bundle: serde_json::Map<String, Value> = match bundle::read(path) {
+        Ok(bundle) => bundle,
+        Err(err) => {
+            problems.push(Severity::Error, BUNDLE, "", None, err.to_string());
+            return [None, None, None];
+        }
+    };
+
+    [TOPOLOGY, SCENARIO, PARAMETERS].map(|file| {
+        let key = section(file);
+        match bundle.remove(key) {
+            Some(Value::Object(object)) => Some(Value::Object(object)),
+            Some(_) => {
+                problems.push(
+                    Severity::Error,
+                    BUNDLE,
+                    key,
+                    None,
+                    String::from("expected an object"),
+                );
+                None
+            }
+            None => {
+                problems.push(
+                    Severity::Error,
+                    BUNDLE,
+                    "",
+                    None,
+                    format!("missing field `{}`", key),
+                );
+                None
+            }
+        }
+    })
+}
+
+/// Key of the contents of a file in a [`bundle`]
+fn section(file: &'static str) -> &'static str {
+    match file {
+        TOPOLOGY => "topology",
+        SCENARIO => "scenario",
+        PARAMETERS => "parameters",
+        _ => file,
+    }
+}
+
+/// Validates the network in the custom format in the directory `path`, or in the [`bundle`]
+/// `path`
+///
+/// Returns all problems found, the network can be simulated if none of them is an error. The
+/// problems of a bundle name the part of the bundle instead of the file.
+pub fn validate(path: &str) -> Vec<Problem> {
+    let is_bundle = bundle::is_bundle(path);
+    let directory = if is_bundle {
+        bundle::directory(path)
+    } else {
+        Path::new(path)
+    };
+    let mut problems = Problems::default();
+
+    let [topology, scenario, parameters] = if is_bundle {
+        read_bundle(path, &mut problems)
+    } else {
+        [
+            read(directory, TOPOLOGY, &mut problems),
+            read(directory, SCENARIO, &mut problems),
+            read(directory, PARAMETERS, &mut problems),
+        ]
+    };
+
+    // topology
+
+    let nodes: Vec<Located<Node>> =
+        parse_array(topology.as_ref(), TOPOLOGY, "nodes", "node", &mut problems);
+    let pipes: Vec<Located<Pipe>> =
+        parse_array(topology.as_ref(), TOPOLOGY, "pipes", "pipe", &mut problems);
+    let consumers: Vec<Located<Consumer>> = parse_array(
+        topology.as_ref(),
+        TOPOLOGY,
+        "consumers",
+        "consumer",
+        &mut problems,
+    );
+    let sources: Vec<Located<Source>> = parse_array(
+        topology.as_ref(),
+        TOPOLOGY,
+        "sources",
+        "source",
+        &mut problems,
+    );
+
+    check_unique_names(TOPOLOGY, &pipes, &mut problems);
+    check_unique_names(TOPOLOGY, &consumers, &mut problems);
+    check_unique_names(TOPOLOGY, &sources, &mut problems);
+
+    let edge = |path: &String, kind, name: &String, src: &String, tgt: &String| Located {
+        path: path.clone(),
+        kind,
+        name: name.clone(),
+        value: (src.clone(), tgt.clone()),
+    };
+    let edges: Vec<Located<(String, String)>> = pipes
+        .iter()
+        .map(|p| edge(&p.path, p.kind, &p.name, &p.value.src, &p.value.tgt))
+        .chain(
+            consumers
+                .iter()
+                .map(|c| edge(&c.path, c.kind, &c.name, &c.value.src, &c.value.tgt)),
+        )
+        .chain(
+            sources
+                .iter()
+                .map(|s| edge(&s.path, s.kind, &s.name, &s.value.src, &s.value.tgt)),
+        )
+        .collect();
+    check_topology(
+        &nodes,
+        &names(topology.as_ref(), "nodes"),
+        &edges,
+        &mut problems,
+    );
+
+    // parameters
+
+    if let Some(friction_model) = field(
+        parameters.as_ref(),
+        PARAMETERS,
+        "friction_model",
+        false,
+        &mut problems,
+    ) {
+        parse::<Friction>(
+            friction_model,
+            PARAMETERS,
+            "friction_model",
+            None,
+            &mut problems,
+        );
+    }
+    if let Some(units) = field(
+        parameters.as_ref(),
+        PARAMETERS,
+        "units",
+        false,
+        &mut problems,
+    ) {
+        parse::<GeometryUnits>(units, PARAMETERS, "units", None, &mut problems);
+    }
+    let parameter_sets: Vec<Located<PipeParameters>> = parse_map(
+        parameters.as_ref(),
+        PARAMETERS,
+        "parameters",
+        "parameters",
+        &mut problems,
+    );
+    let pipe_parameters: Vec<Located<String>> = parse_map(
+        parameters.as_ref(),
+        PARAMETERS,
+        "pipes",
+        "pipe",
+        &mut problems,
+    );
+    if parameters.is_some() {
+        check_parameters(
+            &pipes,
+            &names(topology.as_ref(), "pipes"),
+            &parameter_sets,
+            &names(parameters.as_ref(), "parameters"),
+            &pipe_parameters,
+            &mut problems,
+        );
+    }
+
+    // scenario
+
+    if let Some(settings) = field(scenario.as_ref(), SCENARIO, "settings", true, &mut problems) {
+        if let Some(value) = parse(settings, SCENARIO, "settings", None, &mut problems) {
+            let settings = Located {
+                path: String::from("settings"),
+                kind: "settings",
+                name: String::new(),
+                value,
+            };
+            check_settings(&settings, &mut problems);
+        }
+    }
+
+    let signals: Vec<Located<Signal>> = parse_map(
+        scenario.as_ref(),
+        SCENARIO,
+        "signals",
+        "signal",
+        &mut problems,
+    );
+    let inputs: Vec<Located<Input>> = parse_map(
+        scenario.as_ref(),
+        SCENARIO,
+        "inputs",
+        "input",
+        &mut problems,
+    );
+    let consumer_inputs: Vec<Located<ConsumerInput>> = parse_map(
+        scenario.as_ref(),
+        SCENARIO,
+        "consumer_inputs",
+        "consumer",
+        &mut problems,
+    );
+    let source_inputs: Vec<Located<String>> = parse_map(
+        scenario.as_ref(),
+        SCENARIO,
+        "source_inputs",
+        "source",
+        &mut problems,
+    );
+
+    if scenario.is_some() {
+        let inputs_by_name: HashMap<&String, &Input> = inputs
+            .iter()
+            .map(|input| (&input.name, &input.value))
+            .collect();
+        let input_names = names(scenario.as_ref(), "inputs");
+
+        let consumer_inputs: Vec<Located<String>> = consumer_inputs
+            .into_iter()
+            .map(|consumer| Located {
+                path: format!("{}.input", consumer.path),
+                kind: consumer.kind,
+                name: consumer.name,
+                value: consumer.value.input,
+            })
+            .collect();
+        check_component_inputs(
+            &consumers,
+            &names(topology.as_ref(), "consumers"),
+            &consumer_inputs,
+            &inputs_by_name,
+            &input_names,
+            |input| matches!(input, Input::Consumer { .. }),
+            &mut problems,
+        );
+        check_component_inputs(
+            &sources,
+            &names(topology.as_ref(), "sources"),
+            &source_inputs,
+            &inputs_by_name,
+            &input_names,
+            |input| matches!(input, Input::Source { .. }),
+            &mut problems,
+        );
+
+        let used_inputs: HashSet<&String> = consumer_inputs
+            .iter()
+            .chain(source_inputs.iter())
+            .map(|component| &component.value)
+            .collect();
+        check_signals(
+            directory,
+            signals,
+            &names(scenario.as_ref(), "signals"),
+            &inputs,
+            &used_inputs,
+            &mut problems,
+        );
+    }
+
+    if is_bundle {
+        for problem in &mut problems.0 {
+            problem.file = section(problem.file);
+        }
+    }
+    problems.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_network(path: &str, topology: &str, scenario: &str, parameters: &str) {
+        fs::create_dir_all(path).expect("could not create directory");
+        fs::write(format!("{}/{}", path, TOPOLOGY), topology).expect("could not write topology");
+        fs::write(format!("{}/{}", path, SCENARIO), scenario).expect("could not write scenario");
+        fs::write(format!("{}/{}", path, PARAMETERS), parameters)
+            .expect("could not write parameters");
+    }
+
+    fn assert_reported(problems: &[Problem], expected: &str) {
+        assert!(
+            problems
+                .iter()
+                .any(|problem| problem.to_string() == expected),
+            "'{}' not reported, problems:\n{}",
+            expected,
+            problems
+                .iter()
+                .map(Problem::to_string)
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
+    }
+
+    #[test]
+    fn example_networks_are_valid() {
+        for path in ["data/custom_format", "data/fixed_velocity/csv_signal"] {
+            let problems = validate(path);
+            assert!(
+                problems
+                    .iter()
+                    .all(|problem| problem.severity == Severity::Warning),
+                "{:?}",
+                problems
+            );
+        }
+    }
+
+    #[test]
+    fn report_all_problems() {
+        let path = "/tmp/rimulation_invalid_network";
+        write_network(
+            path,
+            r#"{
+                "nodes": [
+                    {"name": "F1", "position": {"x": 0, "y": 0, "z": 0}, "feed": true},
+                    {"name": "F2", "position": {"x": 1, "y": 0}, "feed": true},
+                    {"name": "F3", "position": {"x": 2, "y": 0, "z": 0}, "feed": true},
+                    {"name": "R1", "position": {"x": 0, "y": 0, "z": 0}, "feed": false}
+                ],
+                "pipes": [
+                    {"name": "P1", "src": "F1", "tgt": "F4"},
+                    {"name": "P2", "src": "F1"}
+                ],
+                "consumers": [{"name": "C1", "src": "F9", "tgt": "R2"}],
+                "sources": [{"name": "S1", "src": "R1", "tgt": "F1"}]
+            }"#,
+            r#"{
+                "settings": {
+                    "feed_temperature": 100, "return_temperature": 65, "ground_temperature": 10,
+                    "time_start": 1, "time_end": 0, "time_step": 5, "ramp_time": 8,
+                    "num_iterations": 100, "tolerance": 1e-6
+                },
+                "signals": {
+                    "demand": {"const": {"scale": 1, "data": 1}},
+                    "unused": {"const": {"scale": 1, "data": 1}},
+                    "loop": {"scale": {"signal": "loop", "factor": 2}},
+                    "broken": {"poly": {"degree": 1, "scale": 1, "data": [{"t": "zero", "v": 1}]}}
+                },
+                "inputs": {
+                    "CON": {"demand": "demand", "return_temperature": "missing"},
+                    "SRC": {"base_pressure": "demand", "pressure_lift": "demand", "temperature": "demand"},
+                    "SPARE": {"demand": "broken", "return_temperature": "demand"}
+                },
+                "consumer_inputs": {
+                    "C1": {"input": "SRC", "factors": {"yearly_demand": 1, "normal_return_temperature": 60}}
+                },
+                "source_inputs": {}
+            }"#,
+            r#"{
+                "parameters": {
+                    "default": {"length": 1, "velocity": 1},
+                    "spare": {"length": 1, "velocity": 1}
+                },
+                "pipes": {"P2": "default", "P3": "missing"}
+            }"#,
+        );
+
+        let problems = validate(path);
+        for expected in [
+            "error: topology.json at nodes[1].position (node 'F2'): missing field `z`",
+            "error: topology.json at pipes[1] (pipe 'P2'): missing field `tgt`",
+            "error: topology.json at pipes[0].tgt (pipe 'P1'): node 'F4' does not exist",
+            "error: topology.json at consumers[0].src (consumer 'C1'): node 'F9' does not exist",
+            "error: topology.json at nodes[2] (node 'F3'): not connected to any pipe",
+            "error: topology.json at pipes[0] (pipe 'P1'): no parameters assigned in parameters.json",
+            "error: parameters.json at pipes.P3 (pipe 'P3'): parameters 'missing' do not exist",
+            "warning: parameters.json at pipes.P3 (pipe 'P3'): pipe does not exist",
+            "warning: parameters.json at parameters.spare (parameters 'spare'): not used by any pipe",
+            "error: scenario.json at settings.time_end: ends at 0 before it starts at 1",
+            "error: scenario.json at signals.broken.poly.data[0].t (signal 'broken'): invalid type: string \"zero\", expected f64",
+            "error: scenario.json at signals.loop (signal 'loop'): signal reference cycle: loop -> loop",
+            "error: scenario.json at inputs.CON.return_temperature (input 'CON'): signal 'missing' does not exist",
+            "error: scenario.json at consumer_inputs.C1.input (consumer 'C1'): input 'SRC' is not an input of a consumer",
+            "error: scenario.json at source_inputs (source 'S1'): source has no inputs",
+            "warning: scenario.json at inputs.SPARE (input 'SPARE'): not used by any consumer or source",
+            "warning: scenario.json at signals.unused (signal 'unused'): not used by any input or signal",
+        ] {
+            assert_reported(&problems, expected);
+        }
+
+        // components that could not be read are not reported again where they are referenced
+        assert!(!problems
+            .iter()
+            .any(|problem| problem.path == "pipes.P2" || problem.path == "inputs.SPARE.demand"));
+    }
+
+    #[test]
+    fn report_multiple_sources() {
+        let path = "/tmp/rimulation_multiple_sources";
+        write_network(
+            path,
+            r#"{
+                "nodes": [
+                    {"name": "F1", "position": {"x": 0, "y": 0, "z": 0}, "feed": true},
+                    {"name": "F2", "position": {"x": 1, "y": 0, "z": 0}, "feed": true},
+                    {"name": "R1", "position": {"x": 0, "y": 1, "z": 0}, "feed": false},
+                    {"name": "R2", "position": {"x": 1, "y": 1, "z": 0}, "feed": false}
+                ],
+                "pipes": [
+                    {"name": "P1", "src": "F1", "tgt": "F2"},
+                    {"name": "P2", "src": "R2", "tgt": "R1"}
+                ],
+                "consumers": [],
+                "sources": [
+                    {"name": "S1", "src": "R1", "tgt": "F1"},
+                    {"name": "S2", "src": "R2", "tgt": "F2"}
+                ]
+            }"#,
+            "{}",
+            "{}",
+        );
+
+        assert_reported(
+            &validate(path),
+            "error: topology.json at sources: network has sources at 2 nodes, multiple sources are not supported yet",
+        );
+    }
+
+    #[test]
+    fn report_invalid_json() {
+        let path = "/tmp/rimulation_invalid_json";
+        write_network(path, "{\"nodes\": [", "[]", "{}");
+
+        let problems = validate(path);
+        assert!(problems[0]
+            .to_string()
+            .starts_with("error: topology.json: invalid JSON: EOF while parsing"));
+        assert_reported(&problems, "error: scenario.json: expected an object");
+        assert_reported(
+            &problems,
+            "error: parameters.json at parameters: missing field `parameters`",
+        );
+    }
+
+    #[test]
+    fn validate_bundles() {
+        let network = super::super::load("data/custom_format").expect("could not load network");
+        let path = "/tmp/rimulation_custom_format.toml";
+        bundle::write(&network, path).expect("could not write bundle");
+        let problems = validate(path);
+        assert!(
+            problems
+                .iter()
+                .all(|problem| problem.severity == Severity::Warning),
+            "{:?}",
+            problems
+        );
+
+        let path = "/tmp/rimulation_invalid_bundle.yaml";
+        fs::write(
+            path,
+            "topology:\n  nodes:\n    - name: F1\n      feed: true\n  pipes: []\nscenario: []\n",
+        )
+        .expect("could not write bundle");
+        let problems = validate(path);
+        assert_reported(
+            &problems,
+            "error: topology at nodes[0] (node 'F1'): missing field `position`",
+        );
+        assert_reported(&problems, "error: bundle at scenario: expected an object");
+        assert_reported(&problems, "error: bundle: missing field `parameters`");
+    }
+}
@@ -0,0 +1,378 @@
+//! Reader of the sections of EPANET input files
+//!
+//! Every section starts with its name in brackets, e.g. `[PIPES]`, followed by one component
+//! per line with whitespace separated fields. Everything after a `;` is a comment.
+
+use anyhow::{anyhow, Error};
+use std::collections::HashMap;
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Junction {
+    pub id: String,
+    pub elevation: f64,
+    pub demand: f64,
+    pub pattern: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Reservoir {
+    pub id: String,
+    pub head: f64,
+    pub pattern: Option<String>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Pipe {
+    pub id: String,
+    pub node1: String,
+    pub node2: String,
+    pub length: f64,
+    pub diameter: f64,
+    pub roughness: f64,
+    pub minor_loss: f64,
+    /// `OPEN`, `CLOSED` or `CV` for pipes with a check valve
+    pub status: String,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Pump {
+    pub id: String,
+    pub node1: String,
+    pub node2: String,
+    /// Keywords and values, e.g. `HEAD` and the name of a curve or `POWER` and the power
+    pub properties: Vec<(String, String)>,
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Valve {
+    pub id: String,
+    pub node1: String,
+    pub node2: String,
+    pub diameter: f64,
+    /// `PRV`, `PSV`, `PBV`, `FCV`, `TCV` or `GPV`
+    pub valve_type: String,
+    pub setting: String,
+    pub minor_loss: f64,
+}
+
+#[derive(Debug, Default)]
+pub struct Inp {
+    pub junctions: Vec<Junction>,
+    pub reservoirs: Vec<Reservoir>,
+    pub pipes: Vec<Pipe>,
+    pub pumps: Vec<Pump>,
+    pub valves: Vec<Valve>,
+    pub coordinates: HashMap<String, (f64, f64)>,
+    /// Multipliers of the patterns in the order of the pattern time steps
+    pub patterns: HashMap<String, Vec<f64>>,
+    /// Values of `[OPTIONS]` by their upper case keywords
+    pub options: HashMap<String, String>,
+    /// Values of `[TIMES]` by their upper case keywords
+    pub times: HashMap<String, String>,
+    /// Sections that were not read although they contain data
+    pub ignored: Vec<String>,
+}
+
+/// Keywords of `[OPTIONS]` that are read
+const OPTIONS: [&str; 6] = [
+    "UNITS",
+    "HEADLOSS",
+    "PATTERN",
+    "TRIALS",
+    "ACCURACY",
+    "DEMAND MULTIPLIER",
+];
+
+/// Keywords of `[TIMES]` that are read
+const TIMES: [&str; 4] = [
+    "DURATION",
+    "HYDRAULIC TIMESTEP",
+    "PATTERN TIMESTEP",
+    "PATTERN START",
+];
+
+/// Splits an option like `DEMAND MULTIPLIER 1.5` into its known keyword and its value
+fn keyword_value(keywords: &[&str], fields: &[&str]) -> Option<(String, String)> {
+    let line = fields.join(" ").to_uppercase();
+    keywords
+        .iter()
+        .filter(|keyword| line.starts_with(*keyword))
+        .max_by_key(|keyword| keyword.len())
+        .map(|keyword| {
+            let num_words = keyword.split(' ').count();
+            (
+                keyword.to_string(),
+                fields[num_words.min(fields.len())..].join(" "),
+            )
+        })
+}
+
+fn number(field: &str, line: usize) -> Result<f64, Error> {
+    field
+        .parse()
+        .map_err(|_| anyhow!("line {}: '{}' is not a number", line, field))
+}
+
+/// Parses the number in the field `index`, or returns `default` if the field is missing
+fn number_or(fields: &[&str], index: usize, default: f64, line: usize) -> Result<f64, Error> {
+    fields
+        .get(index)
+        .map_or(Ok(default), |field| number(field, line))
+}
+
+fn expect_fields(section: &str, fields: &[&str], min: usize, line: usize) -> Result<(), Error> {
+    if fields.len() < min {
+        return Err(anyhow!(
+            "line {}: {} needs at least {} fields, but has {}",
+            line,
+            section,
+            min,
+            fields.len()
+        ));
+    }
+    Ok(())
+}
+
+/// Parses the sections of an EPANET input file
+pub fn parse(text: &str) -> Result<Inp, Error> {
+    let mut inp = Inp::default();
+    let mut section = String::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let line_number = i + 1;
+        let content = line.split(';').next().unwrap_or_default().trim();
+        if content.is_empty() {
+            continue;
+        }
+
+        if content.starts_with('[') {
+            section = content.trim_matches(['[', ']']).to_uppercase();
+            continue;
+        }
+
+        let fields: Vec<&str> = content.split_whitespace().collect();
+        let id = || fields[0].to_string();
+        match section.as_str() {
+            "JUNCTIONS" => {
+                expect_fields("junction", &fields, 2, line_number)?;
+                inp.junctions.push(Junction {
+                    id: id(),
+                    elevation: number(fields[1], line_number)?,
+                    demand: number_or(&fields, 2, 0., line_number)?,
+                    pattern: fields.get(3).map(|pattern| pattern.to_string()),
+                });
+            }
+            "RESERVOIRS" => {
+                expect_fields("reservoir", &fields, 2, line_number)?;
+                inp.reservoirs.push(Reservoir {
+                    id: id(),
+                    head: number(fields[1], line_number)?,
+                    pattern: fields.get(2).map(|pattern| pattern.to_string()),
+                });
+            }
+            "PIPES" => {
+                expect_fields("pipe", &fields, 6, line_number)?;
+                inp.pipes.push(Pipe {
+                    id: id(),
+                    node1: fields[1].to_string(),
+                    node2: fields[2].to_string(),
+                    length: number(fields[3], line_number)?,
+                    diameter: number(fields[4], line_number)?,
+                    roughness: number(fields[5], line_number)?,
+                    minor_loss: number_or(&fields, 6, 0., line_number)?,
+                    status: fields.get(7).unwrap_or(&"OPEN").to_uppercase(),
+                });
+            }
+            "PUMPS" => {
+                expect_fields("pump", &fields, 3, line_number)?;
+                inp.pumps.push(Pump {
+                    id: id(),
+                    node1: fields[1].to_string(),
+                    node2: fields[2].to_string(),
+                    properties: fields[3..]
+                        .chunks(2)
+                        .map(|pair| {
+                            (
+                                pair[0].to_uppercase(),
+                                pair.get(1).unwrap_or(&"").to_string(),
+                            )
+                        })
+                        .collect(),
+                });
+            }
+            "VALVES" => {
+                expect_fields("valve", &fields, 6, line_number)?;
+                inp.valves.push(Valve {
+                    id: id(),
+                    node1: fields[1].to_string(),
+                    node2: fields[2].to_string(),
+                    diameter: number(fields[3], line_number)?,
+                    valve_type: fields[4].to_uppercase(),
+                    setting: fields[5].to_string(),
+                    minor_loss: number_or(&fields, 6, 0., line_number)?,
+                });
+            }
+            "COORDINATES" => {
+                expect_fields("coordinate", &fields, 3, line_number)?;
+                inp.coordinates.insert(
+                    id(),
+                    (
+                        number(fields[1], line_number)?,
+                        number(fields[2], line_number)?,
+                    ),
+                );
+            }
+            "PATTERNS" => {
+                // long patterns continue on several lines starting with the same id
+                let multipliers = fields[1..]
+                    .iter()
+                    .map(|field| number(field, line_number))
+                    .collect::<Result<Vec<_>, Error>>()?;
+                inp.patterns.entry(id()).or_default().extend(multipliers);
+            }
+            "OPTIONS" => {
+                if let Some((keyword, value)) = keyword_value(&OPTIONS, &fields) {
+                    inp.options.insert(keyword, value);
+                }
+            }
+            "TIMES" => {
+                if let Some((keyword, value)) = keyword_value(&TIMES, &fields) {
+                    inp.times.insert(keyword, value);
+                }
+            }
+            "TITLE" | "END" => {}
+            _ => {
+                if !inp.ignored.contains(&section) {
+                    inp.ignored.push(section.clone());
+                }
+            }
+        }
+    }
+
+    Ok(inp)
+}
+
+/// Parses a time of `[TIMES]` into minutes
+///
+/// Times are given either as `hours:minutes[:seconds]` or as a number with an optional unit,
+/// which defaults to hours.
+pub fn minutes(value: &str) -> Result<f64, Error> {
+    let fields: Vec<&str> = value.split_whitespace().collect();
+    let time = fields.first().ok_or(anyhow!("time is missing"))?;
+
+    if time.contains(':') {
+        let parts = time
+            .split(':')
+            .map(|part| {
+                part.parse::<f64>()
+                    .map_err(|_| anyhow!("'{}' is not a valid time", value))
+            })
+            .collect::<Result<Vec<_>, Error>>()?;
+
+        return match parts[..] {
+            [hours, minutes] => Ok(hours * 60. + minutes),
+            [hours, minutes, seconds] => Ok(hours * 60. + minutes + seconds / 60.),
+            _ => Err(anyhow!("'{}' is not a valid time", value)),
+        };
+    }
+
+    let time: f64 = time
+        .parse()
+        .map_err(|_| anyhow!("'{}' is not a valid time", value))?;
+    let unit = fields
+        .get(1)
+        .map_or(String::from("HOURS"), |unit| unit.to_uppercase());
+    if unit.starts_with("SEC") {
+        Ok(time / 60.)
+    } else if unit.starts_with("MIN") {
+        Ok(time)
+    } else if unit.starts_with("HOUR") || unit.starts_with("HR") {
+        Ok(time * 60.)
+    } else if unit.starts_with("DAY") {
+        Ok(time * 24. * 60.)
+    } else {
+        Err(anyhow!("unknown time unit '{}'", unit))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_sections() {
+        let inp = parse(
+            "[TITLE]
+            Example
+
+            [JUNCTIONS]
+            ;ID  Elev  Demand  Pattern
+             J1  10    2.5     P1 ; comment
+             J2  12
+
+            [PIPES]
+             1  J1  J2  100  150  0.1  0.5  CV
+
+            [PATTERNS]
+             P1  1.0  1.2
+             P1  0.8
+
+            [OPTIONS]
+             Units  LPS
+             Demand Multiplier  2
+
+            [TIMES]
+             Pattern Timestep  0:30
+
+            [TANKS]
+             T1  20  1  0  5  10  0
+            ",
+        )
+        .expect("could not parse input file");
+
+        assert_eq!(
+            inp.junctions,
+            vec![
+                Junction {
+                    id: String::from("J1"),
+                    elevation: 10.,
+                    demand: 2.5,
+                    pattern: Some(String::from("P1")),
+                },
+                Junction {
+                    id: String::from("J2"),
+                    elevation: 12.,
+                    demand: 0.,
+                    pattern: None,
+                },
+            ]
+        );
+        assert_eq!(inp.pipes[0].minor_loss, 0.5);
+        assert_eq!(inp.pipes[0].status, "CV");
+        assert_eq!(inp.patterns["P1"], vec![1., 1.2, 0.8]);
+        assert_eq!(inp.options["UNITS"], "LPS");
+        assert_eq!(inp.options["DEMAND MULTIPLIER"], "2");
+        assert_eq!(inp.times["PATTERN TIMESTEP"], "0:30");
+        assert_eq!(inp.ignored, vec![String::from("TANKS")]);
+    }
+
+    #[test]
+    fn report_line_of_invalid_number() {
+        let result = parse("[PIPES]\n1 J1 J2 100 wide 0.1\n");
+
+        assert_eq!(
+            result.expect_err("diameter is not a number").to_string(),
+            "line 2: 'wide' is not a number"
+        );
+    }
+
+    #[test]
+    fn parse_times() {
+        assert_eq!(minutes("1:30").unwrap(), 90.);
+        assert_eq!(minutes("0:00:30").unwrap(), 0.5);
+        assert_eq!(minutes("24").unwrap(), 24. * 60.);
+        assert_eq!(minutes("15 min").unwrap(), 15.);
+        assert_eq!(minutes("2 DAYS").unwrap(), 2. * 24. * 60.);
+        assert!(minutes("2 weeks").is_err());
+    }
+}
@@ -0,0 +1,831 @@
+//! Import of EPANET input files (`.inp`)
+//!
+//! EPANET describes a single network of water pipes, which becomes the feed of a network in the
+//! custom format:
+//! - junctions become nodes, junctions with a demand also consumers of the same name, whose flows
+//!   are converted to heat demands by the cooling from the feed to the return temperature
+//! - reservoirs become nodes with a source of the same name at the elevation of the lowest
+//!   junction linked to them, whose pressure lift is the head above that elevation
+//! - pipes, pumps and valves become pipes
+//! - patterns become signals interpolating linearly between their multipliers
+//!
+//! The return nodes of consumers and sources are named `<node>_return` and left out.
+//! Everything that cannot be represented in the custom format is reported.
+
+mod inp;
+
+pub use inp::{minutes, parse, Inp};
+
+use super::custom::{
+    self, ConsumerInput, ConsumerSignalFactors, DataPoint, Input, PipeParameters, Position,
+};
+use crate::{constants::G, fluid::FluidProperties, types::network::HOURS_PER_YEAR, water::Water};
+
+use anyhow::{anyhow, Error};
+use std::{collections::HashMap, f64::consts::PI, fs};
+
+/// Values the custom format needs, but EPANET does not know
+#[derive(Debug, Clone)]
+pub struct Options {
+    /// Feed and return temperature, whose cooling converts the demand flows to heat demands
+    pub feed_temperature: f64,
+    pub return_temperature: f64,
+    pub ground_temperature: f64,
+    /// Heat transmittance of all pipes
+    pub transmittance: f64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            feed_temperature: 100.,
+            return_temperature: 65.,
+            ground_temperature: 10.,
+            transmittance: 2.,
+        }
+    }
+}
+
+/// Pressure of a water column of one meter \[Pa/m\]
+const PRESSURE_PER_HEAD: f64 = 1000. * G;
+
+/// Length of the pipes replacing pumps and valves \[m\]
+const LINK_LENGTH: f64 = 1.;
+
+/// Velocity \[m/s\] and kinematic viscosity \[m^2/s\] at which the head losses of
+/// Hazen-Williams and Manning roughnesses are matched by sand roughnesses
+const REFERENCE_VELOCITY: f64 = 1.;
+const REFERENCE_VISCOSITY: f64 = 1e-6;
+
+/// Factors converting the units of a file to the units of the custom format
+struct Units {
+    /// Lengths, elevations and heads to \[m\]
+    length: f64,
+    /// Diameters to \[mm\]
+    diameter: f64,
+    /// Darcy-Weisbach roughnesses to \[mm\]
+    roughness: f64,
+    /// Flows to \[m^3/h\]
+    flow: f64,
+}
+
+impl Units {
+    /// Units implied by the flow units of `[OPTIONS]`
+    fn from_flow_units(flow_units: &str) -> Result<Self, Error> {
+        let si = |flow| Units {
+            length: 1.,
+            diameter: 1.,
+            roughness: 1.,
+            flow,
+        };
+        let us = |flow| Units {
+            length: 0.3048,
+            diameter: 25.4,
+            roughness: 0.3048,
+            flow,
+        };
+
+        match flow_units.to_uppercase().as_str() {
+            "CFS" => Ok(us(101.940_648)),
+            "GPM" => Ok(us(0.227_124_7)),
+            "MGD" => Ok(us(157.725_491)),
+            "IMGD" => Ok(us(189.420_6)),
+            "AFD" => Ok(us(51.395_327)),
+            "LPS" => Ok(si(3.6)),
+            "LPM" => Ok(si(0.06)),
+            "MLD" => Ok(si(1000. / 24.)),
+            "CMH" => Ok(si(1.)),
+            "CMD" => Ok(si(1. / 24.)),
+            "CMS" => Ok(si(3600.)),
+            _ => Err(anyhow!("unknown flow units '{}'", flow_units)),
+        }
+    }
+}
+
+/// Sand roughness \[m\] of a pipe with the diameter d \[m\] by which Colebrook yields the Darcy
+/// friction factor f at the reference velocity
+///
+/// Rough pipes that would need a negative roughness are considered smooth.
+fn equivalent_roughness(f: f64, d: f64) -> f64 {
+    let re = REFERENCE_VELOCITY * d / REFERENCE_VISCOSITY;
+    let sqrt_f = f.sqrt();
+
+    (3.7 * d * (10f64.powf(-1. / (2. * sqrt_f)) - 2.51 / (re * sqrt_f))).max(0.)
+}
+
+/// Darcy friction factor with the head loss gradient \[m/m\] of a pipe with the diameter d \[m\]
+/// at the reference velocity
+fn friction_of_gradient(gradient: f64, d: f64) -> f64 {
+    gradient * 2. * G * d / REFERENCE_VELOCITY.powi(2)
+}
+
+/// Darcy friction factor of a pipe with the Hazen-Williams coefficient c and the diameter d \[m\]
+fn hazen_williams_friction(c: f64, d: f64) -> f64 {
+    let q = REFERENCE_VELOCITY * PI / 4. * d * d;
+    friction_of_gradient(10.67 * q.powf(1.852) / (c.powf(1.852) * d.powf(4.87)), d)
+}
+
+/// Darcy friction factor of a pipe with the Manning coefficient n and the diameter d \[m\]
+fn manning_friction(n: f64, d: f64) -> f64 {
+    let hydraulic_radius = d / 4.;
+    friction_of_gradient(
+        (n * REFERENCE_VELOCITY).powi(2) / hydraulic_radius.powf(4. / 3.),
+        d,
+    )
+}
+
+/// Signal through the multipliers of a pattern, repeated to cover the time span
+/// `[0, duration]` \[min\]
+fn pattern_signal(multipliers: &[f64], scale: f64, times: &PatternTimes) -> custom::Signal {
+    if let [multiplier] = multipliers {
+        return custom::Signal::Const {
+            scale,
+            data: *multiplier,
+        };
+    }
+
+    let num_periods = multipliers.len();
+    let first_period = (times.start / times.step).floor() as usize;
+    let num_steps = (times.duration / times.step).ceil().max(num_periods as f64) as usize;
+
+    custom::Signal::Poly {
+        degree: 1,
+        scale,
+        data: (0..=num_steps)
+            .map(|k| DataPoint {
+                t: k as f64 * times.step,
+                v: multipliers[(first_period + k) % num_periods],
+            })
+            .collect(),
+        gaps: Default::default(),
+    }
+}
+
+/// Times of `[TIMES]` relevant for patterns \[min\]
+struct PatternTimes {
+    duration: f64,
+    step: f64,
+    start: f64,
+}
+
+fn time_or(inp: &Inp, keyword: &str, default: f64) -> Result<f64, Error> {
+    inp.times.get(keyword).map_or(Ok(default), |value| {
+        minutes(value).map_err(|err| anyhow!("invalid {}: {}", keyword.to_lowercase(), err))
+    })
+}
+
+fn option_or(inp: &Inp, keyword: &str, default: f64) -> Result<f64, Error> {
+    inp.options.get(keyword).map_or(Ok(default), |value| {
+        value
+            .parse()
+            .map_err(|_| anyhow!("{} '{}' is not a number", keyword.to_lowercase(), value))
+    })
+}
+
+/// Converts the contents of an EPANET input file to a network in the custom format
+///
+/// Returns the network and descriptions of all information lost.
+pub fn convert(inp: Inp, options: &Options) -> Result<(custom::Network, Vec<String>), Error> {
+    let mut notes: Vec<String> = inp
+        .ignored
+        .iter()
+        .map(|section| format!("section [{}] is ignored", section))
+        .collect();
+
+    let units = Units::from_flow_units(inp.options.get("UNITS").map_or("GPM", String::as_str))?;
+    let headloss = inp
+        .options
+        .get("HEADLOSS")
+        .map_or(String::from("H-W"), |headloss| headloss.to_uppercase());
+    let demand_multiplier = option_or(&inp, "DEMAND MULTIPLIER", 1.)?;
+
+    // topology
+
+    // a reservoir lies at the lowest junction linked to it, its head above lifts the pressure
+    let elevations: HashMap<_, _> = inp
+        .junctions
+        .iter()
+        .map(|junction| (&junction.id, junction.elevation * units.length))
+        .collect();
+    let links = inp
+        .pipes
+        .iter()
+        .map(|pipe| (&pipe.node1, &pipe.node2))
+        .chain(inp.pumps.iter().map(|pump| (&pump.node1, &pump.node2)))
+        .chain(inp.valves.iter().map(|valve| (&valve.node1, &valve.node2)));
+    let mut reservoir_elevations: HashMap<_, f64> = HashMap::new();
+    for (node1, node2) in links {
+        for (reservoir, junction) in [(node1, node2), (node2, node1)] {
+            if let Some(&elevation) = elevations.get(junction) {
+                reservoir_elevations
+                    .entry(reservoir)
+                    .and_modify(|z| *z = z.min(elevation))
+                    .or_insert(elevation);
+            }
+        }
+    }
+
+    let mut lifts = HashMap::new();
+    for reservoir in &inp.reservoirs {
+        let head = reservoir.head * units.length;
+        let z = match reservoir_elevations.get(&reservoir.id) {
+            Some(&z) => z,
+            None => {
+                notes.push(format!(
+                    "reservoir '{}' is not linked to a junction and has no pressure lift",
+                    reservoir.id
+                ));
+                head
+            }
+        };
+        lifts.insert(reservoir.id.clone(), (head, z));
+    }
+
+    let mut position = |id: &String, z: f64| {
+        let (x, y) = inp.coordinates.get(id).copied().unwrap_or_else(|| {
+            notes.push(format!("node '{}' has no coordinates", id));
+            (0., 0.)
+        });
+        Position { x, y, z }
+    };
+
+    let mut nodes = vec![];
+    for junction in &inp.junctions {
+        nodes.push(custom::Node {
+            name: junction.id.clone(),
+            position: position(&junction.id, junction.elevation * units.length),
+            feed: true,
+        });
+    }
+    for reservoir in &inp.reservoirs {
+        nodes.push(custom::Node {
+            name: reservoir.id.clone(),
+            position: position(&reservoir.id, lifts[&reservoir.id].1),
+            feed: true,
+        });
+    }
+
+    let return_node = |id: &String| format!("{}_return", id);
+
+    let mut consumers = vec![];
+    for junction in &inp.junctions {
+        if junction.demand > 0. {
+            consumers.push(custom::Consumer {
+                name: junction.id.clone(),
+                src: junction.id.clone(),
+                tgt: return_node(&junction.id),
+            });
+        } else if junction.demand < 0. {
+            notes.push(format!(
+                "negative demand of junction '{}' is ignored",
+                junction.id
+            ));
+        }
+    }
+
+    let sources = inp
+        .reservoirs
+        .iter()
+        .map(|reservoir| custom::Source {
+            name: reservoir.id.clone(),
+            src: return_node(&reservoir.id),
+            tgt: reservoir.id.clone(),
+        })
+        .collect();
+
+    // pipes, pumps and valves
+
+    let mut pipes = vec![];
+    let mut pipe_parameters = vec![];
+
+    let mut converted_roughness = false;
+    for pipe in &inp.pipes {
+        match pipe.status.as_str() {
+            "CLOSED" => {
+                notes.push(format!("closed pipe '{}' is left out", pipe.id));
+                continue;
+            }
+            "CV" => notes.push(format!("check valve of pipe '{}' is ignored", pipe.id)),
+            _ => {}
+        }
+
+        let diameter = pipe.diameter * units.diameter;
+        let roughness = match headloss.as_str() {
+            "D-W" => pipe.roughness * units.roughness,
+            "H-W" => {
+                converted_roughness = true;
+                equivalent_roughness(
+                    hazen_williams_friction(pipe.roughness, diameter * 1e-3),
+                    diameter * 1e-3,
+                ) * 1e3
+            }
+            "C-M" => {
+                converted_roughness = true;
+                equivalent_roughness(
+                    manning_friction(pipe.roughness, diameter * 1e-3),
+                    diameter * 1e-3,
+                ) * 1e3
+            }
+            _ => return Err(anyhow!("unknown head loss formula '{}'", headloss)),
+        };
+
+        pipes.push(custom::Pipe {
+            name: pipe.id.clone(),
+            src: pipe.node1.clone(),
+            tgt: pipe.node2.clone(),
+        });
+        pipe_parameters.push((
+            pipe.id.clone(),
+            PipeParameters::Full {
+                length: pipe.length * units.length,
+                diameter,
+                transmittance: options.transmittance,
+                roughness,
+                zeta: pipe.minor_loss,
+                friction_model: None,
+            },
+        ));
+    }
+    if converted_roughness {
+        notes.push(format!(
+            "{} roughnesses are converted to sand roughnesses with the same head loss at {} m/s",
+            headloss, REFERENCE_VELOCITY
+        ));
+    }
+
+    for valve in &inp.valves {
+        // the setting of throttle control valves is their loss coefficient
+        let zeta = if valve.valve_type == "TCV" {
+            valve.minor_loss
+                + valve
+                    .setting
+                    .parse::<f64>()
+                    .map_err(|_| anyhow!("setting of valve '{}' is not a number", valve.id))?
+        } else {
+            notes.push(format!(
+                "valve '{}' of type {} is imported as an open pipe",
+                valve.id, valve.valve_type
+            ));
+            valve.minor_loss
+        };
+
+        pipes.push(custom::Pipe {
+            name: valve.id.clone(),
+            src: valve.node1.clone(),
+            tgt: valve.node2.clone(),
+        });
+        pipe_parameters.push((
+            valve.id.clone(),
+            PipeParameters::Full {
+                length: LINK_LENGTH,
+                diameter: valve.diameter * units.diameter,
+                transmittance: options.transmittance,
+                roughness: 0.,
+                zeta,
+                friction_model: None,
+            },
+        ));
+    }
+
+    for pump in &inp.pumps {
+        // pumps take the diameter of the widest pipe they are connected to
+        let diameter = inp
+            .pipes
+            .iter()
+            .filter(|pipe| {
+                [&pipe.node1, &pipe.node2]
+                    .iter()
+                    .any(|node| **node == pump.node1 || **node == pump.node2)
+            })
+            .map(|pipe| pipe.diameter * units.diameter)
+            .fold(None, |max: Option<f64>, d| {
+                Some(max.map_or(d, |max| max.max(d)))
+            })
+            .ok_or(anyhow!("pump '{}' is not connected to any pipe", pump.id))?;
+        notes.push(format!(
+            "pump '{}' is imported as a pipe without pressure lift",
+            pump.id
+        ));
+
+        pipes.push(custom::Pipe {
+            name: pump.id.clone(),
+            src: pump.node1.clone(),
+            tgt: pump.node2.clone(),
+        });
+        pipe_parameters.push((
+            pump.id.clone(),
+            PipeParameters::Full {
+                length: LINK_LENGTH,
+                diameter,
+                transmittance: options.transmittance,
+                roughness: 0.,
+                zeta: 0.,
+                friction_model: None,
+            },
+        ));
+    }
+
+    // scenario
+
+    let hydraulic_step = time_or(&inp, "HYDRAULIC TIMESTEP", 60.)?;
+    let times = PatternTimes {
+        duration: time_or(&inp, "DURATION", 0.)?,
+        step: time_or(&inp, "PATTERN TIMESTEP", 60.)?,
+        start: time_or(&inp, "PATTERN START", 0.)?,
+    };
+    if times.step <= 0. {
+        return Err(anyhow!("pattern time step must be positive"));
+    }
+
+    let mut signals = HashMap::from([
+        (
+            String::from("one"),
+            custom::Signal::Const {
+                scale: 1.,
+                data: 1.,
+            },
+        ),
+        (
+            String::from("zero"),
+            custom::Signal::Const {
+                scale: 1.,
+                data: 0.,
+            },
+        ),
+        (
+            String::from("feed_temperature"),
+            custom::Signal::Const {
+                scale: 1.,
+                data: options.feed_temperature,
+            },
+        ),
+    ]);
+    let mut inputs = HashMap::new();
+
+    // junctions without a pattern use the default pattern, if it exists
+    let default_pattern = inp
+        .options
+        .get("PATTERN")
+        .cloned()
+        .unwrap_or(String::from("1"));
+    let mut interpolated = false;
+
+    // kW = m^3/s * GJ/m^3 * 1e6
+    let cooling = Water.energy_density(options.feed_temperature)?
+        - Water.energy_density(options.return_temperature)?;
+    if cooling <= 0. {
+        return Err(anyhow!(
+            "feed temperature {} must be above the return temperature {} to convert demands",
+            options.feed_temperature,
+            options.return_temperature
+        ));
+    }
+    let heat_per_flow = cooling * 1e6 / 3600.;
+
+    let mut consumer_inputs = HashMap::new();
+    for junction in inp.junctions.iter().filter(|junction| junction.demand > 0.) {
+        let pattern = junction
+            .pattern
+            .as_ref()
+            .or(Some(&default_pattern).filter(|pattern| inp.patterns.contains_key(*pattern)));
+
+        let (signal, input) = match pattern {
+            Some(pattern) => {
+                let multipliers = inp.patterns.get(pattern).ok_or(anyhow!(
+                    "pattern '{}' of junction '{}' does not exist",
+                    pattern,
+                    junction.id
+                ))?;
+                let signal = format!("pattern_{}", pattern);
+                signals.insert(signal.clone(), pattern_signal(multipliers, 1., &times));
+                interpolated |= multipliers.len() > 1;
+
+                (signal, format!("demand_{}", pattern))
+            }
+            None => (String::from("one"), String::from("demand_constant")),
+        };
+
+        inputs.insert(
+            input.clone(),
+            Input::Consumer {
+                demand: signal,
+                return_temperature: String::from("one"),
+            },
+        );
+        consumer_inputs.insert(
+            junction.id.clone(),
+            ConsumerInput {
+                input,
+                factors: ConsumerSignalFactors {
+                    // the flow [m^3/h] of the junction carries the heat [kW] of the cooling
+                    yearly_demand: junction.demand
+                        * demand_multiplier
+                        * units.flow
+                        * heat_per_flow
+                        * HOURS_PER_YEAR,
+                    normal_return_temperature: options.return_temperature,
+                },
+            },
+        );
+    }
+
+    let mut source_inputs = HashMap::new();
+    for reservoir in &inp.reservoirs {
+        let (head, z) = lifts[&reservoir.id];
+        let signal = format!("{}_lift", reservoir.id);
+        let lift = match &reservoir.pattern {
+            Some(pattern) => {
+                let multipliers = inp.patterns.get(pattern).ok_or(anyhow!(
+                    "pattern '{}' of reservoir '{}' does not exist",
+                    pattern,
+                    reservoir.id
+                ))?;
+                interpolated |= multipliers.len() > 1;
+
+                // the pattern multiplies the head, not the lift
+                let head_signal = format!("{}_head", reservoir.id);
+                signals.insert(
+                    head_signal.clone(),
+                    pattern_signal(multipliers, head * PRESSURE_PER_HEAD, &times),
+                );
+                custom::Signal::Offset {
+                    signal: head_signal,
+                    value: -z * PRESSURE_PER_HEAD,
+                }
+            }
+            None => custom::Signal::Const {
+                scale: PRESSURE_PER_HEAD,
+                data: head - z,
+            },
+        };
+        signals.insert(signal.clone(), lift);
+
+        let input = format!("{}_source", reservoir.id);
+        inputs.insert(
+            input.clone(),
+            // the feed pressure of a source is its pressure lift above the return side
+            Input::Source {
+                base_pressure: String::from("zero"),
+                pressure_lift: signal,
+                temperature: String::from("feed_temperature"),
+            },
+        );
+        source_inputs.insert(reservoir.id.clone(), input);
+    }
+
+    if interpolated {
+        notes.push(String::from(
+            "patterns are interpolated linearly between their time steps",
+        ));
+    }
+    if !consumer_inputs.is_empty() {
+        notes.push(String::from(
+            "demands are flows [m^3/h], which are converted to heat demands by the cooling",
+        ));
+    }
+
+    let settings = custom::Settings {
+        feed_temperature: options.feed_temperature,
+        return_temperature: options.return_temperature,
+        ground_temperature: options.ground_temperature,
+        time_start: 0.,
+        time_end: times.duration.max(hydraulic_step) / (24. * 60.),
+        time_step: hydraulic_step,
+        ramp_time: 0.,
+        num_iterations: option_or(&inp, "TRIALS", 40.)? as usize,
+        tolerance: option_or(&inp, "ACCURACY", 1e-3)?,
+    };
+
+    let network = custom::Network {
+        topology: custom::Topology {
+            nodes,
+            pipes,
+            consumers,
+            sources,
+        },
+        scenario: custom::Scenario {
+            settings,
+            signals,
+            inputs,
+            consumer_inputs,
+            source_inputs,
+        },
+        parameters: custom::Parameters::deduplicated(pipe_parameters),
+    };
+
+    Ok((network, notes))
+}
+
+/// Loads the EPANET input file `path` as a network in the custom format
+///
+/// Returns the network and descriptions of all information lost.
+pub fn load(path: &str, options: &Options) -> Result<(custom::Network, Vec<String>), Error> {
+    let text =
+        fs::read_to_string(path).map_err(|err| anyhow!("could not read input file: {}", err))?;
+    let inp = parse(&text).map_err(|err| anyhow!("could not parse input file: {}", err))?;
+
+    convert(inp, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::{
+        friction::{Colebrook, FrictionModel},
+        types::{
+            formats::custom::validation::{validate, Severity},
+            network::{FullPipeParameters, Network, Node},
+        },
+    };
+
+    #[test]
+    fn equivalent_roughness_inverts_colebrook() {
+        for (roughness, d) in [(1e-4, 0.1), (1e-3, 0.3), (5e-5, 0.05)] {
+            let re = REFERENCE_VELOCITY * d / REFERENCE_VISCOSITY;
+            let f = Colebrook::default().turbulent(re, roughness / d);
+
+            assert_relative_eq!(equivalent_roughness(f, d), roughness, max_relative = 1e-6);
+        }
+    }
+
+    #[test]
+    fn rougher_pipes_have_lower_hazen_williams_coefficients() {
+        let d = 0.2;
+        let roughness = |c| equivalent_roughness(hazen_williams_friction(c, d), d);
+
+        assert!(roughness(100.) > roughness(120.));
+        assert!(roughness(120.) > roughness(140.));
+        // cast iron of about 100 is in the order of a millimeter
+        assert!((1e-4..1e-2).contains(&roughness(100.)));
+    }
+
+    #[test]
+    fn pattern_repeats_over_duration() {
+        let times = PatternTimes {
+            duration: 300.,
+            step: 60.,
+            start: 60.,
+        };
+
+        let custom::Signal::Poly { degree, data, .. } = pattern_signal(&[1., 2., 3.], 2., &times)
+        else {
+            panic!("pattern with several multipliers is not polynomial");
+        };
+        assert_eq!(degree, 1);
+        assert_eq!(
+            data.iter().map(|point| point.v).collect::<Vec<_>>(),
+            vec![2., 3., 1., 2., 3., 1.]
+        );
+        assert_eq!(data[5].t, 300.);
+    }
+
+    #[test]
+    fn reservoir_lifts_head_above_lowest_linked_junction() {
+        let inp = parse(
+            "[JUNCTIONS]\n J1 10 0\n J2 5 1\n\
+             [RESERVOIRS]\n R1 30 1\n R2 20\n\
+             [PIPES]\n P1 R1 J1 100 200 100\n P2 J2 R1 100 200 100\n\
+             [PATTERNS]\n 1 1.0 2.0\n\
+             [OPTIONS]\n Units CMH\n",
+        )
+        .expect("could not parse input");
+        let (network, notes) = convert(inp, &Options::default()).expect("could not convert");
+
+        let z = |name: &str| {
+            let node = network.topology.nodes.iter().find(|node| node.name == name);
+            node.expect("node exists").position.z
+        };
+        assert_eq!(z("R1"), 5.);
+        assert_eq!(z("R2"), 20.);
+        assert!(notes.contains(&String::from(
+            "reservoir 'R2' is not linked to a junction and has no pressure lift"
+        )));
+
+        let signals = &network.scenario.signals;
+        assert_eq!(
+            signals["R1_lift"],
+            custom::Signal::Offset {
+                signal: String::from("R1_head"),
+                value: -5. * PRESSURE_PER_HEAD,
+            }
+        );
+        assert!(matches!(
+            &signals["R1_head"],
+            custom::Signal::Poly { scale, .. } if *scale == 30. * PRESSURE_PER_HEAD
+        ));
+        assert_eq!(
+            signals["R2_lift"],
+            custom::Signal::Const {
+                scale: PRESSURE_PER_HEAD,
+                data: 0.,
+            }
+        );
+    }
+
+    #[test]
+    fn demands_need_cooling() {
+        let inp = parse("[JUNCTIONS]\n J1 10 1\n").expect("could not parse input");
+        let options = Options {
+            return_temperature: 100.,
+            ..Options::default()
+        };
+
+        assert!(convert(inp, &options).is_err());
+    }
+
+    #[test]
+    fn import_example() {
+        let (network, notes) =
+            load("data/epanet/example.inp", &Options::default()).expect("could not import");
+
+        let topology = &network.topology;
+        assert_eq!(topology.nodes.len(), 6);
+        assert_eq!(topology.pipes.len(), 7);
+        assert_eq!(topology.consumers.len(), 4);
+        assert_eq!(topology.sources[0].tgt, "R1");
+        // R1 is pumped to J1
+        assert_eq!(topology.nodes[5].position.z, 210.);
+        assert_eq!(
+            topology.nodes[0].position,
+            Position {
+                x: 20.,
+                y: 70.,
+                z: 210.
+            }
+        );
+
+        let scenario = &network.scenario;
+        assert_eq!(scenario.settings.time_end, 1.);
+        assert_eq!(scenario.settings.time_step, 60.);
+        assert_eq!(scenario.consumer_inputs["J3"].input, "demand_1");
+        let cooling = Water.energy_density(100.).unwrap() - Water.energy_density(65.).unwrap();
+        assert_relative_eq!(
+            scenario.consumer_inputs["J3"].factors.yearly_demand,
+            5. * 3.6 / 3600. * cooling * 1e6 * HOURS_PER_YEAR
+        );
+        assert_eq!(scenario.consumer_inputs["J5"].input, "demand_2");
+        assert_eq!(
+            scenario.signals["R1_lift"],
+            custom::Signal::Const {
+                scale: PRESSURE_PER_HEAD,
+                data: 250. - 210.,
+            }
+        );
+        assert!(matches!(
+            &scenario.inputs["R1_source"],
+            Input::Source { pressure_lift, .. } if pressure_lift == "R1_lift"
+        ));
+
+        let PipeParameters::Full {
+            length,
+            diameter,
+            roughness,
+            zeta,
+            ..
+        } = network.parameters.parameters[&network.parameters.pipes["P2"]]
+        else {
+            panic!("pipe parameters are not full parameters");
+        };
+        assert_eq!((length, diameter, zeta), (1000., 200., 0.5));
+        assert!(roughness > 0.);
+
+        for note in [
+            "pump 'PU1' is imported as a pipe without pressure lift",
+            "valve 'V1' of type PRV is imported as an open pipe",
+            "check valve of pipe 'P4' is ignored",
+            "section [TANKS] is ignored",
+            "patterns are interpolated linearly between their time steps",
+            "demands are flows [m^3/h], which are converted to heat demands by the cooling",
+        ] {
+            assert!(notes.contains(&String::from(note)), "{:?}", notes);
+        }
+
+        let path = "/tmp/rimulation_epanet_import";
+        custom::save(&network, path).expect("could not save network");
+        let problems = validate(path);
+        assert!(
+            problems
+                .iter()
+                .all(|problem| problem.severity == Severity::Warning),
+            "{:?}",
+            problems
+        );
+
+        // the head above the elevation of the reservoir drives the flow of the simulated network
+        let network: Network<FullPipeParameters> =
+            network.try_into().expect("could not convert network");
+        let source = network
+            .nodes()
+            .find_map(|node| match node {
+                Node::Pressure { pressure, .. } => Some(pressure),
+                _ => None,
+            })
+            .expect("network has a source");
+        assert_relative_eq!(
+            source.value_at(0.).expect("could not evaluate pressure"),
+            40. * 1000. * G
+        );
+    }
+}
@@ -0,0 +1,997 @@
+//! GeoJSON export and import of networks for the review in GIS tools
+//!
+//! Nodes, consumers and sources are written as points, pipes as line strings from their source to
+//! their target node. Coordinates are taken as they are, so they have to be given in the
+//! coordinate reference system of the GIS project.
+//!
+//! On import, coordinates are longitudes and latitudes in WGS84 as required by RFC 7946, unless
+//! the collection names another coordinate reference system in a `crs` member like GeoJSON before
+//! it. Such a system has to be projected with coordinates in meters.
+
+use super::{
+    cells::CellIndex,
+    custom::{
+        Consumer, GeometryUnits, LengthUnit, Node, Parameters, Pipe, PipeParameters, Position,
+        Source, Topology,
+    },
+};
+use crate::output::TemperatureStatistics;
+
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Serialize};
+use serde_json::{from_reader, to_writer_pretty, Map, Value};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+};
+
+const NODE: &str = "node";
+const PIPE: &str = "pipe";
+const CONSUMER: &str = "consumer";
+const SOURCE: &str = "source";
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Geometry {
+    Point {
+        coordinates: Vec<f64>,
+    },
+    LineString {
+        coordinates: Vec<Vec<f64>>,
+    },
+    /// Only read if it consists of a single line string, as written by many GIS tools
+    MultiLineString {
+        coordinates: Vec<Vec<Vec<f64>>>,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Feature {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub geometry: Option<Geometry>,
+    #[serde(default)]
+    pub properties: Option<Map<String, Value>>,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct FeatureCollection {
+    #[serde(rename = "type")]
+    pub kind: String,
+    /// Named coordinate reference system, e.g.
+    /// `{"type": "name", "properties": {"name": "urn:ogc:def:crs:EPSG::25832"}}`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub crs: Option<Value>,
+    pub features: Vec<Feature>,
+}
+
+impl FeatureCollection {
+    /// Whether the coordinates are longitudes and latitudes in WGS84
+    fn is_geographic(&self) -> Result<bool, Error> {
+        let Some(crs) = &self.crs else {
+            return Ok(true);
+        };
+        let name = crs
+            .pointer("/properties/name")
+            .and_then(Value::as_str)
+            .ok_or(anyhow!(
+                "coordinate reference system {} is not given by name",
+                crs
+            ))?
+            .to_uppercase();
+
+        Ok(name.ends_with("CRS84") || name.ends_with(":4326"))
+    }
+}
+
+pub fn load(path: &str) -> Result<FeatureCollection, Error> {
+    let file =
+        fs::File::open(path).map_err(|err| anyhow!("could not open GeoJSON file: {}", err))?;
+    from_reader(file).map_err(|err| anyhow!("could not decode GeoJSON: {}", err))
+}
+
+pub fn save(collection: &FeatureCollection, path: &str) -> Result<(), Error> {
+    let file =
+        fs::File::create(path).map_err(|err| anyhow!("could not create GeoJSON file: {}", err))?;
+    to_writer_pretty(file, collection).map_err(|err| anyhow!("could not encode GeoJSON: {}", err))
+}
+
+fn coordinates(position: &Position) -> Vec<f64> {
+    vec![position.x, position.y, position.z]
+}
+
+fn feature(geometry: Geometry, properties: Map<String, Value>) -> Feature {
+    Feature {
+        kind: String::from("Feature"),
+        geometry: Some(geometry),
+        properties: Some(properties),
+    }
+}
+
+fn component_properties(kind: &str, name: &str) -> Map<String, Value> {
+    let mut properties = Map::new();
+    properties.insert(String::from("kind"), Value::from(kind));
+    properties.insert(String::from("name"), Value::from(name));
+    properties
+}
+
+fn connection_properties(kind: &str, name: &str, src: &str, tgt: &str) -> Map<String, Value> {
+    let mut properties = component_properties(kind, name);
+    properties.insert(String::from("src"), Value::from(src));
+    properties.insert(String::from("tgt"), Value::from(tgt));
+    properties
+}
+
+/// Creates a feature for every component of a network
+///
+/// Pipes get the name of their parameter set as `parameters` and its values in the nominal units
+/// of the parameters file. Nodes with `statistics` get them as `temperature_min`,
+/// `temperature_mean` and `temperature_max`.
+pub fn export(
+    topology: &Topology,
+    parameters: &Parameters,
+    statistics: &HashMap<String, TemperatureStatistics>,
+) -> Result<FeatureCollection, Error> {
+    let positions: HashMap<&str, &Position> = topology
+        .nodes
+        .iter()
+        .map(|node| (node.name.as_str(), &node.position))
+        .collect();
+    let position = |kind: &str, name: &str, node: &str| {
+        positions
+            .get(node)
+            .map(|position| coordinates(position))
+            .ok_or(anyhow!(
+                "{} '{}' refers to unknown node '{}'",
+                kind,
+                name,
+                node
+            ))
+    };
+
+    let mut features = vec![];
+
+    for node in &topology.nodes {
+        let mut properties = component_properties(NODE, &node.name);
+        properties.insert(String::from("feed"), Value::from(node.feed));
+        if let Some(statistics) = statistics.get(&node.name) {
+            properties.insert(String::from("temperature_min"), statistics.min.into());
+            properties.insert(String::from("temperature_mean"), statistics.mean.into());
+            properties.insert(String::from("temperature_max"), statistics.max.into());
+        }
+
+        features.push(feature(
+            Geometry::Point {
+                coordinates: coordinates(&node.position),
+            },
+            properties,
+        ));
+    }
+
+    for pipe in &topology.pipes {
+        let set = parameters
+            .pipes
+            .get(&pipe.name)
+            .ok_or(anyhow!("pipe '{}' has no parameters", pipe.name))?;
+        let mut values = parameters
+            .parameters
+            .get(set)
+            .ok_or(anyhow!(
+                "parameters '{}' of pipe '{}' do not exist",
+                set,
+                pipe.name
+            ))?
+            .clone();
+        if parameters.units != GeometryUnits::nominal() {
+            values = values
+                .in_meters(&parameters.units)
+                .in_units(&GeometryUnits::nominal());
+        }
+
+        let mut properties = connection_properties(PIPE, &pipe.name, &pipe.src, &pipe.tgt);
+        properties.insert(String::from("parameters"), Value::from(set.as_str()));
+        if let Value::Object(values) = serde_json::to_value(values)? {
+            properties.extend(values);
+        }
+
+        features.push(feature(
+            Geometry::LineString {
+                coordinates: vec![
+                    position(PIPE, &pipe.name, &pipe.src)?,
+                    position(PIPE, &pipe.name, &pipe.tgt)?,
+                ],
+            },
+            properties,
+        ));
+    }
+
+    for consumer in &topology.consumers {
+        features.push(feature(
+            Geometry::Point {
+                coordinates: position(CONSUMER, &consumer.name, &consumer.src)?,
+            },
+            connection_properties(CONSUMER, &consumer.name, &consumer.src, &consumer.tgt),
+        ));
+    }
+
+    for source in &topology.sources {
+        features.push(feature(
+            Geometry::Point {
+                coordinates: position(SOURCE, &source.name, &source.tgt)?,
+            },
+            connection_properties(SOURCE, &source.name, &source.src, &source.tgt),
+        ));
+    }
+
+    Ok(FeatureCollection {
+        kind: String::from("FeatureCollection"),
+        crs: None,
+        features,
+    })
+}
+
+/// Names of the properties that describe the components in imported features
+///
+/// The defaults are the names written by [`export`]. A mapping file only has to contain the names
+/// that differ, e.g. `{"name": "ID", "diameter": "DN"}`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PropertyMapping {
+    /// `node`, `pipe`, `consumer` or `source`; without it, points are nodes and lines are pipes
+    pub kind: String,
+    /// Components without a name are named by their kind and the number of their feature
+    pub name: String,
+    /// Nodes without it are feed nodes
+    pub feed: String,
+    /// Pipes without it are connected to the node at the start of their line
+    pub src: String,
+    /// Pipes without it are connected to the node at the end of their line
+    pub tgt: String,
+    /// Pipes without it get the length of their line, which is geodesic for longitudes and
+    /// latitudes
+    pub length: String,
+    pub diameter: String,
+    pub transmittance: String,
+    pub roughness: String,
+    pub zeta: String,
+    /// Pipes with a velocity and without a diameter have a fixed flow velocity
+    pub velocity: String,
+    pub friction_model: String,
+    /// Units of the lengths, diameters and roughnesses in the properties
+    pub units: GeometryUnits,
+    /// Values of the parameters that are missing in the properties of a pipe, e.g. `{"zeta": 0}`
+    pub defaults: HashMap<String, f64>,
+    /// Largest distance between the end of a line and the node it is connected to, in the unit of
+    /// the coordinates
+    pub tolerance: f64,
+}
+
+impl Default for PropertyMapping {
+    fn default() -> Self {
+        Self {
+            kind: String::from("kind"),
+            name: String::from("name"),
+            feed: String::from("feed"),
+            src: String::from("src"),
+            tgt: String::from("tgt"),
+            length: String::from("length"),
+            diameter: String::from("diameter"),
+            transmittance: String::from("transmittance"),
+            roughness: String::from("roughness"),
+            zeta: String::from("zeta"),
+            velocity: String::from("velocity"),
+            friction_model: String::from("friction_model"),
+            units: GeometryUnits::nominal(),
+            defaults: HashMap::new(),
+            tolerance: 1e-6,
+        }
+    }
+}
+
+impl PropertyMapping {
+    pub fn load(path: &str) -> Result<Self, Error> {
+        let file =
+            fs::File::open(path).map_err(|err| anyhow!("could not open mapping file: {}", err))?;
+        from_reader(file).map_err(|err| anyhow!("could not decode mapping: {}", err))
+    }
+}
+
+/// Properties of the feature with the given number
+struct Properties<'a> {
+    feature: usize,
+    values: Option<&'a Map<String, Value>>,
+}
+
+impl Properties<'_> {
+    fn get(&self, key: &str) -> Option<&Value> {
+        self.values
+            .and_then(|values| values.get(key))
+            .filter(|value| !value.is_null())
+    }
+
+    fn text(&self, key: &str) -> Result<Option<String>, Error> {
+        match self.get(key) {
+            None => Ok(None),
+            Some(Value::String(text)) => Ok(Some(text.clone())),
+            Some(Value::Number(number)) => Ok(Some(number.to_string())),
+            Some(value) => Err(anyhow!(
+                "feature {}: property '{}' is not a text, but {}",
+                self.feature,
+                key,
+                value
+            )),
+        }
+    }
+
+    fn number(&self, key: &str) -> Result<Option<f64>, Error> {
+        let number = match self.get(key) {
+            None => return Ok(None),
+            Some(Value::Number(number)) => number.as_f64(),
+            Some(Value::String(text)) => text.trim().parse().ok(),
+            Some(_) => None,
+        };
+
+        number.map(Some).ok_or(anyhow!(
+            "feature {}: property '{}' is not a number",
+            self.feature,
+            key
+        ))
+    }
+
+    fn flag(&self, key: &str) -> Result<Option<bool>, Error> {
+        let flag = match self.get(key) {
+            None => return Ok(None),
+            Some(Value::Bool(flag)) => Some(*flag),
+            Some(Value::Number(number)) => number.as_f64().map(|number| number != 0.),
+            Some(Value::String(text)) => match text.trim().to_lowercase().as_str() {
+                "true" | "yes" | "1" => Some(true),
+                "false" | "no" | "0" => Some(false),
+                _ => None,
+            },
+            Some(_) => None,
+        };
+
+        flag.map(Some).ok_or(anyhow!(
+            "feature {}: property '{}' is not a boolean",
+            self.feature,
+            key
+        ))
+    }
+}
+
+fn distance(a: &[f64], b: &[f64]) -> f64 {
+    a.iter()
+        .zip(b)
+        .take(3)
+        .map(|(a, b)| (a - b).powi(2))
+        .sum::<f64>()
+        .sqrt()
+}
+
+/// Semi-major axis \[m\] and flattening of the WGS84 ellipsoid
+const WGS84_A: f64 = 6_378_137.;
+const WGS84_F: f64 = 1. / 298.257_223_563;
+
+/// Computes the distance \[m\] between two points given by longitude, latitude \[°\] and
+/// optionally altitude \[m\]
+///
+/// The horizontal distance is the geodesic on the WGS84 ellipsoid by the inverse formula of
+/// Vincenty, which is accurate to a millimeter and only fails to converge for nearly antipodal
+/// points.
+fn geodesic_distance(a: &[f64], b: &[f64]) -> f64 {
+    let f = WGS84_F;
+    let semi_minor = WGS84_A * (1. - f);
+    let reduced_latitude = |latitude: f64| ((1. - f) * latitude.to_radians().tan()).atan();
+    let (sin_u1, cos_u1) = reduced_latitude(a[1]).sin_cos();
+    let (sin_u2, cos_u2) = reduced_latitude(b[1]).sin_cos();
+    let l = (b[0] - a[0]).to_radians();
+
+    let mut lambda = l;
+    let (mut sin_sigma, mut cos_sigma, mut sigma, mut cos2_alpha, mut cos_2sigma_m);
+    let mut iterations = 0;
+    loop {
+        let (sin_lambda, cos_lambda) = lambda.sin_cos();
+        sin_sigma = (cos_u2 * sin_lambda).hypot(cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda);
+        if sin_sigma == 0. {
+            // coincident points
+            return (a.get(2).unwrap_or(&0.) - b.get(2).unwrap_or(&0.)).abs();
+        }
+        cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+        sigma = sin_sigma.atan2(cos_sigma);
+        let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+        cos2_alpha = 1. - sin_alpha.powi(2);
+        // the geodesic follows the equator if cos^2 alpha is zero
+        cos_2sigma_m = if cos2_alpha != 0. {
+            cos_sigma - 2. * sin_u1 * sin_u2 / cos2_alpha
+        } else {
+            0.
+        };
+        let c = f / 16. * cos2_alpha * (4. + f * (4. - 3. * cos2_alpha));
+
+        let previous = lambda;
+        lambda = l
+            + (1. - c)
+                * f
+                * sin_alpha
+                * (sigma
+                    + c * sin_sigma
+                        * (cos_2sigma_m + c * cos_sigma * (-1. + 2. * cos_2sigma_m.powi(2))));
+        iterations += 1;
+        if (lambda - previous).abs() < 1e-12 || iterations == 100 {
+            break;
+        }
+    }
+
+    let u2 = cos2_alpha * (WGS84_A.powi(2) - semi_minor.powi(2)) / semi_minor.powi(2);
+    let a_coefficient = 1. + u2 / 16384. * (4096. + u2 * (-768. + u2 * (320. - 175. * u2)));
+    let b_coefficient = u2 / 1024. * (256. + u2 * (-128. + u2 * (74. - 47. * u2)));
+    let delta_sigma = b_coefficient
+        * sin_sigma
+        * (cos_2sigma_m
+            + b_coefficient / 4.
+                * (cos_sigma * (-1. + 2. * cos_2sigma_m.powi(2))
+                    - b_coefficient / 6.
+                        * cos_2sigma_m
+                        * (-3. + 4. * sin_sigma.powi(2))
+                        * (-3. + 4. * cos_2sigma_m.powi(2))));
+    let horizontal = semi_minor * a_coefficient * (sigma - delta_sigma);
+
+    horizontal.hypot(a.get(2).unwrap_or(&0.) - b.get(2).unwrap_or(&0.))
+}
+
+/// Finds the node closest to `point` within the tolerance of the mapping
+fn snap(nodes: &[Node], cells: &CellIndex, point: &[f64], tolerance: f64) -> Option<String> {
+    cells
+        .near(point[0], point[1])
+        .map(|i| (i, distance(&coordinates(&nodes[i].position), point)))
+        .filter(|(_, distance)| *distance <= tolerance)
+        // the first node wins ties
+        .min_by(|(i, a), (j, b)| a.total_cmp(b).then(i.cmp(j)))
+        .map(|(i, _)| nodes[i].name.clone())
+}
+
+/// Reads the topology and pipe parameters of a network from features
+///
+/// Identical pipe parameters are merged into one set like in [`Parameters::deduplicated`].
+pub fn import(
+    collection: &FeatureCollection,
+    mapping: &PropertyMapping,
+) -> Result<(Topology, Parameters), Error> {
+    if collection.kind != "FeatureCollection" {
+        return Err(anyhow!(
+            "expected a FeatureCollection, but got a {}",
+            collection.kind
+        ));
+    }
+    let geographic = collection.is_geographic()?;
+
+    let mut nodes = vec![];
+    // pipes, consumers and sources are connected after all nodes are known
+    let mut connections = vec![];
+    for (i, feature) in collection.features.iter().enumerate() {
+        let properties = Properties {
+            feature: i,
+            values: feature.properties.as_ref(),
+        };
+        let geometry = feature
+            .geometry
+            .as_ref()
+            .ok_or(anyhow!("feature {}: geometry is missing", i))?;
+
+        let kind = match properties.text(&mapping.kind)? {
+            Some(kind) => kind.to_lowercase(),
+            None => match geometry {
+                Geometry::Point { .. } => String::from(NODE),
+                _ => String::from(PIPE),
+            },
+        };
+        let name = properties
+            .text(&mapping.name)?
+            .unwrap_or(format!("{}_{}", kind, i + 1));
+
+        match kind.as_str() {
+            NODE => {
+                let Geometry::Point { coordinates } = geometry else {
+                    return Err(anyhow!("feature {}: node '{}' is not a point", i, name));
+                };
+                if coordinates.len() < 2 {
+                    return Err(anyhow!(
+                        "feature {}: point of node '{}' has less than two coordinates",
+                        i,
+                        name
+                    ));
+                }
+
+                nodes.push(Node {
+                    name,
+                    position: Position {
+                        x: coordinates[0],
+                        y: coordinates[1],
+                        z: coordinates.get(2).copied().unwrap_or_default(),
+                    },
+                    feed: properties.flag(&mapping.feed)?.unwrap_or(true),
+                });
+            }
+            PIPE | CONSUMER | SOURCE => connections.push((kind, name, properties, geometry)),
+            _ => return Err(anyhow!("feature {}: unknown kind '{}'", i, kind)),
+        }
+    }
+
+    let mut cells = CellIndex::new(mapping.tolerance);
+    for (i, node) in nodes.iter().enumerate() {
+        cells.insert(i, node.position.x, node.position.y);
+    }
+
+    let mut topology = Topology {
+        nodes: vec![],
+        pipes: vec![],
+        consumers: vec![],
+        sources: vec![],
+    };
+    let mut pipe_parameters = vec![];
+    for (kind, name, properties, geometry) in connections {
+        let line = match geometry {
+            Geometry::Point { coordinates } => vec![coordinates.clone()],
+            Geometry::LineString { coordinates } => coordinates.clone(),
+            Geometry::MultiLineString { coordinates } => match &coordinates[..] {
+                [line] => line.clone(),
+                _ => {
+                    return Err(anyhow!(
+                        "feature {}: {} '{}' consists of {} lines",
+                        properties.feature,
+                        kind,
+                        name,
+                        coordinates.len()
+                    ))
+                }
+            },
+        };
+
+        let node = |key: &str, end: Option<&Vec<f64>>| -> Result<String, Error> {
+            if let Some(node) = properties.text(key)? {
+                return Ok(node);
+            }
+
+            let end = end.ok_or(anyhow!(
+                "feature {}: {} '{}' has no property '{}'",
+                properties.feature,
+                kind,
+                name,
+                key
+            ))?;
+            snap(&nodes, &cells, end, mapping.tolerance).ok_or(anyhow!(
+                "feature {}: {} '{}' has no property '{}' and no node within {} of {:?}",
+                properties.feature,
+                kind,
+                name,
+                key,
+                mapping.tolerance,
+                end
+            ))
+        };
+
+        match kind.as_str() {
+            PIPE => {
+                if line.len() < 2 {
+                    return Err(anyhow!(
+                        "feature {}: pipe '{}' is not a line",
+                        properties.feature,
+                        name
+                    ));
+                }
+
+                let src = node(&mapping.src, line.first())?;
+                let tgt = node(&mapping.tgt, line.last())?;
+                pipe_parameters.push((
+                    name.clone(),
+                    read_parameters(&properties, &line, geographic, mapping, &name)?,
+                ));
+                topology.pipes.push(Pipe { name, src, tgt });
+            }
+            CONSUMER => {
+                let src = node(&mapping.src, line.first())?;
+                let tgt = node(&mapping.tgt, None)?;
+                topology.consumers.push(Consumer { name, src, tgt });
+            }
+            _ => {
+                let src = node(&mapping.src, None)?;
+                let tgt = node(&mapping.tgt, line.first())?;
+                topology.sources.push(Source { name, src, tgt });
+            }
+        }
+    }
+    topology.nodes = nodes;
+
+    for (kind, names) in [
+        (
+            NODE,
+            topology
+                .nodes
+                .iter()
+                .map(|node| &node.name)
+                .collect::<Vec<_>>(),
+        ),
+        (PIPE, topology.pipes.iter().map(|pipe| &pipe.name).collect()),
+        (
+            CONSUMER,
+            topology
+                .consumers
+                .iter()
+                .map(|consumer| &consumer.name)
+                .collect(),
+        ),
+        (
+            SOURCE,
+            topology.sources.iter().map(|source| &source.name).collect(),
+        ),
+    ] {
+        let mut unique = HashSet::new();
+        if let Some(name) = names.into_iter().find(|name| !unique.insert(*name)) {
+            return Err(anyhow!("{} name '{}' is used twice", kind, name));
+        }
+    }
+
+    Ok((topology, Parameters::deduplicated(pipe_parameters)))
+}
+
+/// Reads the parameters of a pipe in the nominal units of the parameters file
+fn read_parameters(
+    properties: &Properties,
+    line: &[Vec<f64>],
+    geographic: bool,
+    mapping: &PropertyMapping,
+    name: &str,
+) -> Result<PipeParameters, Error> {
+    let nominal_units = GeometryUnits::nominal();
+    let factor = |from: LengthUnit, to: LengthUnit| from.in_meters() / to.in_meters();
+
+    let optional = |key: &str, parameter: &str| -> Result<Option<f64>, Error> {
+        Ok(properties
+            .number(key)?
+            .or(mapping.defaults.get(parameter).copied()))
+    };
+    let required = |key: &str, parameter: &str| -> Result<f64, Error> {
+        optional(key, parameter)?.ok_or(anyhow!(
+            "feature {}: pipe '{}' has no property '{}' and no default {}",
+            properties.feature,
+            name,
+            key,
+            parameter
+        ))
+    };
+
+    let length = match optional(&mapping.length, "length")? {
+        Some(length) => length * factor(mapping.units.length, nominal_units.length),
+        None if geographic => {
+            if let Some(point) = line
+                .iter()
+                .find(|point| point[0].abs() > 180. || point[1].abs() > 90.)
+            {
+                return Err(anyhow!(
+                    "feature {}: {:?} of pipe '{}' is not a longitude and latitude, the collection \
+                     has to name its projected coordinate reference system in 'crs'",
+                    properties.feature,
+                    point,
+                    name
+                ));
+            }
+            line.windows(2)
+                .map(|pair| geodesic_distance(&pair[0], &pair[1]))
+                .sum::<f64>()
+                / nominal_units.length.in_meters()
+        }
+        None => {
+            line.windows(2)
+                .map(|pair| distance(&pair[0], &pair[1]))
+                .sum::<f64>()
+                / nominal_units.length.in_meters()
+        }
+    };
+
+    let diameter = optional(&mapping.diameter, "diameter")?;
+    let velocity = optional(&mapping.velocity, "velocity")?;
+    if let (None, Some(velocity)) = (diameter, velocity) {
+        return Ok(PipeParameters::FixedVelocity { length, velocity });
+    }
+
+    let friction_model = properties
+        .text(&mapping.friction_model)?
+        .map(|model| {
+            serde_json::from_value(Value::String(model.clone())).map_err(|_| {
+                anyhow!(
+                    "feature {}: unknown friction model '{}' of pipe '{}'",
+                    properties.feature,
+                    model,
+                    name
+                )
+            })
+        })
+        .transpose()?;
+
+    Ok(PipeParameters::Full {
+        length,
+        diameter: required(&mapping.diameter, "diameter")?
+            * factor(mapping.units.diameter, nominal_units.diameter),
+        transmittance: required(&mapping.transmittance, "transmittance")?,
+        roughness: required(&mapping.roughness, "roughness")?
+            * factor(mapping.units.roughness, nominal_units.roughness),
+        zeta: required(&mapping.zeta, "zeta")?,
+        friction_model,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::read_temperatures;
+    use crate::types::formats::custom;
+
+    use serde_json::json;
+
+    #[test]
+    fn export_and_import_network() {
+        let network = custom::load("data/running_example").expect("could not load network");
+
+        let collection = export(&network.topology, &network.parameters, &HashMap::new())
+            .expect("could not export network");
+        let path = "/tmp/rimulation_running_example.geojson";
+        save(&collection, path).expect("could not save GeoJSON");
+        let (topology, parameters) = import(
+            &load(path).expect("could not load GeoJSON"),
+            &PropertyMapping::default(),
+        )
+        .expect("could not import network");
+
+        assert_eq!(topology.nodes.len(), network.topology.nodes.len());
+        for (imported, original) in topology.nodes.iter().zip(&network.topology.nodes) {
+            assert_eq!(imported.name, original.name);
+            assert_eq!(imported.position, original.position);
+            assert_eq!(imported.feed, original.feed);
+        }
+        assert_eq!(topology.pipes.len(), network.topology.pipes.len());
+        for (imported, original) in topology.pipes.iter().zip(&network.topology.pipes) {
+            assert_eq!(
+                (&imported.name, &imported.src, &imported.tgt),
+                (&original.name, &original.src, &original.tgt)
+            );
+            assert_eq!(
+                parameters.parameters[&parameters.pipes[&imported.name]],
+                network.parameters.parameters[&network.parameters.pipes[&original.name]]
+            );
+        }
+        for (imported, original) in topology.consumers.iter().zip(&network.topology.consumers) {
+            assert_eq!(
+                (&imported.name, &imported.src, &imported.tgt),
+                (&original.name, &original.src, &original.tgt)
+            );
+        }
+        for (imported, original) in topology.sources.iter().zip(&network.topology.sources) {
+            assert_eq!(
+                (&imported.name, &imported.src, &imported.tgt),
+                (&original.name, &original.src, &original.tgt)
+            );
+        }
+    }
+
+    #[test]
+    fn export_temperature_statistics() {
+        let network = custom::load("data/fixed_velocity/triangle").expect("could not load network");
+        let statistics = read_temperatures("data/fixed_velocity/triangle/result")
+            .expect("could not read results")
+            .into_iter()
+            .filter_map(|(name, temperatures)| {
+                TemperatureStatistics::of(&temperatures).map(|statistics| (name, statistics))
+            })
+            .collect();
+
+        let collection = export(&network.topology, &network.parameters, &statistics)
+            .expect("could not export network");
+
+        let properties = |name: &str| {
+            collection
+                .features
+                .iter()
+                .filter_map(|feature| feature.properties.as_ref())
+                .find(|properties| properties["kind"] == NODE && properties["name"] == name)
+                .expect("node was not exported")
+        };
+        let node = properties("F002");
+        let (min, mean, max) = (
+            node["temperature_min"].as_f64().unwrap(),
+            node["temperature_mean"].as_f64().unwrap(),
+            node["temperature_max"].as_f64().unwrap(),
+        );
+        assert!(min <= mean && mean <= max);
+        assert!(!properties("F001").contains_key("temperature_mean"));
+    }
+
+    fn line_feature(properties: Value, coordinates: Value) -> Value {
+        json!({
+            "type": "Feature",
+            "geometry": {"type": "LineString", "coordinates": coordinates},
+            "properties": properties
+        })
+    }
+
+    fn point_feature(properties: Value, coordinates: Value) -> Value {
+        json!({
+            "type": "Feature",
+            "geometry": {"type": "Point", "coordinates": coordinates},
+            "properties": properties
+        })
+    }
+
+    #[test]
+    fn import_with_property_mapping() {
+        let collection: FeatureCollection = serde_json::from_value(json!({
+            "type": "FeatureCollection",
+            "crs": {"type": "name", "properties": {"name": "urn:ogc:def:crs:EPSG::25832"}},
+            "features": [
+                point_feature(json!({"ID": "A", "Side": "supply"}), json!([0, 0])),
+                point_feature(json!({"ID": "B"}), json!([30, 40, 5])),
+                point_feature(json!({"ID": "C"}), json!([30, 0])),
+                line_feature(json!({"ID": "L1", "DN": "0.1", "U": 1.5}), json!([[0, 0], [30, 40]])),
+                line_feature(
+                    json!({"ID": "L2", "DN": 0.1, "U": 1.5, "Len": 0.05}),
+                    json!([[30, 40.0000001], [30, 0]])
+                ),
+                line_feature(json!({"V": 2}), json!([[0, 0], [30, 0]])),
+                point_feature(
+                    json!({"ID": "S", "Type": "Source", "From": "C"}),
+                    json!([0, 0])
+                ),
+            ]
+        }))
+        .expect("could not parse features");
+
+        let mapping: PropertyMapping = serde_json::from_value(json!({
+            "kind": "Type",
+            "name": "ID",
+            "src": "From",
+            "tgt": "To",
+            "length": "Len",
+            "diameter": "DN",
+            "transmittance": "U",
+            "velocity": "V",
+            "units": {"length": "km", "diameter": "m", "roughness": "mm"},
+            "defaults": {"roughness": 0.1, "zeta": 0},
+            "tolerance": 0.001
+        }))
+        .expect("could not parse mapping");
+        assert_eq!(mapping.feed, "feed");
+        assert_eq!(mapping.units.roughness, LengthUnit::Millimeters);
+
+        let (topology, parameters) = import(&collection, &mapping).expect("could not import");
+
+        assert!(topology.nodes.iter().all(|node| node.feed));
+        assert_eq!(
+            topology.nodes[1].position,
+            Position {
+                x: 30.,
+                y: 40.,
+                z: 5.
+            }
+        );
+        let connections: Vec<_> = topology
+            .pipes
+            .iter()
+            .map(|pipe| (pipe.name.as_str(), pipe.src.as_str(), pipe.tgt.as_str()))
+            .collect();
+        assert_eq!(
+            connections,
+            vec![("L1", "A", "B"), ("L2", "B", "C"), ("pipe_6", "A", "C")]
+        );
+        assert_eq!(
+            parameters.parameters[&parameters.pipes["L1"]],
+            PipeParameters::Full {
+                length: 50.,
+                diameter: 100.,
+                transmittance: 1.5,
+                roughness: 0.1,
+                zeta: 0.,
+                friction_model: None,
+            }
+        );
+        assert_eq!(
+            parameters.pipes["L1"], parameters.pipes["L2"],
+            "identical parameters are merged"
+        );
+        assert_eq!(
+            parameters.parameters[&parameters.pipes["pipe_6"]],
+            PipeParameters::FixedVelocity {
+                length: 30.,
+                velocity: 2.
+            }
+        );
+        assert_eq!(
+            (&topology.sources[0].src, &topology.sources[0].tgt),
+            (&String::from("C"), &String::from("A"))
+        );
+    }
+
+    #[test]
+    fn geodesic_distance_on_wgs84() {
+        // Flinders Peak to Buninyong, the example of Vincenty's inverse formula
+        let flinders_peak = [144.424_867_889, -37.951_033_417];
+        let buninyong = [143.926_495_528, -37.652_821_139];
+
+        assert!((geodesic_distance(&flinders_peak, &buninyong) - 54_972.271).abs() < 1e-3);
+        assert_eq!(geodesic_distance(&[10., 50., 200.], &[10., 50., 150.]), 50.);
+        // a minute of latitude at the equator
+        assert!((geodesic_distance(&[0., 0.], &[0., 1. / 60.]) - 1842.9).abs() < 0.1);
+    }
+
+    #[test]
+    fn measure_pipes_in_longitude_and_latitude() {
+        let pipe = |coordinates| {
+            line_feature(
+                json!({"src": "A", "tgt": "B", "diameter": 100, "transmittance": 1,
+                       "roughness": 0.1, "zeta": 0}),
+                coordinates,
+            )
+        };
+        let collection = |crs: Option<&str>, coordinates| -> FeatureCollection {
+            let mut collection = json!({
+                "type": "FeatureCollection",
+                "features": [
+                    point_feature(json!({"name": "A"}), json!([0, 0])),
+                    point_feature(json!({"name": "B"}), json!([1, 1])),
+                    pipe(coordinates),
+                ]
+            });
+            if let Some(crs) = crs {
+                collection["crs"] = json!({"type": "name", "properties": {"name": crs}});
+            }
+            serde_json::from_value(collection).expect("could not parse features")
+        };
+        let length = |collection: &FeatureCollection| -> Result<f64, Error> {
+            let (_, parameters) = import(collection, &PropertyMapping::default())?;
+            match parameters.parameters[&parameters.pipes["pipe_3"]] {
+                PipeParameters::Full { length, .. } => Ok(length),
+                _ => panic!("pipe parameters are not full parameters"),
+            }
+        };
+
+        let degrees = json!([[8.68, 50.11], [8.68, 50.12]]);
+        for crs in [None, Some("urn:ogc:def:crs:OGC:1.3:CRS84")] {
+            let meters = length(&collection(crs, degrees.clone())).expect("could not import");
+            assert!((1100. ..1120.).contains(&meters), "{}", meters);
+        }
+        assert_eq!(
+            length(&collection(Some("EPSG:25832"), json!([[0, 0], [30, 40]]))).unwrap(),
+            50.
+        );
+        assert_eq!(
+            length(&collection(
+                None,
+                json!([[475000, 5550000], [475030, 5550040]])
+            ))
+            .expect_err("coordinates are projected")
+            .to_string(),
+            "feature 2: [475000.0, 5550000.0] of pipe 'pipe_3' is not a longitude and latitude, \
+             the collection has to name its projected coordinate reference system in 'crs'"
+        );
+    }
+
+    #[test]
+    fn report_unconnected_pipe() {
+        let collection: FeatureCollection = serde_json::from_value(json!({
+            "type": "FeatureCollection",
+            "features": [
+                point_feature(json!({"name": "A"}), json!([0, 0])),
+                line_feature(
+                    json!({"name": "P", "diameter": 100, "transmittance": 1, "roughness": 0.1, "zeta": 0}),
+                    json!([[0, 0], [1, 1]])
+                ),
+            ]
+        }))
+        .expect("could not parse features");
+
+        let result = import(&collection, &PropertyMapping::default());
+
+        assert_eq!(
+            result.expect_err("pipe is not connected").to_string(),
+            "feature 1: pipe 'P' has no property 'tgt' and no node within 0.000001 of [1.0, 1.0]"
+        );
+    }
+}
@@ -1,4 +1,10 @@
+pub mod cells;
 pub mod custom;
+pub mod epanet;
+pub mod geojson;
+pub mod modelica;
+pub mod proprietary;
+pub mod shapefile;
 
 pub trait NamedComponent {
     fn get_name(&self) -> String;
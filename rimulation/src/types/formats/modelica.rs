@@ -0,0 +1,643 @@
+//! Export of networks to Modelica models built from the Modelica Buildings library
+//!
+//! - pipes become `PlugFlowPipe`s, whose insulation is chosen to match the transmittance
+//! - consumers become `HeaterCooler_u`s drawing their demand between feed and return node
+//! - sources become a `Boundary_pT` on the feed node with the base pressure plus the pressure
+//!   lift and the source temperature, and a `Boundary_pT` on the return node with the base
+//!   pressure
+//! - signals become `CombiTimeTable`s sampled at the time steps of the simulation, which
+//!   interpolate linearly between the samples
+//!
+//! Nodes are not components, the ports of all components at a node are connected instead.
+//! Consumers whose return node is not part of the network return into a `Boundary_pT` with the
+//! base pressure of the first source.
+
+use super::custom::{self, Input, PipeParameters};
+use crate::types::{network::HOURS_PER_YEAR, signal};
+
+use anyhow::{anyhow, Error};
+use std::{
+    collections::{HashMap, HashSet},
+    f64::consts::PI,
+    fs,
+};
+
+/// Values the model needs, but the custom format does not know
+#[derive(Debug, Clone)]
+pub struct Options {
+    pub model_name: String,
+    /// Factor converting the demands of consumers to \[W\]
+    pub demand_unit: f64,
+    /// Heat conductivity of the pipe insulation \[W/(m K)\]
+    pub insulation_conductivity: f64,
+    /// Velocity \[m/s\] at which pipes reach their nominal mass flow
+    pub nominal_velocity: f64,
+    /// Pressure drop \[Pa\] of consumers at their nominal mass flow
+    pub consumer_pressure_drop: f64,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            model_name: String::from("Network"),
+            demand_unit: 1000.,
+            insulation_conductivity: 0.024,
+            nominal_velocity: 1.,
+            consumer_pressure_drop: 50_000.,
+        }
+    }
+}
+
+const MEDIUM: &str = "redeclare package Medium = Medium";
+const ZERO_CELSIUS: f64 = 273.15;
+/// Wall thickness of all pipes \[m\], the default of `PlugFlowPipe`
+const WALL_THICKNESS: f64 = 0.0035;
+/// Density \[kg/m^3\] and specific heat \[J/(kg K)\] of water for nominal mass flows
+const DENSITY: f64 = 1000.;
+const SPECIFIC_HEAT: f64 = 4180.;
+/// Half the size of the icons of components in the diagram
+const ICON_SIZE: f64 = 4.;
+/// Half the size of the diagram the positions of the nodes are scaled to
+const DIAGRAM_SIZE: f64 = 100.;
+
+/// Name of a component that is a valid Modelica identifier
+fn identifier(prefix: &str, name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("{}_{}", prefix, name)
+}
+
+/// Thickness \[m\] of an insulation with the heat conductivity k \[W/(m K)\] that lets a pipe
+/// with the diameter d \[m\] lose as much heat as the transmittance u \[W/(m^2 K)\] of its inner
+/// surface
+fn insulation_thickness(u: f64, d: f64, k: f64) -> f64 {
+    let inner_radius = d / 2. + WALL_THICKNESS;
+    inner_radius * ((2. * k / (u * d)).exp() - 1.)
+}
+
+/// Places components in the diagram by the positions of their nodes
+struct Diagram {
+    center: (f64, f64),
+    scale: f64,
+}
+
+impl Diagram {
+    fn new(nodes: &[custom::Node]) -> Self {
+        let (mut min, mut max) = (
+            (f64::INFINITY, f64::INFINITY),
+            (f64::NEG_INFINITY, f64::NEG_INFINITY),
+        );
+        for node in nodes {
+            min = (min.0.min(node.position.x), min.1.min(node.position.y));
+            max = (max.0.max(node.position.x), max.1.max(node.position.y));
+        }
+
+        let size = (max.0 - min.0).max(max.1 - min.1);
+        Self {
+            center: ((min.0 + max.0) / 2., (min.1 + max.1) / 2.),
+            scale: if size > 0. {
+                2. * DIAGRAM_SIZE / size
+            } else {
+                1.
+            },
+        }
+    }
+
+    fn placement(&self, position: (f64, f64)) -> String {
+        format!(
+            "annotation (Placement(transformation(origin = {{{}, {}}}, extent = {{{{-{}, -{}}}, {{{}, {}}}}})))",
+            ((position.0 - self.center.0) * self.scale).round(),
+            ((position.1 - self.center.1) * self.scale).round(),
+            ICON_SIZE,
+            ICON_SIZE,
+            ICON_SIZE,
+            ICON_SIZE
+        )
+    }
+}
+
+/// Declarations and connections of the model
+#[derive(Default)]
+struct Model {
+    declarations: Vec<String>,
+    equations: Vec<String>,
+    identifiers: HashSet<String>,
+}
+
+impl Model {
+    /// Declares a component with modifications, each given as `parameter = value`
+    fn declare(
+        &mut self,
+        class: &str,
+        name: &str,
+        modifications: &[String],
+        placement: Option<String>,
+    ) -> Result<(), Error> {
+        if !self.identifiers.insert(name.to_string()) {
+            return Err(anyhow!("two components are named '{}' in Modelica", name));
+        }
+
+        let mut declaration = format!("  {} {}", class, name);
+        if !modifications.is_empty() {
+            declaration += &format!("(\n    {})", modifications.join(",\n    "));
+        }
+        if let Some(placement) = placement {
+            declaration += &format!("\n    {}", placement);
+        }
+        self.declarations.push(declaration + ";");
+        Ok(())
+    }
+
+    fn connect(&mut self, a: &str, b: &str) {
+        self.equations.push(format!("  connect({}, {});", a, b));
+    }
+}
+
+/// Samples a signal at the time steps \[min\] of the simulation
+///
+/// After the last time step, the tables hold the last sample.
+fn sample(name: &str, scenario: &custom::Scenario) -> Result<Vec<(f64, f64)>, Error> {
+    let settings = &scenario.settings;
+    let signal = signal::resolve(name, &scenario.signals)
+        .map_err(|err| anyhow!("could not resolve signal '{}': {}", name, err))?;
+
+    let samples = (0..settings.num_steps())
+        .map(|i| i as f64 * settings.time_step)
+        .map(|t| {
+            signal
+                .value_at(t)
+                .map(|value| (t, value))
+                .map_err(|err| anyhow!("could not sample signal '{}' at {} min: {}", name, t, err))
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+
+    if let Some((t, _)) = samples.iter().find(|(_, value)| !value.is_finite()) {
+        return Err(anyhow!("signal '{}' has no value at {} min", name, t));
+    }
+    if samples.is_empty() {
+        return Err(anyhow!(
+            "signal '{}' has no value at any time step of the simulation",
+            name
+        ));
+    }
+
+    Ok(samples)
+}
+
+/// Writes a network as Modelica model and reports everything the model does not represent
+pub fn export(
+    network: &custom::Network,
+    options: &Options,
+) -> Result<(String, Vec<String>), Error> {
+    let topology = &network.topology;
+    let scenario = &network.scenario;
+    let settings = &scenario.settings;
+    let mut model = Model::default();
+    let mut losses = vec![];
+
+    let diagram = Diagram::new(&topology.nodes);
+    let positions: HashMap<&str, (f64, f64)> = topology
+        .nodes
+        .iter()
+        .map(|node| (node.name.as_str(), (node.position.x, node.position.y)))
+        .collect();
+    let feed: HashMap<&str, bool> = topology
+        .nodes
+        .iter()
+        .map(|node| (node.name.as_str(), node.feed))
+        .collect();
+    let position = |kind: &str, name: &str, node: &str| {
+        positions.get(node).copied().ok_or(anyhow!(
+            "{} '{}' refers to unknown node '{}'",
+            kind,
+            name,
+            node
+        ))
+    };
+
+    // ports of the components connected at each node
+    let mut ports: HashMap<&str, Vec<String>> = HashMap::new();
+
+    // signals in the order they are first used
+    let mut tables: Vec<String> = vec![];
+    let mut table = |name: &String| {
+        if !tables.contains(name) {
+            tables.push(name.clone());
+        }
+        format!("{}.y[1]", identifier("signal", name))
+    };
+
+    model.declare(
+        "Modelica.Thermal.HeatTransfer.Sources.FixedTemperature",
+        "ground",
+        &[format!(
+            "T = {}",
+            settings.ground_temperature + ZERO_CELSIUS
+        )],
+        None,
+    )?;
+
+    let mut num_with_zeta = 0;
+    for pipe in &topology.pipes {
+        let set = network
+            .parameters
+            .pipes
+            .get(&pipe.name)
+            .ok_or(anyhow!("pipe '{}' has no parameters", pipe.name))?;
+        let parameters = network
+            .parameters
+            .parameters
+            .get(set)
+            .ok_or(anyhow!(
+                "parameters '{}' of pipe '{}' do not exist",
+                set,
+                pipe.name
+            ))?
+            .clone()
+            .in_meters(&network.parameters.units);
+        let PipeParameters::Full {
+            length,
+            diameter,
+            transmittance,
+            roughness,
+            zeta,
+            ..
+        } = parameters
+        else {
+            return Err(anyhow!(
+                "pipe '{}' has a fixed velocity, which the pipe model cannot represent",
+                pipe.name
+            ));
+        };
+        if transmittance <= 0. {
+            return Err(anyhow!(
+                "pipe '{}' needs a positive transmittance, but has {}",
+                pipe.name,
+                transmittance
+            ));
+        }
+        if zeta != 0. {
+            num_with_zeta += 1;
+        }
+
+        let (src, tgt) = (
+            position("pipe", &pipe.name, &pipe.src)?,
+            position("pipe", &pipe.name, &pipe.tgt)?,
+        );
+        let temperature = if feed[pipe.src.as_str()] {
+            settings.feed_temperature
+        } else {
+            settings.return_temperature
+        } + ZERO_CELSIUS;
+
+        let name = identifier("pipe", &pipe.name);
+        model.declare(
+            "Buildings.Fluid.FixedResistances.PlugFlowPipe",
+            &name,
+            &[
+                String::from(MEDIUM),
+                format!("length = {}", length),
+                format!("dh = {}", diameter),
+                format!("thickness = {}", WALL_THICKNESS),
+                format!("roughness = {}", roughness),
+                format!("kIns = {}", options.insulation_conductivity),
+                format!(
+                    "dIns = {}",
+                    insulation_thickness(transmittance, diameter, options.insulation_conductivity)
+                ),
+                format!(
+                    "m_flow_nominal = {}",
+                    DENSITY * options.nominal_velocity * PI / 4. * diameter * diameter
+                ),
+                format!("T_start_in = {}", temperature),
+                format!("T_start_out = {}", temperature),
+            ],
+            Some(diagram.placement(((src.0 + tgt.0) / 2., (src.1 + tgt.1) / 2.))),
+        )?;
+        model.connect("ground.port", &format!("{}.heatPort", name));
+        ports
+            .entry(pipe.src.as_str())
+            .or_default()
+            .push(format!("{}.port_a", name));
+        ports
+            .entry(pipe.tgt.as_str())
+            .or_default()
+            .push(format!("{}.port_b", name));
+    }
+    if num_with_zeta > 0 {
+        losses.push(format!(
+            "minor losses (zeta) of {} pipes are not exported",
+            num_with_zeta
+        ));
+    }
+    losses.push(String::from(
+        "friction models are replaced by the pressure drop correlation of PlugFlowPipe",
+    ));
+
+    let source_input = |source: &custom::Source| {
+        let input_name = scenario
+            .source_inputs
+            .get(&source.name)
+            .ok_or(anyhow!("no inputs defined for source '{}'", source.name))?;
+        match scenario.inputs.get(input_name) {
+            Some(Input::Source {
+                base_pressure,
+                pressure_lift,
+                temperature,
+            }) => Ok((base_pressure, pressure_lift, temperature)),
+            _ => Err(anyhow!(
+                "input '{}' of source '{}' is not a source input",
+                input_name,
+                source.name
+            )),
+        }
+    };
+
+    for source in &topology.sources {
+        let (base_pressure, pressure_lift, temperature) = source_input(source)?;
+        let name = identifier("source", &source.name);
+        let tgt = position("source", &source.name, &source.tgt)?;
+
+        model.declare(
+            "Buildings.Fluid.Sources.Boundary_pT",
+            &name,
+            &[
+                String::from(MEDIUM),
+                String::from("use_p_in = true"),
+                String::from("use_T_in = true"),
+                String::from("nPorts = 1"),
+            ],
+            Some(diagram.placement(tgt)),
+        )?;
+        model.declare(
+            "Modelica.Blocks.Math.Add",
+            &format!("{}_pressure", name),
+            &[],
+            None,
+        )?;
+        model.declare(
+            "Modelica.Blocks.Math.UnitConversions.From_degC",
+            &format!("{}_temperature", name),
+            &[],
+            None,
+        )?;
+        model.connect(&table(base_pressure), &format!("{}_pressure.u1", name));
+        model.connect(&table(pressure_lift), &format!("{}_pressure.u2", name));
+        model.connect(&format!("{}_pressure.y", name), &format!("{}.p_in", name));
+        model.connect(&table(temperature), &format!("{}_temperature.u", name));
+        model.connect(
+            &format!("{}_temperature.y", name),
+            &format!("{}.T_in", name),
+        );
+        ports
+            .entry(source.tgt.as_str())
+            .or_default()
+            .push(format!("{}.ports[1]", name));
+
+        if let Some(src) = positions.get(source.src.as_str()) {
+            let name = format!("{}_return", name);
+            model.declare(
+                "Buildings.Fluid.Sources.Boundary_pT",
+                &name,
+                &[
+                    String::from(MEDIUM),
+                    String::from("use_p_in = true"),
+                    format!("T = {}", settings.return_temperature + ZERO_CELSIUS),
+                    String::from("nPorts = 1"),
+                ],
+                Some(diagram.placement(*src)),
+            )?;
+            model.connect(&table(base_pressure), &format!("{}.p_in", name));
+            ports
+                .entry(source.src.as_str())
+                .or_default()
+                .push(format!("{}.ports[1]", name));
+        }
+    }
+
+    for consumer in &topology.consumers {
+        let consumer_input = scenario.consumer_inputs.get(&consumer.name).ok_or(anyhow!(
+            "no inputs defined for consumer '{}'",
+            consumer.name
+        ))?;
+        let Some(Input::Consumer { demand, .. }) = scenario.inputs.get(&consumer_input.input)
+        else {
+            return Err(anyhow!(
+                "input '{}' of consumer '{}' is not a consumer input",
+                consumer_input.input,
+                consumer.name
+            ));
+        };
+
+        let scale = consumer_input.factors.yearly_demand / HOURS_PER_YEAR * options.demand_unit;
+        let max_demand = sample(demand, scenario)?
+            .iter()
+            .map(|(_, value)| value.abs())
+            .fold(0., f64::max)
+            * scale;
+        let temperature_difference = (settings.feed_temperature - settings.return_temperature)
+            .abs()
+            .max(1.);
+
+        let name = identifier("consumer", &consumer.name);
+        let src = position("consumer", &consumer.name, &consumer.src)?;
+        model.declare(
+            "Buildings.Fluid.HeatExchangers.HeaterCooler_u",
+            &name,
+            &[
+                String::from(MEDIUM),
+                format!(
+                    "m_flow_nominal = {}",
+                    (max_demand / (SPECIFIC_HEAT * temperature_difference)).max(1e-3)
+                ),
+                format!("dp_nominal = {}", options.consumer_pressure_drop),
+                format!("Q_flow_nominal = {}", -scale),
+            ],
+            Some(diagram.placement(src)),
+        )?;
+        model.connect(&table(demand), &format!("{}.u", name));
+        ports
+            .entry(consumer.src.as_str())
+            .or_default()
+            .push(format!("{}.port_a", name));
+
+        if positions.contains_key(consumer.tgt.as_str()) {
+            ports
+                .entry(consumer.tgt.as_str())
+                .or_default()
+                .push(format!("{}.port_b", name));
+        } else {
+            let (base_pressure, _, _) = source_input(
+                topology
+                    .sources
+                    .first()
+                    .ok_or(anyhow!("network has no source"))?,
+            )?;
+            let return_name = format!("{}_return", name);
+            model.declare(
+                "Buildings.Fluid.Sources.Boundary_pT",
+                &return_name,
+                &[
+                    String::from(MEDIUM),
+                    String::from("use_p_in = true"),
+                    String::from("nPorts = 1"),
+                ],
+                Some(diagram.placement((src.0, src.1))),
+            )?;
+            model.connect(&table(base_pressure), &format!("{}.p_in", return_name));
+            model.connect(
+                &format!("{}.port_b", name),
+                &format!("{}.ports[1]", return_name),
+            );
+        }
+    }
+    if !topology.consumers.is_empty() {
+        losses.push(String::from(
+            "return temperatures of consumers are not exported, consumers draw their demand only",
+        ));
+    }
+
+    for node in &topology.nodes {
+        if let Some([first, others @ ..]) = ports.get(node.name.as_str()).map(Vec::as_slice) {
+            for other in others {
+                model.connect(first, other);
+            }
+        }
+    }
+
+    let mut resampled = false;
+    for name in &tables {
+        let samples = sample(name, scenario)?;
+
+        let constant = samples.iter().all(|(_, value)| *value == samples[0].1);
+        resampled |= !constant;
+        let rows: Vec<String> = if constant {
+            // constant signals only need their first and last time
+            [samples[0], samples[samples.len() - 1]]
+                .iter()
+                .map(|(t, value)| format!("{}, {}", t * 60., value))
+                .collect()
+        } else {
+            samples
+                .iter()
+                .map(|(t, value)| format!("{}, {}", t * 60., value))
+                .collect()
+        };
+
+        model.declare(
+            "Modelica.Blocks.Sources.CombiTimeTable",
+            &identifier("signal", name),
+            &[
+                format!("table = [{}]", rows.join(";\n      ")),
+                String::from("smoothness = Modelica.Blocks.Types.Smoothness.LinearSegments"),
+                String::from("extrapolation = Modelica.Blocks.Types.Extrapolation.HoldLastPoint"),
+            ],
+            None,
+        )?;
+    }
+
+    if resampled {
+        losses.push(String::from(
+            "signals are sampled at the time steps of the simulation and interpolated linearly \
+             between them, changes in between are lost",
+        ));
+    }
+
+    let text = format!(
+        "model {name}\n  replaceable package Medium = Buildings.Media.Water;\n\n{declarations}\n\nequation\n{equations}\n  annotation (Diagram(coordinateSystem(extent = {{{{-{size}, -{size}}}, {{{size}, {size}}}}})));\nend {name};\n",
+        name = options.model_name,
+        declarations = model.declarations.join("\n"),
+        equations = model.equations.join("\n"),
+        size = DIAGRAM_SIZE + 2. * ICON_SIZE,
+    );
+
+    Ok((text, losses))
+}
+
+pub fn save(text: &str, path: &str) -> Result<(), Error> {
+    fs::write(path, text).map_err(|err| anyhow!("could not write Modelica model: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insulation_matches_transmittance() {
+        let (u, d, k) = (2., 0.1, 0.024);
+        let inner_radius = d / 2. + WALL_THICKNESS;
+        let outer_radius = inner_radius + insulation_thickness(u, d, k);
+
+        // heat loss per length and kelvin of the insulation equals the one of the inner surface
+        let loss = 2. * PI * k / (outer_radius / inner_radius).ln();
+        approx::assert_relative_eq!(loss, u * PI * d, max_relative = 1e-12);
+    }
+
+    #[test]
+    fn export_running_example() {
+        let mut network = custom::load("data/running_example").expect("could not load network");
+        // the signals of the example are given for the time steps up to 15 min
+        network.scenario.settings.time_end = 20. / (24. * 60.);
+
+        let (text, losses) = export(&network, &Default::default()).expect("could not export");
+
+        assert!(text.starts_with("model Network\n"));
+        assert!(text.ends_with("end Network;\n"));
+        assert_eq!(text.matches("PlugFlowPipe pipe_").count(), 8);
+        assert!(text.contains("HeaterCooler_u consumer_C1"));
+        assert!(text.contains("Boundary_pT source_S1("));
+        assert!(text.contains("Boundary_pT source_S1_return("));
+        for signal in [
+            "C1_demand",
+            "S1_base_pressure",
+            "S1_pressure_lift",
+            "S1_temperature",
+        ] {
+            assert!(text.contains(&format!("CombiTimeTable signal_{}(", signal)));
+        }
+        assert!(!text.contains("signal_C1_return_temperature"));
+
+        // the consumer connects feed node F004 and return node R004
+        assert!(text.contains("connect(pipe_PF3.port_b, pipe_PF4.port_b);"));
+        assert!(text.contains("connect(pipe_PF3.port_b, consumer_C1.port_a);"));
+        assert!(text.contains("connect(pipe_PR3.port_a, consumer_C1.port_b);"));
+        assert!(text.contains("connect(pipe_PF1.port_a, source_S1.ports[1]);"));
+        assert_eq!(
+            text.matches("connect(ground.port").count(),
+            8,
+            "every pipe loses heat to the ground"
+        );
+        assert_eq!(losses.len(), 4);
+        assert!(
+            text.contains("table = [0, 0.4;\n      300, 0.6;\n      600, 0.8;\n      900, 0.7]")
+        );
+
+        save(&text, "/tmp/rimulation_running_example.mo").expect("could not save model");
+    }
+
+    #[test]
+    fn report_signals_without_value_at_time_step() {
+        let network = custom::load("data/running_example").expect("could not load network");
+
+        assert_eq!(
+            export(&network, &Default::default())
+                .expect_err("signals end after 15 min")
+                .to_string(),
+            "could not sample signal 'C1_demand' at 20 min: 20 out of bounds ([0, 15])"
+        );
+    }
+
+    #[test]
+    fn reject_fixed_velocity() {
+        let network = custom::load("data/fixed_velocity/triangle").expect("could not load network");
+
+        assert_eq!(
+            export(&network, &Default::default())
+                .expect_err("pipes have fixed velocities")
+                .to_string(),
+            "pipe 'PF1' has a fixed velocity, which the pipe model cannot represent"
+        );
+    }
+}
@@ -0,0 +1,644 @@
+//! Vendor export format
+//!
+//! A `main.json` names the topology and scenario files, components are stored in dictionaries
+//! keyed by their names and settings carry their units in their keys.
+
+use super::custom::{self, TimeUnit};
+
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Serialize};
+use serde_json::from_reader;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Configuration {
+    pub topology: String,
+    pub scenario: String,
+    #[serde(default)]
+    pub ui_parameters: Option<UiParameters>,
+}
+
+/// Settings of the visualisation in the vendor's user interface
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct UiParameters {
+    /// Temperatures \[°C\] at the lower and upper end of the color scale
+    pub color_limits_temperature: [f64; 2],
+    /// Date and time of the start of the simulation, e.g. `2023-04-22 08:00:00`
+    pub start_date: String,
+}
+
+// Topology
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Node {
+    pub position: Vec<f64>,
+    pub is_feed: bool,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Pipe {
+    pub nodes: Vec<String>,
+    pub length: f64,
+    pub diameter: f64,
+    pub transmittance: f64,
+    pub roughness: f64,
+    pub zeta: f64,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Consumer {
+    pub nodes: Vec<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Source {
+    pub nodes: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Topology {
+    pub nodes: BTreeMap<String, Node>,
+    pub pipes: BTreeMap<String, Pipe>,
+    pub consumers: BTreeMap<String, Consumer>,
+    pub sources: BTreeMap<String, Source>,
+}
+
+// Scenario
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Settings {
+    #[serde(rename = "feed_temperature [C]")]
+    pub feed_temperature: f64,
+    #[serde(rename = "return_temperature [C]")]
+    pub return_temperature: f64,
+    #[serde(rename = "ground_temperature [C]")]
+    pub ground_temperature: f64,
+    #[serde(rename = "t_start [d]")]
+    pub t_start: f64,
+    #[serde(rename = "t_end [d]")]
+    pub t_end: f64,
+    #[serde(rename = "dt [min]")]
+    pub dt: f64,
+    #[serde(rename = "ramp [h]")]
+    pub ramp: f64,
+    pub num_iter: usize,
+    pub tol: f64,
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SignalData {
+    Constant(f64),
+    Points(Vec<[f64; 2]>),
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct Signal {
+    /// `CONSTANT` or `PIECEWISE_CONSTANT`, `_LINEAR`, `_QUADRATIC` or `_CUBIC`
+    #[serde(rename = "type")]
+    pub signal_type: String,
+    /// Name and unit of the time and value axis
+    pub axes: Vec<[String; 2]>,
+    pub unit_scale: f64,
+    pub data: SignalData,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Input {
+    pub signals: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ConsumerSignal {
+    pub return_temperature: f64,
+    pub annual_consumption: f64,
+    pub input: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SourceSignal {
+    #[serde(rename = "type")]
+    pub source_type: String,
+    pub input: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PipeSignal {
+    pub input: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Scenario {
+    pub settings: Settings,
+    pub signals: BTreeMap<String, Signal>,
+    pub inputs: BTreeMap<String, Input>,
+    pub consumers: BTreeMap<String, ConsumerSignal>,
+    pub sources: BTreeMap<String, SourceSignal>,
+    #[serde(default)]
+    pub pipes: BTreeMap<String, PipeSignal>,
+}
+
+#[derive(Debug)]
+pub struct Network {
+    pub topology: Topology,
+    pub scenario: Scenario,
+}
+
+/// Loads the `main.json` in the directory `path`
+pub fn load_configuration(path: &str) -> Result<Configuration, Error> {
+    let configuration_file = fs::File::open(Path::new(path).join("main.json"))
+        .map_err(|err| anyhow!("could not open main file: {}", err))?;
+    from_reader(configuration_file).map_err(|err| anyhow!("could not decode main file: {}", err))
+}
+
+/// Loads the network described by the `main.json` in the directory `path`
+pub fn load(path: &str) -> Result<Network, Error> {
+    let directory = Path::new(path);
+    let configuration = load_configuration(path)?;
+
+    let topology_file = fs::File::open(directory.join(&configuration.topology))
+        .map_err(|err| anyhow!("could not open topology file: {}", err))?;
+    let topology: Topology =
+        from_reader(topology_file).map_err(|err| anyhow!("could not decode topology: {}", err))?;
+
+    let scenario_file = fs::File::open(directory.join(&configuration.scenario))
+        .map_err(|err| anyhow!("could not open scenario file: {}", err))?;
+    let scenario: Scenario =
+        from_reader(scenario_file).map_err(|err| anyhow!("could not decode scenario: {}", err))?;
+
+    Ok(Network { topology, scenario })
+}
+
+fn endpoints(kind: &str, name: &str, nodes: &[String]) -> Result<(String, String), Error> {
+    match nodes {
+        [src, tgt] => Ok((src.clone(), tgt.clone())),
+        _ => Err(anyhow!(
+            "{} '{}' connects {} nodes instead of 2",
+            kind,
+            name,
+            nodes.len()
+        )),
+    }
+}
+
+impl TryFrom<Topology> for custom::Topology {
+    type Error = Error;
+
+    fn try_from(value: Topology) -> Result<Self, Self::Error> {
+        let nodes = value
+            .nodes
+            .into_iter()
+            .map(|(name, node)| match node.position[..] {
+                [x, y, z] => Ok(custom::Node {
+                    name,
+                    position: custom::Position { x, y, z },
+                    feed: node.is_feed,
+                }),
+                _ => Err(anyhow!(
+                    "position of node '{}' has {} coordinates instead of 3",
+                    name,
+                    node.position.len()
+                )),
+            })
+            .collect::<Result<_, Error>>()?;
+
+        let pipes = value
+            .pipes
+            .into_iter()
+            .map(|(name, pipe)| {
+                let (src, tgt) = endpoints("pipe", &name, &pipe.nodes)?;
+                Ok(custom::Pipe { name, src, tgt })
+            })
+            .collect::<Result<_, Error>>()?;
+
+        let consumers = value
+            .consumers
+            .into_iter()
+            .map(|(name, consumer)| {
+                let (src, tgt) = endpoints("consumer", &name, &consumer.nodes)?;
+                Ok(custom::Consumer { name, src, tgt })
+            })
+            .collect::<Result<_, Error>>()?;
+
+        let sources = value
+            .sources
+            .into_iter()
+            .map(|(name, source)| {
+                let (src, tgt) = endpoints("source", &name, &source.nodes)?;
+                Ok(custom::Source { name, src, tgt })
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(custom::Topology {
+            nodes,
+            pipes,
+            consumers,
+            sources,
+        })
+    }
+}
+
+impl From<Settings> for custom::Settings {
+    fn from(value: Settings) -> Self {
+        custom::Settings {
+            feed_temperature: value.feed_temperature,
+            return_temperature: value.return_temperature,
+            ground_temperature: value.ground_temperature,
+            time_start: value.t_start,
+            time_end: value.t_end,
+            time_step: value.dt,
+            ramp_time: value.ramp,
+            num_iterations: value.num_iter,
+            tolerance: value.tol,
+        }
+    }
+}
+
+/// Parses the unit of the time axis of a signal
+fn time_unit(unit: &str) -> Result<TimeUnit, Error> {
+    match unit {
+        "s" => Ok(TimeUnit::Seconds),
+        "min" => Ok(TimeUnit::Minutes),
+        "h" => Ok(TimeUnit::Hours),
+        "d" => Ok(TimeUnit::Days),
+        _ => Err(anyhow!("unknown time unit '{}'", unit)),
+    }
+}
+
+impl TryFrom<Signal> for custom::Signal {
+    type Error = Error;
+
+    fn try_from(value: Signal) -> Result<Self, Self::Error> {
+        let degree = match value.signal_type.as_str() {
+            "CONSTANT" => {
+                let SignalData::Constant(data) = value.data else {
+                    return Err(anyhow!("constant signal has more than one value"));
+                };
+                return Ok(custom::Signal::Const {
+                    scale: value.unit_scale,
+                    data,
+                });
+            }
+            "PIECEWISE_CONSTANT" => 0,
+            "PIECEWISE_LINEAR" => 1,
+            "PIECEWISE_QUADRATIC" => 2,
+            "PIECEWISE_CUBIC" => 3,
+            signal_type => return Err(anyhow!("signal type '{}' unknown", signal_type)),
+        };
+
+        let points = match value.data {
+            SignalData::Points(points) => points,
+            SignalData::Constant(data) => vec![[0., data]],
+        };
+        let time_unit = match value.axes.first() {
+            Some([_, unit]) => time_unit(unit)?,
+            None => TimeUnit::default(),
+        };
+
+        Ok(custom::Signal::Poly {
+            degree,
+            scale: value.unit_scale,
+            data: points
+                .into_iter()
+                .map(|[t, v]| custom::DataPoint {
+                    t: t * time_unit.in_minutes(),
+                    v,
+                })
+                .collect(),
+            gaps: Default::default(),
+        })
+    }
+}
+
+impl TryFrom<Scenario> for custom::Scenario {
+    type Error = Error;
+
+    fn try_from(value: Scenario) -> Result<Self, Self::Error> {
+        let consumer_inputs: HashMap<String, custom::ConsumerInput> = value
+            .consumers
+            .into_iter()
+            .map(|(name, consumer)| {
+                (
+                    name,
+                    custom::ConsumerInput {
+                        input: consumer.input,
+                        factors: custom::ConsumerSignalFactors {
+                            yearly_demand: consumer.annual_consumption,
+                            normal_return_temperature: consumer.return_temperature,
+                        },
+                    },
+                )
+            })
+            .collect();
+        let consumer_input_names: HashSet<&String> =
+            consumer_inputs.values().map(|input| &input.input).collect();
+
+        let source_inputs: HashMap<String, String> = value
+            .sources
+            .into_iter()
+            .map(|(name, source)| (name, source.input))
+            .collect();
+        let source_input_names: HashSet<&String> = source_inputs.values().collect();
+
+        // pipes have no inputs in the custom format
+        let pipe_input_names: HashSet<&String> =
+            value.pipes.values().map(|pipe| &pipe.input).collect();
+
+        let mut inputs = HashMap::new();
+        for (name, input) in value.inputs {
+            let signals = &input.signals;
+            let input = if consumer_input_names.contains(&name) {
+                match &signals[..] {
+                    [demand, return_temperature, ..] => custom::Input::Consumer {
+                        demand: demand.clone(),
+                        return_temperature: return_temperature.clone(),
+                    },
+                    _ => {
+                        return Err(anyhow!(
+                            "consumer input '{}' needs 2 signals, but has {}",
+                            name,
+                            signals.len()
+                        ))
+                    }
+                }
+            } else if source_input_names.contains(&name) {
+                match &signals[..] {
+                    [base_pressure, pressure_lift, temperature, ..] => custom::Input::Source {
+                        base_pressure: base_pressure.clone(),
+                        pressure_lift: pressure_lift.clone(),
+                        temperature: temperature.clone(),
+                    },
+                    _ => {
+                        return Err(anyhow!(
+                            "source input '{}' needs 3 signals, but has {}",
+                            name,
+                            signals.len()
+                        ))
+                    }
+                }
+            } else if pipe_input_names.contains(&name) {
+                continue;
+            } else {
+                return Err(anyhow!("unused input '{}'", name));
+            };
+            inputs.insert(name, input);
+        }
+
+        let signals = value
+            .signals
+            .into_iter()
+            .map(|(name, signal)| {
+                custom::Signal::try_from(signal)
+                    .map(|signal| (name.clone(), signal))
+                    .map_err(|err| anyhow!("could not convert signal '{}': {}", name, err))
+            })
+            .collect::<Result<_, Error>>()?;
+
+        Ok(custom::Scenario {
+            settings: value.settings.into(),
+            signals,
+            inputs,
+            consumer_inputs,
+            source_inputs,
+        })
+    }
+}
+
+/// Pipe parameters of the custom format
+///
+/// Identical parameters of several pipes are merged into one set, see
+/// [`custom::Parameters::deduplicated`]. The geometry is given in the nominal units of the
+/// custom format.
+impl From<&Topology> for custom::Parameters {
+    fn from(value: &Topology) -> Self {
+        custom::Parameters::deduplicated(value.pipes.iter().map(|(name, pipe)| {
+            (
+                name.clone(),
+                custom::PipeParameters::Full {
+                    length: pipe.length,
+                    diameter: pipe.diameter,
+                    transmittance: pipe.transmittance,
+                    roughness: pipe.roughness,
+                    zeta: pipe.zeta,
+                    friction_model: None,
+                },
+            )
+        }))
+    }
+}
+
+impl Network {
+    /// Describes all information that is lost when converting to the custom format
+    pub fn lossy_mappings(&self) -> Vec<String> {
+        let scenario = &self.scenario;
+        let mut losses = vec![];
+
+        for (name, pipe) in &scenario.pipes {
+            losses.push(format!(
+                "input '{}' of pipe '{}' is dropped, pipes have no inputs in the custom format",
+                pipe.input, name
+            ));
+        }
+
+        for (name, source) in &scenario.sources {
+            losses.push(format!(
+                "type '{}' of source '{}' is dropped",
+                source.source_type, name
+            ));
+        }
+
+        let consumer_inputs: HashSet<&String> = scenario
+            .consumers
+            .values()
+            .map(|consumer| &consumer.input)
+            .collect();
+        let source_inputs: HashSet<&String> = scenario
+            .sources
+            .values()
+            .map(|source| &source.input)
+            .collect();
+        for (name, input) in &scenario.inputs {
+            let num_used = if consumer_inputs.contains(name) {
+                2
+            } else if source_inputs.contains(name) {
+                3
+            } else {
+                continue;
+            };
+
+            for signal in input.signals.iter().skip(num_used) {
+                losses.push(format!(
+                    "signal '{}' of input '{}' is dropped",
+                    signal, name
+                ));
+            }
+        }
+
+        for (name, signal) in &scenario.signals {
+            if let Some([quantity, unit]) = signal.axes.get(1) {
+                losses.push(format!(
+                    "value axis '{}' [{}] of signal '{}' is dropped",
+                    quantity, unit, name
+                ));
+            }
+        }
+
+        losses
+    }
+}
+
+impl TryFrom<Network> for custom::Network {
+    type Error = Error;
+
+    fn try_from(value: Network) -> Result<Self, Self::Error> {
+        let parameters = custom::Parameters::from(&value.topology);
+
+        Ok(custom::Network {
+            topology: value
+                .topology
+                .try_into()
+                .map_err(|err| anyhow!("could not convert topology: {}", err))?,
+            scenario: value
+                .scenario
+                .try_into()
+                .map_err(|err| anyhow!("could not convert scenario: {}", err))?,
+            parameters,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn convert_proprietary_format() {
+        let network: custom::Network = load("data/proprietary_format")
+            .expect("could not load network")
+            .try_into()
+            .expect("could not convert network");
+
+        let topology = &network.topology;
+        assert_eq!(topology.nodes.len(), 8);
+        assert_eq!(topology.nodes[0].name, "F001");
+        assert!(topology.nodes[0].feed);
+        assert_eq!(
+            topology.nodes[1].position,
+            custom::Position {
+                x: 200.,
+                y: 100.,
+                z: 0.
+            }
+        );
+        assert_eq!(topology.pipes.len(), 10);
+        assert_eq!(topology.sources[0].src, "R001");
+        assert_eq!(topology.sources[0].tgt, "F001");
+
+        let scenario = &network.scenario;
+        assert_eq!(scenario.settings.time_end, 3.);
+        assert_eq!(scenario.settings.time_step, 20.);
+        assert_eq!(scenario.settings.num_iterations, 100);
+        assert_eq!(
+            scenario.signals["S1_pressure"],
+            custom::Signal::Const {
+                scale: 1e5,
+                data: 5.
+            }
+        );
+        let custom::Signal::Poly { degree, data, .. } = &scenario.signals["S1_temperature"] else {
+            panic!("piecewise cubic signal was not converted to a polynomial signal");
+        };
+        assert_eq!(*degree, 3);
+        assert_eq!(data[1], custom::DataPoint { t: 500., v: 90. });
+
+        assert!(matches!(
+            &scenario.inputs["CON"],
+            custom::Input::Consumer { demand, .. } if demand == "consumer_consumption_profile"
+        ));
+        assert!(!scenario.inputs.contains_key("default_pipe_input"));
+        assert_eq!(scenario.consumer_inputs["C1"].factors.yearly_demand, 25.);
+        assert_eq!(scenario.source_inputs["S1"], "SRC");
+
+        // all pipes share the same parameters
+        assert_eq!(network.parameters.parameters.len(), 1);
+        assert_eq!(network.parameters.pipes.len(), 10);
+        assert_eq!(network.parameters.pipes["PR3"], "parameters_1");
+        assert_eq!(
+            network.parameters.parameters["parameters_1"],
+            custom::PipeParameters::Full {
+                length: 44.7,
+                diameter: 25.,
+                transmittance: 2.,
+                roughness: 0.05,
+                zeta: 0.3,
+                friction_model: None,
+            }
+        );
+    }
+
+    #[test]
+    fn load_ui_parameters() {
+        let configuration =
+            load_configuration("data/proprietary_format").expect("could not load main file");
+
+        assert_eq!(
+            configuration.ui_parameters,
+            Some(UiParameters {
+                color_limits_temperature: [75., 120.],
+                start_date: String::from("2023-04-22 08:00:00"),
+            })
+        );
+    }
+
+    #[test]
+    fn report_lossy_mappings() {
+        let network = load("data/proprietary_format").expect("could not load network");
+        let losses = network.lossy_mappings();
+
+        // 10 pipe inputs, 1 source type, 1 consumer signal and the value axes of 7 signals
+        assert_eq!(losses.len(), 19);
+        assert!(losses.contains(&String::from(
+            "signal 'temperature_drop_profile' of input 'CON' is dropped"
+        )));
+        assert!(losses.contains(&String::from("type 'Source2' of source 'S1' is dropped")));
+    }
+
+    #[test]
+    fn signals_in_hours() {
+        let signal = Signal {
+            signal_type: String::from("PIECEWISE_LINEAR"),
+            axes: vec![
+                [String::from("time"), String::from("h")],
+                [String::from("scale"), String::from("1/h")],
+            ],
+            unit_scale: 2.,
+            data: SignalData::Points(vec![[0., 1.], [1.5, 2.]]),
+        };
+
+        assert_eq!(
+            custom::Signal::try_from(signal.clone()).expect("could not convert signal"),
+            custom::Signal::Poly {
+                degree: 1,
+                scale: 2.,
+                data: vec![
+                    custom::DataPoint { t: 0., v: 1. },
+                    custom::DataPoint { t: 90., v: 2. },
+                ],
+                gaps: Default::default(),
+            }
+        );
+
+        let unknown = Signal {
+            signal_type: String::from("SPLINE"),
+            ..signal
+        };
+        assert!(custom::Signal::try_from(unknown)
+            .expect_err("signal type does not exist")
+            .to_string()
+            .contains("signal type 'SPLINE' unknown"));
+    }
+}
@@ -0,0 +1,167 @@
+//! Reader of the attribute tables of shapefiles (`.dbf`)
+//!
+//! The dBASE file starts with a header holding the number and length of the records, followed by
+//! a descriptor of 32 bytes per field up to the terminator `0x0D`. Every record starts with a
+//! deletion flag, followed by the fields with fixed widths.
+
+use anyhow::{anyhow, Error};
+use std::collections::HashMap;
+
+const DESCRIPTOR_LENGTH: usize = 32;
+const TERMINATOR: u8 = 0x0D;
+const DELETED: u8 = b'*';
+
+#[derive(Debug, PartialEq, Clone)]
+pub struct Record {
+    /// Deleted records are still stored to keep the order of the shapes
+    pub deleted: bool,
+    /// Trimmed values of the fields by field name
+    pub values: HashMap<String, String>,
+}
+
+struct Field {
+    name: String,
+    length: usize,
+}
+
+fn little_endian_u16(bytes: &[u8], offset: usize) -> Result<usize, Error> {
+    bytes
+        .get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+        .ok_or(anyhow!("header is truncated"))
+}
+
+fn text(bytes: &[u8]) -> String {
+    String::from_utf8_lossy(bytes)
+        .trim_matches(|c: char| c == '\0' || c.is_whitespace())
+        .to_string()
+}
+
+/// Parses the records of an attribute table in their order
+pub fn parse(bytes: &[u8]) -> Result<Vec<Record>, Error> {
+    let num_records = bytes
+        .get(4..8)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().expect("read four bytes")) as usize)
+        .ok_or(anyhow!("header is truncated"))?;
+    let header_length = little_endian_u16(bytes, 8)?;
+    let record_length = little_endian_u16(bytes, 10)?;
+
+    let mut fields = vec![];
+    let mut offset = DESCRIPTOR_LENGTH;
+    while bytes.get(offset).is_some_and(|byte| *byte != TERMINATOR) {
+        let descriptor = bytes
+            .get(offset..offset + DESCRIPTOR_LENGTH)
+            .ok_or(anyhow!(
+                "field descriptor {} is truncated",
+                fields.len() + 1
+            ))?;
+        fields.push(Field {
+            name: text(&descriptor[..11]),
+            length: descriptor[16] as usize,
+        });
+        offset += DESCRIPTOR_LENGTH;
+    }
+
+    if fields.iter().map(|field| field.length).sum::<usize>() + 1 != record_length {
+        return Err(anyhow!(
+            "fields take {} bytes, but records are {} bytes long",
+            fields.iter().map(|field| field.length).sum::<usize>() + 1,
+            record_length
+        ));
+    }
+
+    (0..num_records)
+        .map(|i| {
+            let start = header_length + i * record_length;
+            let record = bytes
+                .get(start..start + record_length)
+                .ok_or(anyhow!("record {} is truncated", i + 1))?;
+
+            let mut values = HashMap::new();
+            let mut offset = 1;
+            for field in &fields {
+                values.insert(
+                    field.name.clone(),
+                    text(&record[offset..offset + field.length]),
+                );
+                offset += field.length;
+            }
+
+            Ok(Record {
+                deleted: record[0] == DELETED,
+                values,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+pub mod test_util {
+    /// Writes records, given as values in the order of the fields, into a table whose fields
+    /// are all characters of the given widths
+    pub fn write(fields: &[(&str, usize)], records: &[Vec<&str>]) -> Vec<u8> {
+        let record_length = 1 + fields.iter().map(|(_, width)| width).sum::<usize>();
+        let header_length = 32 * (fields.len() + 1) + 1;
+
+        let mut bytes = vec![0u8; 32];
+        bytes[0] = 3;
+        bytes[4..8].copy_from_slice(&(records.len() as u32).to_le_bytes());
+        bytes[8..10].copy_from_slice(&(header_length as u16).to_le_bytes());
+        bytes[10..12].copy_from_slice(&(record_length as u16).to_le_bytes());
+
+        for (name, width) in fields {
+            let mut descriptor = [0u8; 32];
+            descriptor[..name.len()].copy_from_slice(name.as_bytes());
+            descriptor[11] = b'C';
+            descriptor[16] = *width as u8;
+            bytes.extend(descriptor);
+        }
+        bytes.push(0x0D);
+
+        for record in records {
+            bytes.push(b' ');
+            for ((_, width), value) in fields.iter().zip(record) {
+                bytes.extend(format!("{:<width$}", value, width = width).as_bytes());
+            }
+        }
+        bytes.push(0x1A);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_records() {
+        let mut bytes = test_util::write(
+            &[("ID", 4), ("DN", 6)],
+            &[vec!["A1", "100"], vec!["A2", " 80.5"]],
+        );
+        let header_length = 32 * 3 + 1;
+        bytes[header_length + 11] = b'*';
+
+        let records = parse(&bytes).expect("could not parse table");
+
+        assert_eq!(records.len(), 2);
+        assert!(!records[0].deleted);
+        assert_eq!(records[0].values["ID"], "A1");
+        assert_eq!(records[0].values["DN"], "100");
+        assert!(records[1].deleted);
+        assert_eq!(records[1].values["DN"], "80.5");
+    }
+
+    #[test]
+    fn report_inconsistent_record_length() {
+        let mut bytes = test_util::write(&[("ID", 4)], &[vec!["A1"]]);
+        bytes[10] = 9;
+
+        assert_eq!(
+            parse(&bytes)
+                .expect_err("record length is wrong")
+                .to_string(),
+            "fields take 5 bytes, but records are 9 bytes long"
+        );
+    }
+}
@@ -0,0 +1,612 @@
+//! Import of pipe inventories from polyline shapefiles
+//!
+//! Every line of the shapefile becomes a pipe, whose parameters are read from the attribute
+//! table in the `.dbf` file next to it:
+//! - the diameter from a column with the nominal diameter
+//! - the roughness by the material of the pipe
+//! - the transmittance by the year the pipe was built
+//!
+//! Line ends closer than the tolerance are joined into nodes named `N1`, `N2`, ..., and pipe
+//! lengths are measured along the lines. Ends that are not joined with any other pipe are
+//! reported as dangling, as well as everything that was skipped or replaced by a default.
+//! Lines of several parts become one pipe per part with the suffix `.part1`, `.part2`, ...
+//! Pipes whose name is already taken get the suffix `_2`, `_3`, ... and are reported as renamed.
+
+mod dbf;
+mod shp;
+
+use super::{
+    cells::CellIndex,
+    custom::{GeometryUnits, Node, Parameters, Pipe, PipeParameters, Position, Topology},
+};
+
+use anyhow::{anyhow, Error};
+use serde::{Deserialize, Serialize};
+use serde_json::from_reader;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::Path,
+};
+
+/// Attribute columns and the values derived from them
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Options {
+    /// Largest distance between line ends that are joined into one node, in the unit of the
+    /// coordinates
+    pub tolerance: f64,
+    /// Column with the names of the pipes; pipes are named `P1`, `P2`, ... without it
+    pub name: Option<String>,
+    /// Column with the nominal diameter in the diameter unit of `units`
+    pub diameter: String,
+    /// Column with the material that selects the roughness
+    pub material: Option<String>,
+    /// Roughness by material in the roughness unit of `units`
+    pub roughness: HashMap<String, f64>,
+    /// Roughness of pipes without a known material
+    pub default_roughness: f64,
+    /// Column with the year the pipe was built that selects the transmittance
+    pub year: Option<String>,
+    /// Transmittance of the pipes built up to a year, e.g. `{"1980": 3, "2000": 2}`
+    pub transmittance: BTreeMap<i32, f64>,
+    /// Transmittance of the pipes without a year or built after all years of `transmittance`
+    pub default_transmittance: f64,
+    pub zeta: f64,
+    /// Units of the diameters and roughnesses; coordinates are expected in meters
+    pub units: GeometryUnits,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            tolerance: 0.1,
+            name: None,
+            diameter: String::from("DN"),
+            material: Some(String::from("MATERIAL")),
+            roughness: HashMap::new(),
+            default_roughness: 0.1,
+            year: Some(String::from("YEAR")),
+            transmittance: BTreeMap::new(),
+            default_transmittance: 2.,
+            zeta: 0.,
+            units: GeometryUnits::nominal(),
+        }
+    }
+}
+
+impl Options {
+    pub fn load(path: &str) -> Result<Self, Error> {
+        let file =
+            fs::File::open(path).map_err(|err| anyhow!("could not open options file: {}", err))?;
+        from_reader(file).map_err(|err| anyhow!("could not decode options: {}", err))
+    }
+}
+
+fn length(line: &[[f64; 3]]) -> f64 {
+    line.windows(2)
+        .map(|pair| {
+            pair[0]
+                .iter()
+                .zip(&pair[1])
+                .map(|(a, b)| (a - b).powi(2))
+                .sum::<f64>()
+                .sqrt()
+        })
+        .sum()
+}
+
+/// Nodes at the line ends, each one joining all ends within the tolerance of its first end
+struct Snapping {
+    tolerance: f64,
+    positions: Vec<Position>,
+    /// Number of line ends joined in each node
+    degrees: Vec<usize>,
+    cells: CellIndex,
+}
+
+impl Snapping {
+    fn new(tolerance: f64) -> Self {
+        Self {
+            tolerance,
+            positions: vec![],
+            degrees: vec![],
+            cells: CellIndex::new(tolerance),
+        }
+    }
+
+    /// Finds the node closest to `point` or creates a new one, and returns its index
+    fn snap(&mut self, point: &[f64; 3]) -> usize {
+        let closest = self
+            .cells
+            .near(point[0], point[1])
+            .map(|i| {
+                let position = &self.positions[i];
+                (i, (position.x - point[0]).hypot(position.y - point[1]))
+            })
+            .filter(|(_, distance)| *distance <= self.tolerance)
+            // the first node wins ties, as in the order the nodes were created
+            .min_by(|(i, a), (j, b)| a.total_cmp(b).then(i.cmp(j)))
+            .map(|(i, _)| i);
+
+        let index = closest.unwrap_or_else(|| {
+            self.positions.push(Position {
+                x: point[0],
+                y: point[1],
+                z: point[2],
+            });
+            self.degrees.push(0);
+            self.cells
+                .insert(self.positions.len() - 1, point[0], point[1]);
+            self.positions.len() - 1
+        });
+        self.degrees[index] += 1;
+        index
+    }
+
+    /// Creates the nodes that join any line end, named `N1`, `N2`, ... in the order they were
+    /// snapped, and returns them with the names of the nodes by their index
+    ///
+    /// Nodes that only joined the ends of skipped pipes are left out.
+    fn nodes(&self) -> (Vec<Node>, Vec<Option<String>>) {
+        let mut nodes = vec![];
+        let names = self
+            .positions
+            .iter()
+            .zip(&self.degrees)
+            .map(|(position, degree)| {
+                (*degree > 0).then(|| {
+                    let name = format!("N{}", nodes.len() + 1);
+                    nodes.push(Node {
+                        name: name.clone(),
+                        position: position.clone(),
+                        feed: true,
+                    });
+                    name
+                })
+            })
+            .collect();
+        (nodes, names)
+    }
+}
+
+/// Returns `name`, or the first of `{name}_2`, `{name}_3`, ... that is not in `names` yet, and
+/// adds it to `names`
+fn unique_name(name: String, names: &mut HashSet<String>) -> String {
+    let name = if names.contains(&name) {
+        (2..)
+            .map(|n| format!("{}_{}", name, n))
+            .find(|suffixed| !names.contains(suffixed))
+            .expect("names are finite")
+    } else {
+        name
+    };
+    names.insert(name.clone());
+    name
+}
+
+/// Converts the lines and attribute records of a shapefile into the topology and pipe parameters
+/// of a network, and reports dangling pipe ends as well as skipped and defaulted data
+pub fn convert(
+    shapes: Vec<Option<shp::Polyline>>,
+    records: Vec<dbf::Record>,
+    options: &Options,
+) -> Result<(Topology, Parameters, Vec<String>), Error> {
+    if shapes.len() != records.len() {
+        return Err(anyhow!(
+            "shapefile has {} shapes, but attribute table has {} records",
+            shapes.len(),
+            records.len()
+        ));
+    }
+
+    let nominal_units = GeometryUnits::nominal();
+    let diameter_factor = options.units.diameter.in_meters() / nominal_units.diameter.in_meters();
+    let roughness_factor =
+        options.units.roughness.in_meters() / nominal_units.roughness.in_meters();
+
+    let mut report = vec![];
+    let mut snapping = Snapping::new(options.tolerance);
+    let mut pipes = vec![];
+    let mut names = HashSet::new();
+    let mut unknown_materials: BTreeMap<String, usize> = BTreeMap::new();
+
+    for (i, (shape, record)) in shapes.into_iter().zip(records).enumerate() {
+        if record.deleted {
+            continue;
+        }
+
+        let name = options
+            .name
+            .as_ref()
+            .and_then(|column| record.values.get(column))
+            .filter(|name| !name.is_empty())
+            .cloned()
+            .unwrap_or(format!("P{}", i + 1));
+        let Some(parts) = shape else {
+            report.push(format!("skipped pipe '{}' without geometry", name));
+            continue;
+        };
+
+        let value = |column: &str| -> Result<Option<f64>, Error> {
+            let Some(value) = record.values.get(column).filter(|value| !value.is_empty()) else {
+                return Ok(None);
+            };
+            value.parse().map(Some).map_err(|_| {
+                anyhow!(
+                    "'{}' in column '{}' of pipe '{}' is not a number",
+                    value,
+                    column,
+                    name
+                )
+            })
+        };
+
+        let diameter = value(&options.diameter)?.ok_or(anyhow!(
+            "pipe '{}' has no diameter in column '{}'",
+            name,
+            options.diameter
+        ))? * diameter_factor;
+
+        let material = options
+            .material
+            .as_ref()
+            .and_then(|column| record.values.get(column))
+            .filter(|material| !material.is_empty());
+        let roughness = match material.and_then(|material| options.roughness.get(material)) {
+            Some(roughness) => *roughness,
+            None => {
+                *unknown_materials
+                    .entry(material.cloned().unwrap_or_default())
+                    .or_default() += 1;
+                options.default_roughness
+            }
+        } * roughness_factor;
+
+        let year = match &options.year {
+            Some(column) => value(column)?,
+            None => None,
+        };
+        let transmittance = year
+            .and_then(|year| {
+                options
+                    .transmittance
+                    .range(year.ceil() as i32..)
+                    .next()
+                    .map(|(_, transmittance)| *transmittance)
+            })
+            .unwrap_or(options.default_transmittance);
+
+        let num_parts = parts.len();
+        for (k, line) in parts.into_iter().enumerate() {
+            let name = if num_parts > 1 {
+                format!("{}.part{}", name, k + 1)
+            } else {
+                name.clone()
+            };
+            let (Some(start), Some(end)) = (line.first(), line.last()) else {
+                report.push(format!("skipped pipe '{}' without points", name));
+                continue;
+            };
+
+            let src = snapping.snap(start);
+            let tgt = snapping.snap(end);
+            if src == tgt {
+                // the ends do not count as connections
+                snapping.degrees[src] -= 2;
+                report.push(format!(
+                    "skipped pipe '{}' whose ends are within the tolerance",
+                    name
+                ));
+                continue;
+            }
+
+            let unique = unique_name(name.clone(), &mut names);
+            if unique != name {
+                report.push(format!("renamed duplicate pipe '{}' to '{}'", name, unique));
+            }
+            pipes.push((
+                unique,
+                PipeParameters::Full {
+                    length: length(&line) / nominal_units.length.in_meters(),
+                    diameter,
+                    transmittance,
+                    roughness,
+                    zeta: options.zeta,
+                    friction_model: None,
+                },
+                [src, tgt],
+            ));
+        }
+    }
+
+    for (material, count) in unknown_materials {
+        report.push(if material.is_empty() {
+            format!("{} pipes without material got the default roughness", count)
+        } else {
+            format!(
+                "{} pipes of unknown material '{}' got the default roughness",
+                count, material
+            )
+        });
+    }
+
+    let (nodes, node_names) = snapping.nodes();
+    let node_name = |index: usize| node_names[index].clone().expect("node joins a pipe");
+
+    for (name, _, ends) in &pipes {
+        for index in ends {
+            if snapping.degrees[*index] == 1 {
+                report.push(format!(
+                    "pipe '{}' is dangling at node '{}'",
+                    name,
+                    node_name(*index)
+                ));
+            }
+        }
+    }
+
+    let parameters = Parameters::deduplicated(
+        pipes
+            .iter()
+            .map(|(name, parameters, _)| (name.clone(), parameters.clone())),
+    );
+
+    Ok((
+        Topology {
+            nodes,
+            pipes: pipes
+                .into_iter()
+                .map(|(name, _, [src, tgt])| Pipe {
+                    name,
+                    src: node_name(src),
+                    tgt: node_name(tgt),
+                })
+                .collect(),
+            consumers: vec![],
+            sources: vec![],
+        },
+        parameters,
+        report,
+    ))
+}
+
+/// Reads a shapefile and the attribute table with the same name and the extension `.dbf`
+pub fn load(path: &str, options: &Options) -> Result<(Topology, Parameters, Vec<String>), Error> {
+    let path = Path::new(path);
+    let shapes =
+        shp::parse(&fs::read(path).map_err(|err| anyhow!("could not open shapefile: {}", err))?)
+            .map_err(|err| anyhow!("could not read shapefile: {}", err))?;
+    let records = dbf::parse(
+        &fs::read(path.with_extension("dbf"))
+            .map_err(|err| anyhow!("could not open attribute table: {}", err))?,
+    )
+    .map_err(|err| anyhow!("could not read attribute table: {}", err))?;
+
+    convert(shapes, records, options)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn records(values: &[(&str, &str, &str, &str)]) -> Vec<dbf::Record> {
+        values
+            .iter()
+            .map(|(id, dn, material, year)| dbf::Record {
+                deleted: false,
+                values: [
+                    ("ID", id),
+                    ("DN", dn),
+                    ("MATERIAL", material),
+                    ("YEAR", year),
+                ]
+                .into_iter()
+                .map(|(column, value)| (column.to_string(), value.to_string()))
+                .collect(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn snap_line_ends_into_nodes() {
+        let shapes = vec![
+            Some(vec![vec![[0., 0., 0.], [30., 0., 0.], [30., 40., 0.]]]),
+            Some(vec![vec![[30.05, 40., 0.], [60., 40., 0.]]]),
+            Some(vec![vec![[60., 40., 0.], [60., 0., 0.]]]),
+            None,
+        ];
+        let options = Options {
+            name: Some(String::from("ID")),
+            roughness: HashMap::from([(String::from("steel"), 0.05)]),
+            transmittance: BTreeMap::from([(1990, 3.), (2010, 2.5)]),
+            ..Default::default()
+        };
+
+        let (topology, parameters, report) = convert(
+            shapes,
+            records(&[
+                ("A", "100", "steel", "1985"),
+                ("", "80", "steel", "2000"),
+                ("C", "65", "PE", ""),
+                ("D", "100", "steel", "1985"),
+            ]),
+            &options,
+        )
+        .expect("could not convert shapes");
+
+        let names: Vec<_> = topology.nodes.iter().map(|node| &node.name).collect();
+        assert_eq!(names, vec!["N1", "N2", "N3", "N4"]);
+        let connections: Vec<_> = topology
+            .pipes
+            .iter()
+            .map(|pipe| (pipe.name.as_str(), pipe.src.as_str(), pipe.tgt.as_str()))
+            .collect();
+        assert_eq!(
+            connections,
+            vec![("A", "N1", "N2"), ("P2", "N2", "N3"), ("C", "N3", "N4")]
+        );
+        assert_eq!(
+            parameters.parameters[&parameters.pipes["A"]],
+            PipeParameters::Full {
+                length: 70.,
+                diameter: 100.,
+                transmittance: 3.,
+                roughness: 0.05,
+                zeta: 0.,
+                friction_model: None,
+            }
+        );
+        let PipeParameters::Full {
+            transmittance,
+            roughness,
+            ..
+        } = parameters.parameters[&parameters.pipes["P2"]]
+        else {
+            panic!("pipe has full parameters");
+        };
+        assert_eq!((transmittance, roughness), (2.5, 0.05));
+        let PipeParameters::Full {
+            transmittance,
+            roughness,
+            ..
+        } = parameters.parameters[&parameters.pipes["C"]]
+        else {
+            panic!("pipe has full parameters");
+        };
+        assert_eq!((transmittance, roughness), (2., 0.1));
+
+        assert_eq!(
+            report,
+            vec![
+                "skipped pipe 'D' without geometry",
+                "1 pipes of unknown material 'PE' got the default roughness",
+                "pipe 'A' is dangling at node 'N1'",
+                "pipe 'C' is dangling at node 'N4'",
+            ]
+        );
+    }
+
+    #[test]
+    fn rename_duplicate_pipes() {
+        let shapes = vec![
+            Some(vec![vec![[0., 0., 0.], [10., 0., 0.]]]),
+            Some(vec![vec![[10., 0., 0.], [20., 0., 0.]]]),
+            Some(vec![
+                vec![[20., 0., 0.], [30., 0., 0.]],
+                vec![[30., 0., 0.], [40., 0., 0.]],
+            ]),
+            Some(vec![vec![[40., 0., 0.], [50., 0., 0.]]]),
+        ];
+        let options = Options {
+            name: Some(String::from("ID")),
+            ..Default::default()
+        };
+
+        let (topology, parameters, report) = convert(
+            shapes,
+            records(&[
+                ("A", "100", "steel", ""),
+                ("A", "80", "steel", ""),
+                ("A", "65", "steel", ""),
+                ("A_2", "50", "steel", ""),
+            ]),
+            &options,
+        )
+        .expect("could not convert shapes");
+
+        let names: Vec<_> = topology.pipes.iter().map(|pipe| &pipe.name).collect();
+        assert_eq!(names, vec!["A", "A_2", "A.part1", "A.part2", "A_2_2"]);
+        assert_eq!(parameters.pipes.len(), 5);
+        let PipeParameters::Full { diameter, .. } = parameters.parameters[&parameters.pipes["A_2"]]
+        else {
+            panic!("pipe has full parameters");
+        };
+        assert_eq!(diameter, 80.);
+        assert_eq!(
+            report[..2],
+            [
+                "renamed duplicate pipe 'A' to 'A_2'",
+                "renamed duplicate pipe 'A_2' to 'A_2_2'",
+            ]
+        );
+    }
+
+    #[test]
+    fn leave_out_nodes_of_skipped_pipes() {
+        let shapes = vec![
+            Some(vec![vec![[0., 0., 0.], [10., 0., 0.]]]),
+            Some(vec![
+                vec![[100., 0., 0.], [100.05, 0., 0.]],
+                vec![[10., 0., 0.], [20., 0., 0.]],
+            ]),
+        ];
+        let options = Options {
+            name: Some(String::from("ID")),
+            ..Default::default()
+        };
+
+        let (topology, _, report) = convert(
+            shapes,
+            records(&[("A", "100", "steel", ""), ("B", "80", "steel", "")]),
+            &options,
+        )
+        .expect("could not convert shapes");
+
+        let nodes: Vec<_> = topology
+            .nodes
+            .iter()
+            .map(|node| (node.name.as_str(), node.position.x))
+            .collect();
+        assert_eq!(nodes, vec![("N1", 0.), ("N2", 10.), ("N3", 20.)]);
+        let connections: Vec<_> = topology
+            .pipes
+            .iter()
+            .map(|pipe| (pipe.name.as_str(), pipe.src.as_str(), pipe.tgt.as_str()))
+            .collect();
+        assert_eq!(
+            connections,
+            vec![("A", "N1", "N2"), ("B.part2", "N2", "N3")]
+        );
+        assert_eq!(
+            report[0],
+            "skipped pipe 'B.part1' whose ends are within the tolerance"
+        );
+    }
+
+    #[test]
+    fn import_example() {
+        let options = Options::load("data/shapefile/options.json").expect("could not load options");
+        let (topology, parameters, report) =
+            load("data/shapefile/pipes.shp", &options).expect("could not load shapefile");
+
+        assert_eq!(topology.nodes.len(), 5);
+        assert_eq!(topology.pipes.len(), 5);
+        assert_eq!(
+            parameters.parameters[&parameters.pipes["P2"]],
+            PipeParameters::Full {
+                length: 100.,
+                diameter: 125.,
+                transmittance: 2.5,
+                roughness: 0.05,
+                zeta: 0.,
+                friction_model: None,
+            }
+        );
+        assert_eq!(report, vec!["pipe 'P5' is dangling at node 'N5'"]);
+    }
+
+    #[test]
+    fn report_missing_diameter() {
+        let result = convert(
+            vec![Some(vec![vec![[0., 0., 0.], [1., 0., 0.]]])],
+            records(&[("A", "", "steel", "2000")]),
+            &Default::default(),
+        );
+
+        assert_eq!(
+            result.expect_err("diameter is missing").to_string(),
+            "pipe 'P1' has no diameter in column 'DN'"
+        );
+    }
+}
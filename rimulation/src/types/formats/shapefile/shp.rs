@@ -0,0 +1,235 @@
+//! Reader of the geometry of polyline shapefiles (`.shp`)
+//!
+//! The file starts with a header of 100 bytes, followed by one record per shape. Record headers
+//! and the file header up to the file length are big endian, everything else is little endian.
+
+use anyhow::{anyhow, Error};
+
+const FILE_CODE: i32 = 9994;
+const HEADER_LENGTH: usize = 100;
+
+const NULL_SHAPE: i32 = 0;
+const POLYLINE: i32 = 3;
+const POLYLINE_Z: i32 = 13;
+const POLYLINE_M: i32 = 23;
+
+/// Parts of a polyline, each a sequence of points with x, y and z coordinates
+///
+/// Points of shapes without z coordinates have z = 0.
+pub type Polyline = Vec<Vec<[f64; 3]>>;
+
+fn slice(bytes: &[u8], offset: usize, length: usize) -> Result<&[u8], Error> {
+    bytes.get(offset..offset + length).ok_or(anyhow!(
+        "file ends at byte {}, but needs {} bytes from {}",
+        bytes.len(),
+        length,
+        offset
+    ))
+}
+
+fn big_endian_i32(bytes: &[u8], offset: usize) -> Result<i32, Error> {
+    Ok(i32::from_be_bytes(
+        slice(bytes, offset, 4)?
+            .try_into()
+            .expect("read four bytes"),
+    ))
+}
+
+fn little_endian_i32(bytes: &[u8], offset: usize) -> Result<i32, Error> {
+    Ok(i32::from_le_bytes(
+        slice(bytes, offset, 4)?
+            .try_into()
+            .expect("read four bytes"),
+    ))
+}
+
+fn little_endian_f64(bytes: &[u8], offset: usize) -> Result<f64, Error> {
+    Ok(f64::from_le_bytes(
+        slice(bytes, offset, 8)?
+            .try_into()
+            .expect("read eight bytes"),
+    ))
+}
+
+fn count(bytes: &[u8], offset: usize, what: &str) -> Result<usize, Error> {
+    let count = little_endian_i32(bytes, offset)?;
+    usize::try_from(count).map_err(|_| anyhow!("negative number of {}: {}", what, count))
+}
+
+/// Parses the polylines of a shapefile in the order of their records
+///
+/// Records with a null shape are `None`.
+pub fn parse(bytes: &[u8]) -> Result<Vec<Option<Polyline>>, Error> {
+    if big_endian_i32(bytes, 0)? != FILE_CODE {
+        return Err(anyhow!("not a shapefile"));
+    }
+
+    let shape_type = little_endian_i32(bytes, 32)?;
+    if ![NULL_SHAPE, POLYLINE, POLYLINE_Z, POLYLINE_M].contains(&shape_type) {
+        return Err(anyhow!(
+            "only polylines are supported, but the shapes have type {}",
+            shape_type
+        ));
+    }
+
+    let mut shapes = vec![];
+    let mut offset = HEADER_LENGTH;
+    while offset < bytes.len() {
+        let number = big_endian_i32(bytes, offset)?;
+        // lengths are given in 16 bit words
+        let length = 2 * usize::try_from(big_endian_i32(bytes, offset + 4)?)
+            .map_err(|_| anyhow!("record {} has a negative length", number))?;
+        let content = slice(bytes, offset + 8, length)?;
+
+        shapes.push(
+            parse_record(content)
+                .map_err(|err| anyhow!("could not read record {}: {}", number, err))?,
+        );
+        offset += 8 + length;
+    }
+
+    Ok(shapes)
+}
+
+fn parse_record(content: &[u8]) -> Result<Option<Polyline>, Error> {
+    let shape_type = little_endian_i32(content, 0)?;
+    if shape_type == NULL_SHAPE {
+        return Ok(None);
+    }
+    if ![POLYLINE, POLYLINE_Z, POLYLINE_M].contains(&shape_type) {
+        return Err(anyhow!("shape type {} is not a polyline", shape_type));
+    }
+
+    // the bounding box takes the 32 bytes after the shape type
+    let num_parts = count(content, 36, "parts")?;
+    let num_points = count(content, 40, "points")?;
+    let parts_offset = 44;
+    let points_offset = parts_offset + 4 * num_parts;
+    // z values follow the points and their range of 16 bytes
+    let z_offset = points_offset + 16 * num_points + 16;
+
+    // the count is read from the file, every point takes at least 16 bytes of the record
+    let mut points =
+        Vec::with_capacity(num_points.min(content.len().saturating_sub(points_offset) / 16));
+    for i in 0..num_points {
+        let z = if shape_type == POLYLINE_Z {
+            little_endian_f64(content, z_offset + 8 * i)?
+        } else {
+            0.
+        };
+        points.push([
+            little_endian_f64(content, points_offset + 16 * i)?,
+            little_endian_f64(content, points_offset + 16 * i + 8)?,
+            z,
+        ]);
+    }
+
+    let starts = (0..num_parts)
+        .map(|i| count(content, parts_offset + 4 * i, "points"))
+        .collect::<Result<Vec<_>, Error>>()?;
+    starts
+        .iter()
+        .zip(starts.iter().skip(1).chain([&num_points]))
+        .map(|(&start, &end)| {
+            points
+                .get(start..end)
+                .map(<[[f64; 3]]>::to_vec)
+                .ok_or(anyhow!(
+                    "part from point {} to {} does not exist",
+                    start,
+                    end
+                ))
+        })
+        .collect::<Result<Vec<_>, Error>>()
+        .map(Some)
+}
+
+#[cfg(test)]
+pub mod test_util {
+    /// Writes polylines with z coordinates, or null shapes for `None`, into a shapefile
+    pub fn write(shapes: &[Option<Vec<Vec<[f64; 3]>>>]) -> Vec<u8> {
+        let mut records = vec![];
+        for (i, shape) in shapes.iter().enumerate() {
+            let mut content = vec![];
+            match shape {
+                None => content.extend(0i32.to_le_bytes()),
+                Some(parts) => {
+                    let points: Vec<&[f64; 3]> = parts.iter().flatten().collect();
+                    content.extend(13i32.to_le_bytes());
+                    content.extend([0u8; 32]);
+                    content.extend((parts.len() as i32).to_le_bytes());
+                    content.extend((points.len() as i32).to_le_bytes());
+                    let mut start = 0;
+                    for part in parts {
+                        content.extend((start as i32).to_le_bytes());
+                        start += part.len();
+                    }
+                    for point in &points {
+                        content.extend(point[0].to_le_bytes());
+                        content.extend(point[1].to_le_bytes());
+                    }
+                    content.extend([0u8; 16]);
+                    for point in &points {
+                        content.extend(point[2].to_le_bytes());
+                    }
+                }
+            }
+
+            records.extend((i as i32 + 1).to_be_bytes());
+            records.extend((content.len() as i32 / 2).to_be_bytes());
+            records.extend(content);
+        }
+
+        let mut bytes = vec![0u8; 100];
+        bytes[0..4].copy_from_slice(&9994i32.to_be_bytes());
+        bytes[24..28].copy_from_slice(&((100 + records.len()) as i32 / 2).to_be_bytes());
+        bytes[28..32].copy_from_slice(&1000i32.to_le_bytes());
+        bytes[32..36].copy_from_slice(&13i32.to_le_bytes());
+        bytes.extend(records);
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_polylines() {
+        let shapes = vec![
+            Some(vec![vec![[0., 0., 1.], [3., 4., 2.]]]),
+            None,
+            Some(vec![
+                vec![[0., 0., 0.], [1., 0., 0.]],
+                vec![[5., 5., 0.], [6., 6., 0.]],
+            ]),
+        ];
+
+        assert_eq!(parse(&test_util::write(&shapes)).unwrap(), shapes);
+    }
+
+    #[test]
+    fn report_truncated_record() {
+        let bytes = test_util::write(&[Some(vec![vec![[0., 0., 0.], [1., 1., 0.]]])]);
+
+        assert_eq!(
+            parse(&bytes[..bytes.len() - 8])
+                .expect_err("record is truncated")
+                .to_string(),
+            format!(
+                "file ends at byte {}, but needs 112 bytes from 108",
+                bytes.len() - 8
+            )
+        );
+    }
+
+    #[test]
+    fn report_excessive_number_of_points() {
+        let bytes = test_util::write(&[Some(vec![vec![[0., 0., 0.], [1., 1., 0.]]])]);
+        let mut record = bytes[108..].to_vec();
+        // the record claims far more points than it holds
+        record[40..44].copy_from_slice(&i32::MAX.to_le_bytes());
+
+        assert!(parse_record(&record).is_err());
+    }
+}
@@ -5,12 +5,16 @@ use super::formats::{
     custom::{self, Input, PipeParameters, Position},
     NamedComponent,
 };
-use super::signal::Signal;
+use super::signal::{self, Signal};
+use crate::{fluid::FluidProperties, friction::Friction, scalar::Scalar, water::Water};
 
 use anyhow::{anyhow, Error};
-use std::collections::{HashMap, HashSet, VecDeque};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    f64::consts::PI,
+};
 
-const HOURS_PER_YEAR: f64 = 8760.;
+pub const HOURS_PER_YEAR: f64 = 8760.;
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum Node {
@@ -51,27 +55,57 @@ impl NamedComponent for Node {
     }
 }
 
-pub trait HydraulicPipeParameters {
-    fn length(&self) -> f64;
-    fn diameter(&self) -> f64;
-    fn transmittance(&self) -> f64;
-    fn roughness(&self) -> f64;
-    fn zeta(&self) -> f64;
+/// Parameters of pipes for hydraulic calculations
+///
+/// Lengths, diameters and roughnesses are in \[m\].
+/// The geometry is generic over the [`Scalar`] type to compute sensitivities with respect to it.
+pub trait HydraulicPipeParameters<T: Scalar = f64> {
+    fn length(&self) -> T;
+    fn diameter(&self) -> T;
+    fn transmittance(&self) -> T;
+    fn roughness(&self) -> T;
+    fn zeta(&self) -> T;
+    fn friction_model(&self) -> Friction;
+
+    /// Area of the cross-section of the pipe \[m^2\]
+    fn cross_section(&self) -> T {
+        self.diameter() * self.diameter() * (PI / 4.)
+    }
+
+    /// Roughness of the pipe wall relative to the diameter
+    fn relative_roughness(&self) -> T {
+        self.roughness() / self.diameter()
+    }
+
+    /// Reynolds number of the flow with velocity v \[m/s\] and kinematic viscosity nu \[m^2/s\]
+    fn reynolds(&self, v: T, nu: T) -> T {
+        v.abs() * self.diameter() / nu
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct EmptyPipeParameters {}
 
+/// Drops the parameters, e.g. to inspect the structure of a network only
+impl TryFrom<PipeParameters> for EmptyPipeParameters {
+    type Error = Error;
+
+    fn try_from(_: PipeParameters) -> Result<Self, Self::Error> {
+        Ok(Self {})
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
-pub struct FullPipeParameters {
-    pub length: f64,
-    pub diameter: f64,
-    pub transmittance: f64,
-    pub roughness: f64,
-    pub zeta: f64,
+pub struct FullPipeParameters<T = f64> {
+    pub length: T,   // in m
+    pub diameter: T, // in m
+    pub transmittance: T,
+    pub roughness: T, // in m
+    pub zeta: T,
+    pub friction_model: Friction,
 }
 
-impl TryFrom<PipeParameters> for FullPipeParameters {
+impl<T: Scalar> TryFrom<PipeParameters> for FullPipeParameters<T> {
     type Error = Error;
 
     fn try_from(value: PipeParameters) -> Result<Self, Self::Error> {
@@ -82,26 +116,29 @@ impl TryFrom<PipeParameters> for FullPipeParameters {
                 transmittance,
                 roughness,
                 zeta,
+                friction_model,
             } => Ok(Self {
-                length,
-                diameter,
-                transmittance,
-                roughness,
-                zeta,
+                length: T::from_f64(length),
+                diameter: T::from_f64(diameter),
+                transmittance: T::from_f64(transmittance),
+                roughness: T::from_f64(roughness),
+                zeta: T::from_f64(zeta),
+                friction_model: friction_model.unwrap_or_default(),
             }),
             _ => Err(anyhow!("wrong enum type: {:?} expected Full", value)),
         }
     }
 }
 
-impl TryFrom<PipeParameters> for FixedVelocityPipeParameters {
+impl<T: Scalar> TryFrom<PipeParameters> for FixedVelocityPipeParameters<T> {
     type Error = Error;
 
     fn try_from(value: PipeParameters) -> Result<Self, Self::Error> {
         match value {
-            PipeParameters::FixedVelocity { length, velocity } => {
-                Ok(FixedVelocityPipeParameters { length, velocity })
-            }
+            PipeParameters::FixedVelocity { length, velocity } => Ok(FixedVelocityPipeParameters {
+                length: T::from_f64(length),
+                velocity: T::from_f64(velocity),
+            }),
             _ => Err(anyhow!(
                 "wrong enum type: {:?} expected FixedVelocity",
                 value
@@ -110,32 +147,36 @@ impl TryFrom<PipeParameters> for FixedVelocityPipeParameters {
     }
 }
 
-impl HydraulicPipeParameters for FullPipeParameters {
-    fn length(&self) -> f64 {
+impl<T: Scalar> HydraulicPipeParameters<T> for FullPipeParameters<T> {
+    fn length(&self) -> T {
         self.length
     }
 
-    fn diameter(&self) -> f64 {
+    fn diameter(&self) -> T {
         self.diameter
     }
 
-    fn transmittance(&self) -> f64 {
+    fn transmittance(&self) -> T {
         self.transmittance
     }
 
-    fn roughness(&self) -> f64 {
+    fn roughness(&self) -> T {
         self.roughness
     }
 
-    fn zeta(&self) -> f64 {
+    fn zeta(&self) -> T {
         self.zeta
     }
+
+    fn friction_model(&self) -> Friction {
+        self.friction_model
+    }
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub struct FixedVelocityPipeParameters {
-    pub length: f64,   // in m
-    pub velocity: f64, // in m/s
+pub struct FixedVelocityPipeParameters<T = f64> {
+    pub length: T,   // in m
+    pub velocity: T, // in m/s
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -157,7 +198,7 @@ impl Edge {
 }
 
 #[derive(Debug, PartialEq, Clone)]
-pub struct Network<T> {
+pub struct Network<T, F = Water> {
     pub demand_nodes: Vec<Node>,
     pub pressure_nodes: Vec<Node>,
     pub root_node_index: usize,
@@ -168,9 +209,13 @@ pub struct Network<T> {
     pub adjacent_edges: HashMap<usize, Vec<usize>>,
     // Future: pressure_edges
     pub edge_parameters: Vec<T>,
+    pub fluid: F,
 }
 
 impl<EdgeParameters> Network<EdgeParameters> {
+    /// Creates a network of water from the nodes and edges of its feed
+    ///
+    /// Use [`Network::with_fluid`] to simulate other fluids.
     pub fn try_from_feed(
         nodes: Vec<Node>,
         edges: Vec<Edge>,
@@ -225,8 +270,30 @@ impl<EdgeParameters> Network<EdgeParameters> {
             edge_indices_by_connected_nodes,
             adjacent_edges,
             edge_parameters,
+            fluid: Water,
         })
     }
+}
+
+impl<EdgeParameters, Fluid> Network<EdgeParameters, Fluid> {
+    /// Replaces the fluid flowing through the network
+    pub fn with_fluid<OtherFluid>(self, fluid: OtherFluid) -> Network<EdgeParameters, OtherFluid>
+    where
+        OtherFluid: FluidProperties,
+    {
+        Network {
+            demand_nodes: self.demand_nodes,
+            pressure_nodes: self.pressure_nodes,
+            root_node_index: self.root_node_index,
+            spanning_tree_edges: self.spanning_tree_edges,
+            cycle_edges: self.cycle_edges,
+            pred_nodes: self.pred_nodes,
+            edge_indices_by_connected_nodes: self.edge_indices_by_connected_nodes,
+            adjacent_edges: self.adjacent_edges,
+            edge_parameters: self.edge_parameters,
+            fluid,
+        }
+    }
 
     pub fn nodes(&self) -> impl Iterator<Item = &Node> {
         self.demand_nodes.iter().chain(self.pressure_nodes.iter())
@@ -291,9 +358,10 @@ impl<EdgeParameters> Network<EdgeParameters> {
     }
 }
 
-impl<EdgeParameters> TryFrom<custom::Network> for Network<EdgeParameters>
+impl<EdgeParameters, Fluid> TryFrom<custom::Network> for Network<EdgeParameters, Fluid>
 where
     EdgeParameters: TryFrom<PipeParameters, Error = Error> + Clone,
+    Fluid: FluidProperties + Default,
 {
     type Error = Error;
 
@@ -314,7 +382,7 @@ where
         }
 
         let (nodes, edges, edge_parameters) = extract_feed(nodes, edges, edge_parameters)?;
-        Network::try_from_feed(nodes, edges, edge_parameters)
+        Ok(Network::try_from_feed(nodes, edges, edge_parameters)?.with_fluid(Fluid::default()))
     }
 }
 
@@ -444,14 +512,7 @@ fn extract_nodes(value: &custom::Network) -> Result<Vec<Node>, Error> {
         node_mapping(&value.topology.consumers, |consumer| consumer.src.clone());
     let sources_by_node = node_mapping(&value.topology.sources, |source| source.tgt.clone());
 
-    let get_signal = |name: &String| -> Result<custom::Signal, Error> {
-        Ok(value
-            .scenario
-            .signals
-            .get(name)
-            .ok_or(anyhow!("signal with name '{}' does not exist", name))?
-            .clone())
-    };
+    let get_signal = |name: &String| signal::resolve(name, &value.scenario.signals);
 
     let create_consumer_node = |consumer_name: &String,
                                 node: &custom::Node|
@@ -486,8 +547,7 @@ fn extract_nodes(value: &custom::Network) -> Result<Vec<Node>, Error> {
 
         // TODO: why scaled by hours per year, not seconds?
         let demand = get_signal(demand_signal_name)?
-            .scale_data(consumer_input.factors.yearly_demand / HOURS_PER_YEAR)
-            .try_into()?;
+            .scale(consumer_input.factors.yearly_demand / HOURS_PER_YEAR);
         Ok(Node::Demand {
             name: node.name.clone(),
             demand,
@@ -517,8 +577,8 @@ fn extract_nodes(value: &custom::Network) -> Result<Vec<Node>, Error> {
                 )),
             }?;
 
-        let pressure = get_signal(pressure_signal_name)?.try_into()?;
-        let temperature = get_signal(temperature_signal_name)?.try_into()?;
+        let pressure = get_signal(pressure_signal_name)?;
+        let temperature = get_signal(temperature_signal_name)?;
 
         Ok(Node::Pressure {
             name: node.name.clone(),
@@ -578,7 +638,11 @@ where
             .parameters
             .get(parameters_name)
             .ok_or(anyhow!("could not get parameters with the name {}", name))?;
-        parsed.clone().try_into()
+        parsed
+            .clone()
+            .or_friction_model(value.parameters.friction_model)
+            .in_meters(&value.parameters.units)
+            .try_into()
     };
 
     value
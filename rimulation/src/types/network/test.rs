@@ -5,6 +5,12 @@ use custom::test_util::DUMMY_CUSTOM_POSITION;
 use super::*;
 
 use super::super::formats::custom::test_util::{DUMMY_CONST_CUSTOM_SIGNAL, DUMMY_CONSUMER_FACTORS};
+use crate::{
+    fluid::FluidProperties,
+    friction::{self, FrictionModel},
+    scalar::Scalar,
+    water::Water,
+};
 
 pub const DUMMY_CONST_SIGNAL: Signal = Signal::Const { value: 1. };
 
@@ -16,6 +22,7 @@ pub const DUMMY_PARSED_PIPE_PARAMETERS: PipeParameters = PipeParameters::Full {
     transmittance: 3.,
     roughness: 4.,
     zeta: 5.,
+    friction_model: None,
 };
 
 // TODO: move to some utils module
@@ -74,10 +81,9 @@ fn from_custom_network() {
         .try_into()
         .expect("could not convert custom network into internal network type");
 
-    let scaled_dummy_const_signal: Signal = DUMMY_CONST_CUSTOM_SIGNAL
-        .scale_data(DUMMY_CONSUMER_FACTORS.yearly_demand / HOURS_PER_YEAR)
-        .try_into()
-        .expect("could not convert custom signal");
+    let scaled_dummy_const_signal = Signal::try_from(DUMMY_CONST_CUSTOM_SIGNAL)
+        .expect("could not convert custom signal")
+        .scale(DUMMY_CONSUMER_FACTORS.yearly_demand / HOURS_PER_YEAR);
 
     assert_eq!(
         network.nodes().cloned().collect::<Vec<_>>(),
@@ -145,10 +151,9 @@ fn extract_nodes_of_custom_net() {
     let nodes = extract_nodes(&custom_net).expect("could not extract nodes from custom net");
     assert_eq!(nodes.len(), 10);
 
-    let scaled_dummy_const_signal: Signal = DUMMY_CONST_CUSTOM_SIGNAL
-        .scale_data(DUMMY_CONSUMER_FACTORS.yearly_demand / HOURS_PER_YEAR)
-        .try_into()
-        .expect("could not convert suctom signal");
+    let scaled_dummy_const_signal = Signal::try_from(DUMMY_CONST_CUSTOM_SIGNAL)
+        .expect("could not convert custom signal")
+        .scale(DUMMY_CONSUMER_FACTORS.yearly_demand / HOURS_PER_YEAR);
     assert_eq!(
         nodes.into_iter().take(5).collect::<Vec<_>>(),
         vec![
@@ -405,6 +410,7 @@ fn from_feed() {
                 .collect(),
             adjacent_edges: expected_adjacent_edges,
             edge_parameters,
+            fluid: Water,
         }
     );
 }
@@ -578,3 +584,121 @@ fn reordering_demand_nodes() {
             .collect::<Vec<_>>()
     );
 }
+
+#[derive(Debug, Default, PartialEq, Clone)]
+struct ConstantFluid;
+
+impl FluidProperties for ConstantFluid {
+    fn density<T: Scalar>(&self, _: T) -> T {
+        T::from_f64(1000.)
+    }
+
+    fn specific_heat<T: Scalar>(&self, _: T) -> T {
+        T::from_f64(4000.)
+    }
+
+    fn viscosity<T: Scalar>(&self, _: T) -> T {
+        T::from_f64(1e-6)
+    }
+
+    fn thermal_conductivity<T: Scalar>(&self, _: T) -> T {
+        T::from_f64(0.6)
+    }
+
+    fn energy_density<T: Scalar>(&self, t: T) -> Result<T, Error> {
+        Ok(t * 4e-3)
+    }
+
+    fn temperature<T: Scalar>(&self, e: T) -> T {
+        e / 4e-3
+    }
+}
+
+#[test]
+fn from_custom_network_with_other_fluid() {
+    let custom_net = custom::test_util::create_test_net(4, 2, &[(0, 1)], &[1], &[0]);
+
+    let network: Network<FullPipeParameters, ConstantFluid> = custom_net
+        .try_into()
+        .expect("could not convert custom network into internal network type");
+
+    assert_eq!(network.fluid, ConstantFluid);
+    assert_eq!(network.num_edges(), 1);
+
+    let network = network.with_fluid(Water);
+    assert_eq!(network.fluid, Water);
+    assert_eq!(network.num_nodes(), 2);
+}
+
+#[test]
+fn friction_model_per_network_and_parameter_set() {
+    let mut custom_net = custom::test_util::create_test_net(6, 3, &[(0, 1), (1, 2)], &[1, 2], &[0]);
+
+    custom_net.parameters.friction_model = Friction::Colebrook;
+    custom_net.parameters.parameters.insert(
+        String::from("haaland_pipe_parameters"),
+        DUMMY_PARSED_PIPE_PARAMETERS.or_friction_model(Friction::Haaland),
+    );
+    let haaland_pipe = custom_net.topology.pipes[1].name.clone();
+    custom_net
+        .parameters
+        .pipes
+        .insert(haaland_pipe, String::from("haaland_pipe_parameters"));
+
+    let network: Network<FullPipeParameters> = custom_net
+        .try_into()
+        .expect("could not convert custom network into internal network type");
+
+    let mut friction_models: Vec<_> = network
+        .edge_parameters()
+        .map(|parameters| parameters.friction_model)
+        .collect();
+    friction_models.sort_by_key(|friction_model| format!("{:?}", friction_model));
+    assert_eq!(friction_models, [Friction::Colebrook, Friction::Haaland]);
+}
+
+#[test]
+fn reynolds_number_uses_diameter() {
+    let pipe = |length| FullPipeParameters {
+        length,
+        diameter: 0.2,
+        transmittance: 1.,
+        roughness: 1e-4,
+        zeta: 1.,
+        friction_model: Friction::default(),
+    };
+
+    let e = Water
+        .energy_density(60.)
+        .expect("could not compute energy density");
+    let re = pipe(100.).reynolds(-1.5, Water.viscosity(e));
+
+    assert_eq!(re, 1.5 * 0.2 / Water.viscosity(e));
+    assert_eq!(re, pipe(1.).reynolds(1.5, Water.viscosity(e)));
+    // water at 60 °C has a viscosity of about 0.47 mm^2/s
+    assert!((6e5..7e5).contains(&re), "Re = {}", re);
+}
+
+#[test]
+fn pipe_geometry_in_meters() {
+    let network: Network<FullPipeParameters> = custom::load("data/running_example")
+        .expect("could not load network")
+        .try_into()
+        .expect("could not convert network");
+
+    for pipe in network.edge_parameters() {
+        assert_eq!(pipe.length(), 44.7);
+        assert_eq!(pipe.diameter(), 0.025);
+        assert_eq!(pipe.roughness(), 5e-5);
+        assert_eq!(pipe.relative_roughness(), 2e-3);
+        assert_eq!(
+            pipe.cross_section(),
+            std::f64::consts::PI * 0.025 * 0.025 / 4.
+        );
+
+        assert_eq!(
+            friction::darcy_friction(&pipe.friction_model(), 1e5, pipe.relative_roughness()),
+            Friction::Serghides.turbulent(1e5, 2e-3)
+        );
+    }
+}
@@ -0,0 +1,112 @@
+use super::Signal;
+
+use anyhow::{anyhow, Error};
+use nalgebra::DVector;
+
+/// Finds the grid segment of every point
+///
+/// Sorted points are assigned by walking the segments from left to right,
+/// unsorted points fall back to computing the index of each point.
+/// The right boundary belongs to the last segment.
+fn segments(h: f64, a: f64, b: f64, xs: &[f64], num_segments: usize) -> Result<Vec<usize>, Error> {
+    if let Some(x) = xs.iter().find(|x| **x < a || **x > b) {
+        return Err(anyhow!("{} out of bounds ([{}, {}])", x, a, b));
+    }
+
+    if !xs.is_sorted() {
+        return Ok(xs
+            .iter()
+            .map(|x| (((x - a) / h).floor() as usize).min(num_segments - 1))
+            .collect());
+    }
+
+    let mut i = 0;
+    let mut right = a + h;
+
+    Ok(xs
+        .iter()
+        .map(|x| {
+            while *x >= right && i + 1 < num_segments {
+                i += 1;
+                right = a + (i + 1) as f64 * h;
+            }
+            i
+        })
+        .collect())
+}
+
+impl Signal {
+    /// Evaluates the signal at all points
+    ///
+    /// Equivalent to calling [`Signal::value_at`] for every point,
+    /// but the segments of interpolated signals are looked up once for all points.
+    /// This is fastest for sorted points.
+    pub fn values_at(&self, xs: &[f64]) -> Result<DVector<f64>, Error> {
+        let n = xs.len();
+
+        Ok(match self {
+            Signal::Const { value } => DVector::from_element(n, *value),
+            Signal::Linear { h, a, b, y, dy } => {
+                let segments = segments(*h, *a, *b, xs, y.len())?;
+
+                DVector::from_iterator(n, xs.iter().zip(segments).map(|(x, i)| y[i] + x * dy[i]))
+            }
+            Signal::Cubic { h, a, b, y, m } => {
+                let segments = segments(*h, *a, *b, xs, y.len() - 1)?;
+
+                DVector::from_iterator(
+                    n,
+                    xs.iter().zip(segments).map(|(x, i)| {
+                        let dx_l = x - (a + i as f64 * h);
+                        let dx_r = (a + (i + 1) as f64 * h) - x;
+
+                        (m[i] * dx_r * dx_r * dx_r
+                            + m[i + 1] * dx_l * dx_l * dx_l
+                            + (6. * y[i] - m[i] * h * h) * dx_r
+                            + (6. * y[i + 1] - m[i + 1] * h * h) * dx_l)
+                            / (6. * h)
+                    }),
+                )
+            }
+            Signal::Step { low, high, time } => {
+                DVector::from_iterator(n, xs.iter().map(|x| if x < time { *low } else { *high }))
+            }
+            Signal::Sum { signals } => {
+                let mut values = DVector::zeros(n);
+                for signal in signals {
+                    values += signal.values_at(xs)?;
+                }
+                values
+            }
+            Signal::Product { signals } => {
+                let mut values = DVector::from_element(n, 1.);
+                for signal in signals {
+                    values.component_mul_assign(&signal.values_at(xs)?);
+                }
+                values
+            }
+            Signal::Shift { signal, time } => {
+                signal.values_at(&xs.iter().map(|x| x - time).collect::<Vec<_>>())?
+            }
+            Signal::Scale { signal, factor } => signal.values_at(xs)? * *factor,
+            Signal::Offset { signal, value } => signal.values_at(xs)?.add_scalar(*value),
+            Signal::Clamp { signal, min, max } => signal.values_at(xs)?.map(|mut y| {
+                if let Some(min) = min {
+                    y = y.max(*min);
+                }
+                if let Some(max) = max {
+                    y = y.min(*max);
+                }
+                y
+            }),
+            Signal::Invalid { signal, .. } => signal.values_at(xs)?,
+        })
+    }
+
+    /// Evaluates the signal at `n` equidistant points `start + i * step`
+    pub fn values_on_grid(&self, start: f64, step: f64, n: usize) -> Result<DVector<f64>, Error> {
+        let xs: Vec<f64> = (0..n).map(|i| start + i as f64 * step).collect();
+
+        self.values_at(&xs)
+    }
+}
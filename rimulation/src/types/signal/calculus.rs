@@ -0,0 +1,315 @@
+use super::{get_index, Signal};
+use crate::{
+    polynome::{chebyshev_interpolation, roots},
+    scalar::Scalar,
+};
+
+use anyhow::{anyhow, Error};
+use std::cell::RefCell;
+
+/// Nodes and weights of the 5-point Gauss-Legendre rule on [-1, 1]
+///
+/// Exact for polynomials up to degree 9, i.e. products of up to three cubic signals.
+const GAUSS_LEGENDRE: [(f64, f64); 5] = [
+    (0., 0.568_888_888_888_888_9),
+    (-0.538_469_310_105_683_1, 0.478_628_670_499_366_5),
+    (0.538_469_310_105_683_1, 0.478_628_670_499_366_5),
+    (-0.906_179_845_938_664, 0.236_926_885_056_189_1),
+    (0.906_179_845_938_664, 0.236_926_885_056_189_1),
+];
+
+/// Degree of the polynomial pieces of a clamped signal whose crossings with the bounds are found
+///
+/// Exact for products of up to three cubic signals, like [`GAUSS_LEGENDRE`].
+const CLAMP_DEGREE: usize = 9;
+
+/// Index of the grid segment containing `x`, the right boundary belongs to the last segment
+fn segment(h: &f64, a: &f64, b: &f64, x: &f64, num_segments: usize) -> Result<usize, Error> {
+    Ok(get_index(h, a, b, x)?.min(num_segments - 1))
+}
+
+/// Antiderivative of the linear piece `y + x dy`
+fn linear_antiderivative(y: f64, dy: f64, x: f64) -> f64 {
+    y * x + dy * x * x / 2.
+}
+
+/// Antiderivative of the cubic piece between the grid points `i` and `i + 1`
+fn cubic_antiderivative(h: f64, a: f64, y: &[f64], m: &[f64], i: usize, x: f64) -> f64 {
+    let dx_l = x - (a + i as f64 * h);
+    let dx_r = (a + (i + 1) as f64 * h) - x;
+
+    (-m[i] * dx_r.powi(4) / 4. + m[i + 1] * dx_l.powi(4) / 4.
+        - (6. * y[i] - m[i] * h * h) * dx_r * dx_r / 2.
+        + (6. * y[i + 1] - m[i + 1] * h * h) * dx_l * dx_l / 2.)
+        / (6. * h)
+}
+
+/// Converts the coefficients of a Chebyshev series into the coefficients of the same polynomial
+/// in ascending order of degree
+fn chebyshev_to_monomial(coefficients: &[f64]) -> Vec<f64> {
+    let mut monomial = vec![0.; coefficients.len()];
+    // T_k(u) in ascending order of degree, from T_{k + 1} = 2 u T_k - T_{k - 1}
+    let mut previous = vec![0.; coefficients.len()];
+    let mut current = vec![0.; coefficients.len()];
+    current[0] = 1.;
+
+    for (k, c) in coefficients.iter().enumerate() {
+        for (m, t) in monomial.iter_mut().zip(&current) {
+            *m += c * t;
+        }
+
+        let mut next: Vec<f64> = previous.iter().map(|t| -t).collect();
+        for i in 0..current.len() - 1 {
+            next[i + 1] += if k == 0 { 1. } else { 2. } * current[i];
+        }
+        previous = std::mem::replace(&mut current, next);
+    }
+
+    monomial
+}
+
+/// Sums the integrals of the pieces of a piecewise defined signal between `l` and `r`
+fn integrate_pieces(
+    h: f64,
+    a: f64,
+    l: f64,
+    r: f64,
+    first: usize,
+    last: usize,
+    antiderivative: impl Fn(usize, f64) -> f64,
+) -> f64 {
+    (first..=last)
+        .map(|i| {
+            let start = if i == first { l } else { a + i as f64 * h };
+            let end = if i == last { r } else { a + (i + 1) as f64 * h };
+
+            antiderivative(i, end) - antiderivative(i, start)
+        })
+        .sum()
+}
+
+impl Signal {
+    /// Computes the derivative of the signal at `x`
+    ///
+    /// Returns an error at the jump of a step signal, where the derivative does not exist.
+    pub fn derivative_at(&self, x: f64) -> Result<f64, Error> {
+        Ok(match self {
+            Signal::Const { .. } => 0.,
+            Signal::Linear { h, a, b, dy, .. } => dy[segment(h, a, b, &x, dy.len())?],
+            Signal::Cubic { h, a, b, y, m } => {
+                let i = segment(h, a, b, &x, y.len() - 1)?;
+
+                let dx_l = x - (a + i as f64 * h);
+                let dx_r = (a + (i + 1) as f64 * h) - x;
+
+                (-3. * m[i] * dx_r * dx_r + 3. * m[i + 1] * dx_l * dx_l
+                    - (6. * y[i] - m[i] * h * h)
+                    + (6. * y[i + 1] - m[i + 1] * h * h))
+                    / (6. * h)
+            }
+            Signal::Step { time, .. } => {
+                if x == *time {
+                    return Err(anyhow!("step signal has a jump at {}", time));
+                }
+                0.
+            }
+            Signal::Sum { signals } => signals
+                .iter()
+                .map(|signal| signal.derivative_at(x))
+                .sum::<Result<f64, Error>>()?,
+            Signal::Product { signals } => {
+                let values = signals
+                    .iter()
+                    .map(|signal| signal.value_at(x))
+                    .collect::<Result<Vec<_>, Error>>()?;
+
+                // product rule
+                let mut derivative = 0.;
+                for (i, signal) in signals.iter().enumerate() {
+                    let others: f64 = values
+                        .iter()
+                        .enumerate()
+                        .filter_map(|(j, value)| (i != j).then_some(value))
+                        .product();
+                    derivative += signal.derivative_at(x)? * others;
+                }
+                derivative
+            }
+            Signal::Shift { signal, time } => signal.derivative_at(x - time)?,
+            Signal::Scale { signal, factor } => factor * signal.derivative_at(x)?,
+            Signal::Offset { signal, .. } | Signal::Invalid { signal, .. } => {
+                signal.derivative_at(x)?
+            }
+            Signal::Clamp { signal, min, max } => {
+                let y = signal.value_at(x)?;
+                if min.is_some_and(|min| y < min) || max.is_some_and(|max| y > max) {
+                    0.
+                } else {
+                    signal.derivative_at(x)?
+                }
+            }
+        })
+    }
+
+    /// Evaluates the signal at a [`Scalar`] `x`, propagating its derivative by the chain rule
+    ///
+    /// The derivative of the signal is only computed if `x` carries one,
+    /// so constant arguments also work at the jump of a step signal.
+    pub fn value_at_scalar<T: Scalar>(&self, x: T) -> Result<T, Error> {
+        let value = self.value_at(x.value())?;
+
+        if x.is_constant() {
+            return Ok(x.apply(value, 0.));
+        }
+
+        Ok(x.apply(value, self.derivative_at(x.value())?))
+    }
+
+    /// Computes the integral of the signal from `l` to `r`
+    ///
+    /// The integrals of products are computed with Gauss-Legendre quadrature between the knots of
+    /// the signal, which is exact for products of up to three cubic signals.
+    /// Clamps are split where the signal crosses its bounds, the clamped parts are integrated as
+    /// constants and the rest as the signal, which is exact for the same signals.
+    pub fn integral(&self, l: f64, r: f64) -> Result<f64, Error> {
+        if l > r {
+            return Ok(-self.integral(r, l)?);
+        }
+
+        Ok(match self {
+            Signal::Const { value } => value * (r - l),
+            Signal::Linear { h, a, b, y, dy } => {
+                let first = segment(h, a, b, &l, dy.len())?;
+                let last = segment(h, a, b, &r, dy.len())?;
+
+                integrate_pieces(*h, *a, l, r, first, last, |i, x| {
+                    linear_antiderivative(y[i], dy[i], x)
+                })
+            }
+            Signal::Cubic { h, a, b, y, m } => {
+                let first = segment(h, a, b, &l, y.len() - 1)?;
+                let last = segment(h, a, b, &r, y.len() - 1)?;
+
+                integrate_pieces(*h, *a, l, r, first, last, |i, x| {
+                    cubic_antiderivative(*h, *a, y, m, i, x)
+                })
+            }
+            Signal::Step { low, high, time } => {
+                let split = time.clamp(l, r);
+                low * (split - l) + high * (r - split)
+            }
+            Signal::Sum { signals } => signals
+                .iter()
+                .map(|signal| signal.integral(l, r))
+                .sum::<Result<f64, Error>>()?,
+            Signal::Shift { signal, time } => signal.integral(l - time, r - time)?,
+            Signal::Scale { signal, factor } => factor * signal.integral(l, r)?,
+            Signal::Offset { signal, value } => signal.integral(l, r)? + value * (r - l),
+            Signal::Invalid { signal, .. } => signal.integral(l, r)?,
+            Signal::Product { .. } => self.quadrature(l, r)?,
+            Signal::Clamp { signal, min, max } => {
+                let mut points = vec![l];
+                for w in signal.segments(l, r).windows(2) {
+                    points.extend(signal.crossings(w[0], w[1], [*min, *max])?);
+                    points.push(w[1]);
+                }
+                points.dedup();
+
+                let mut integral = 0.;
+                for w in points.windows(2) {
+                    let y = signal.value_at((w[0] + w[1]) / 2.)?;
+                    integral += match (min, max) {
+                        (Some(min), _) if y < *min => min * (w[1] - w[0]),
+                        (_, Some(max)) if y > *max => max * (w[1] - w[0]),
+                        _ => signal.integral(w[0], w[1])?,
+                    };
+                }
+                integral
+            }
+        })
+    }
+
+    /// Times at which the signal or one of its derivatives is not continuous
+    pub fn knots(&self) -> Vec<f64> {
+        match self {
+            Signal::Const { .. } => vec![],
+            Signal::Linear { h, a, y, .. } => (0..=y.len()).map(|i| a + i as f64 * h).collect(),
+            Signal::Cubic { h, a, y, .. } => (0..y.len()).map(|i| a + i as f64 * h).collect(),
+            Signal::Step { time, .. } => vec![*time],
+            Signal::Sum { signals } | Signal::Product { signals } => {
+                signals.iter().flat_map(|signal| signal.knots()).collect()
+            }
+            Signal::Shift { signal, time } => {
+                signal.knots().into_iter().map(|knot| knot + time).collect()
+            }
+            Signal::Scale { signal, .. }
+            | Signal::Offset { signal, .. }
+            | Signal::Clamp { signal, .. }
+            | Signal::Invalid { signal, .. } => signal.knots(),
+        }
+    }
+
+    /// `l`, `r` and the knots between them in ascending order
+    fn segments(&self, l: f64, r: f64) -> Vec<f64> {
+        let mut points: Vec<f64> = self
+            .knots()
+            .into_iter()
+            .filter(|knot| l < *knot && *knot < r)
+            .collect();
+        points.push(l);
+        points.push(r);
+        points.sort_by(f64::total_cmp);
+        points.dedup();
+        points
+    }
+
+    /// Times in `(l, r)` between two knots at which the signal crosses one of the `bounds`,
+    /// in ascending order
+    fn crossings(&self, l: f64, r: f64, bounds: [Option<f64>; 2]) -> Result<Vec<f64>, Error> {
+        let failure = RefCell::new(None);
+        let coefficients = chebyshev_interpolation(
+            |x| {
+                self.value_at(x).unwrap_or_else(|err| {
+                    failure.borrow_mut().get_or_insert(err);
+                    f64::NAN
+                })
+            },
+            CLAMP_DEGREE,
+            (l, r),
+        );
+        if let Some(err) = failure.into_inner() {
+            return Err(err);
+        }
+        let monomial = chebyshev_to_monomial(&coefficients);
+
+        let mut crossings: Vec<f64> = bounds
+            .into_iter()
+            .flatten()
+            .flat_map(|bound| {
+                let mut shifted = monomial.clone();
+                shifted[0] -= bound;
+                roots(&shifted, (-1., 1.))
+            })
+            .map(|u| (l + r) / 2. + (r - l) / 2. * u)
+            .filter(|x| l < *x && *x < r)
+            .collect();
+        crossings.sort_by(f64::total_cmp);
+        Ok(crossings)
+    }
+
+    fn quadrature(&self, l: f64, r: f64) -> Result<f64, Error> {
+        let points = self.segments(l, r);
+
+        let mut integral = 0.;
+        for w in points.windows(2) {
+            let half_width = (w[1] - w[0]) / 2.;
+            let center = (w[1] + w[0]) / 2.;
+
+            for (node, weight) in GAUSS_LEGENDRE {
+                integral += weight * half_width * self.value_at(center + node * half_width)?;
+            }
+        }
+
+        Ok(integral)
+    }
+}
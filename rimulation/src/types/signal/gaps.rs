@@ -0,0 +1,80 @@
+use super::super::formats::custom::GapPolicy;
+
+use anyhow::{anyhow, Error};
+
+/// Fills the NaN values in place according to the policy
+///
+/// Returns the index ranges `(first, last)` of the filled gaps.
+pub fn fill_gaps(values: &mut [f64], policy: GapPolicy) -> Result<Vec<(usize, usize)>, Error> {
+    let mut gaps = vec![];
+
+    let mut i = 0;
+    while i < values.len() {
+        if !values[i].is_nan() {
+            i += 1;
+            continue;
+        }
+
+        let first = i;
+        while i < values.len() && values[i].is_nan() {
+            i += 1;
+        }
+        gaps.push((first, i - 1));
+    }
+
+    if gaps == [(0, values.len() - 1)] {
+        return Err(anyhow!("data does not have any valid values"));
+    }
+
+    for (first, last) in &gaps {
+        let left = first.checked_sub(1).map(|l| (l, values[l]));
+        let right = (last + 1 < values.len()).then(|| (last + 1, values[last + 1]));
+
+        for (j, value) in values[*first..=*last].iter_mut().enumerate() {
+            let j = first + j;
+            *value = match (policy, left, right) {
+                (GapPolicy::HoldLast, Some((_, l)), _) => l,
+                (_, Some((l, y_l)), Some((r, y_r))) => {
+                    y_l + (y_r - y_l) * (j - l) as f64 / (r - l) as f64
+                }
+                (_, Some((_, y)), None) | (_, None, Some((_, y))) => y,
+                (_, None, None) => unreachable!("there is at least one valid value"),
+            };
+        }
+    }
+
+    Ok(gaps)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NAN: f64 = f64::NAN;
+
+    #[test]
+    fn fill_linear() {
+        let mut values = [NAN, 1., NAN, NAN, 4., NAN];
+
+        let gaps = fill_gaps(&mut values, GapPolicy::Linear).expect("could not fill gaps");
+
+        assert_eq!(gaps, [(0, 0), (2, 3), (5, 5)]);
+        assert_eq!(values, [1., 1., 2., 3., 4., 4.]);
+    }
+
+    #[test]
+    fn fill_hold_last() {
+        let mut values = [NAN, 1., NAN, NAN, 4., NAN];
+
+        fill_gaps(&mut values, GapPolicy::HoldLast).expect("could not fill gaps");
+
+        assert_eq!(values, [1., 1., 1., 1., 4., 4.]);
+    }
+
+    #[test]
+    fn fill_without_valid_values() {
+        let mut values = [NAN, NAN];
+
+        assert!(fill_gaps(&mut values, GapPolicy::Linear).is_err());
+    }
+}
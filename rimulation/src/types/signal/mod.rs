@@ -1,9 +1,13 @@
-use super::formats::custom::{self, DataPoint};
+use super::formats::custom::{self, GapPolicy};
 
 use anyhow::{anyhow, Error};
 use approx::AbsDiffEq;
 use nalgebra::{DMatrix, DVector};
+use std::collections::HashMap;
 
+mod batch;
+mod calculus;
+mod gaps;
 #[cfg(test)]
 mod test;
 
@@ -31,6 +35,34 @@ pub enum Signal {
         high: f64,
         time: f64,
     },
+    Sum {
+        signals: Vec<Signal>,
+    },
+    Product {
+        signals: Vec<Signal>,
+    },
+    Shift {
+        signal: Box<Signal>,
+        time: f64,
+    },
+    Scale {
+        signal: Box<Signal>,
+        factor: f64,
+    },
+    Offset {
+        signal: Box<Signal>,
+        value: f64,
+    },
+    Clamp {
+        signal: Box<Signal>,
+        min: Option<f64>,
+        max: Option<f64>,
+    },
+    /// Signal with time spans that are based on invalid data
+    Invalid {
+        signal: Box<Signal>,
+        intervals: Vec<(f64, f64)>,
+    },
 }
 
 impl TryFrom<custom::Signal> for Signal {
@@ -45,6 +77,7 @@ impl TryFrom<custom::Signal> for Signal {
                 degree,
                 scale,
                 data,
+                gaps,
             } => {
                 if ![1, 3].contains(&degree) {
                     return Err(anyhow!("polynomial of degree {} not supported", degree));
@@ -54,32 +87,131 @@ impl TryFrom<custom::Signal> for Signal {
                     return Err(anyhow!("data needs at least 2 points"));
                 }
 
-                let h = data[1].t - data[0].t;
-                for i in 1..data.len() - 1 {
-                    if data[i + 1].t - data[i].t != h {
-                        return Err(anyhow!("data has inconsistent dt at index {}", i));
-                    }
-                }
-
                 let a = data.first().expect("at least 2 points in data").t;
                 let b = data.last().expect("at least 2 points in data").t;
 
-                let data = data
-                    .into_iter()
-                    .map(|DataPoint { t: _, v }| scale * v)
-                    .collect();
+                let (h, mut data) = custom::grid::equidistant(&data)?;
+                let filled = gaps::fill_gaps(&mut data, gaps)?;
 
-                match degree {
-                    1 => Ok(interpolate_linear(h, a, b, data)),
-                    3 => interpolate_cubic(h, a, b, data),
+                let n = data.len() - 1;
+                let data = data.into_iter().map(|v| scale * v).collect();
+
+                let signal = match degree {
+                    1 => interpolate_linear(h, a, b, data),
+                    3 => interpolate_cubic(h, a, b, data)?,
                     _ => unreachable!("all other degrees are not allowed"),
+                };
+
+                if gaps != GapPolicy::Invalid || filled.is_empty() {
+                    return Ok(signal);
                 }
+
+                // the interpolation between the neighbours of a gap depends on the missing data
+                let intervals = filled
+                    .into_iter()
+                    .map(|(first, last)| {
+                        (
+                            a + first.saturating_sub(1) as f64 * h,
+                            a + (last + 1).min(n) as f64 * h,
+                        )
+                    })
+                    .collect();
+
+                Ok(Signal::Invalid {
+                    signal: Box::new(signal),
+                    intervals,
+                })
             }
             custom::Signal::Step { low, high, time } => Ok(Signal::Step { low, high, time }),
+            custom::Signal::Csv { path, .. } => Err(anyhow!(
+                "data of time series '{}' has to be loaded before conversion",
+                path
+            )),
+            custom::Signal::Sum { .. }
+            | custom::Signal::Product { .. }
+            | custom::Signal::Shift { .. }
+            | custom::Signal::Scale { .. }
+            | custom::Signal::Offset { .. }
+            | custom::Signal::Clamp { .. } => Err(anyhow!(
+                "composite signal referencing {:?} has to be resolved by name",
+                value.references()
+            )),
         }
     }
 }
 
+/// Resolves the signal with the given name, including all signals it references
+pub fn resolve(name: &str, signals: &HashMap<String, custom::Signal>) -> Result<Signal, Error> {
+    resolve_rec(name, signals, &mut vec![])
+}
+
+fn resolve_rec(
+    name: &str,
+    signals: &HashMap<String, custom::Signal>,
+    path: &mut Vec<String>,
+) -> Result<Signal, Error> {
+    if let Some(start) = path.iter().position(|visited| visited == name) {
+        return Err(anyhow!(
+            "signal reference cycle: {} -> {}",
+            path[start..].join(" -> "),
+            name
+        ));
+    }
+
+    let signal = signals
+        .get(name)
+        .ok_or(anyhow!("signal with name '{}' does not exist", name))?;
+
+    path.push(name.to_string());
+
+    let resolved = match signal {
+        custom::Signal::Sum { signals: operands } => Signal::Sum {
+            signals: resolve_operands(name, operands, signals, path)?,
+        },
+        custom::Signal::Product { signals: operands } => Signal::Product {
+            signals: resolve_operands(name, operands, signals, path)?,
+        },
+        custom::Signal::Shift { signal, time } => Signal::Shift {
+            signal: Box::new(resolve_rec(signal, signals, path)?),
+            time: *time,
+        },
+        custom::Signal::Scale { signal, factor } => Signal::Scale {
+            signal: Box::new(resolve_rec(signal, signals, path)?),
+            factor: *factor,
+        },
+        custom::Signal::Offset { signal, value } => Signal::Offset {
+            signal: Box::new(resolve_rec(signal, signals, path)?),
+            value: *value,
+        },
+        custom::Signal::Clamp { signal, min, max } => {
+            if let (Some(min), Some(max)) = (min, max) {
+                if min > max {
+                    return Err(anyhow!(
+                        "clamp signal '{}' has min {} larger than max {}",
+                        name,
+                        min,
+                        max
+                    ));
+                }
+            }
+
+            Signal::Clamp {
+                signal: Box::new(resolve_rec(signal, signals, path)?),
+                min: *min,
+                max: *max,
+            }
+        }
+        leaf => leaf
+            .clone()
+            .try_into()
+            .map_err(|err| anyhow!("could not convert signal '{}': {}", name, err))?,
+    };
+
+    path.pop();
+
+    Ok(resolved)
+}
+
 fn interpolate_linear(h: f64, a: f64, b: f64, data: Vec<f64>) -> Signal {
     let n = data.len() - 1;
     let mut y = vec![0.; n];
@@ -149,12 +281,85 @@ fn get_index(h: &f64, a: &f64, b: &f64, x: &f64) -> Result<usize, Error> {
     Ok(((x - a) / h).floor() as usize)
 }
 
+fn resolve_operands(
+    name: &str,
+    operands: &[String],
+    signals: &HashMap<String, custom::Signal>,
+    path: &mut Vec<String>,
+) -> Result<Vec<Signal>, Error> {
+    if operands.is_empty() {
+        return Err(anyhow!("composite signal '{}' has no operands", name));
+    }
+
+    operands
+        .iter()
+        .map(|operand| resolve_rec(operand, signals, path))
+        .collect()
+}
+
 impl Signal {
+    /// Multiplies every value of the signal by `factor`
+    pub fn scale(self, factor: f64) -> Self {
+        match self {
+            Signal::Const { value } => Signal::Const {
+                value: value * factor,
+            },
+            Signal::Linear { h, a, b, y, dy } => Signal::Linear {
+                h,
+                a,
+                b,
+                y: y.into_iter().map(|y| y * factor).collect(),
+                dy: dy.into_iter().map(|dy| dy * factor).collect(),
+            },
+            Signal::Cubic { h, a, b, y, m } => Signal::Cubic {
+                h,
+                a,
+                b,
+                y: y.into_iter().map(|y| y * factor).collect(),
+                m: m.into_iter().map(|m| m * factor).collect(),
+            },
+            Signal::Step { low, high, time } => Signal::Step {
+                low: low * factor,
+                high: high * factor,
+                time,
+            },
+            Signal::Invalid { signal, intervals } => Signal::Invalid {
+                signal: Box::new(signal.scale(factor)),
+                intervals,
+            },
+            signal => Signal::Scale {
+                signal: Box::new(signal),
+                factor,
+            },
+        }
+    }
+
+    /// Whether the value at `x` is based on valid data only
+    pub fn is_valid_at(&self, x: f64) -> bool {
+        match self {
+            Signal::Const { .. }
+            | Signal::Linear { .. }
+            | Signal::Cubic { .. }
+            | Signal::Step { .. } => true,
+            Signal::Sum { signals } | Signal::Product { signals } => {
+                signals.iter().all(|signal| signal.is_valid_at(x))
+            }
+            Signal::Shift { signal, time } => signal.is_valid_at(x - time),
+            Signal::Scale { signal, .. }
+            | Signal::Offset { signal, .. }
+            | Signal::Clamp { signal, .. } => signal.is_valid_at(x),
+            Signal::Invalid { signal, intervals } => {
+                !intervals.iter().any(|(l, r)| *l < x && x < *r) && signal.is_valid_at(x)
+            }
+        }
+    }
+
     pub fn value_at(&self, x: f64) -> Result<f64, Error> {
         Ok(match self {
             Signal::Const { value } => *value,
             Signal::Linear { h, a, b, y, dy } => {
-                let i = get_index(h, a, b, &x)?;
+                // the right boundary belongs to the last segment
+                let i = get_index(h, a, b, &x)?.min(y.len() - 1);
                 y[i] + x * dy[i]
             }
             Signal::Cubic { h, a, b, y, m } => {
@@ -180,6 +385,28 @@ impl Signal {
                     *high
                 }
             }
+            Signal::Sum { signals } => signals
+                .iter()
+                .map(|signal| signal.value_at(x))
+                .sum::<Result<f64, Error>>()?,
+            Signal::Product { signals } => signals
+                .iter()
+                .map(|signal| signal.value_at(x))
+                .product::<Result<f64, Error>>()?,
+            Signal::Shift { signal, time } => signal.value_at(x - time)?,
+            Signal::Scale { signal, factor } => factor * signal.value_at(x)?,
+            Signal::Offset { signal, value } => value + signal.value_at(x)?,
+            Signal::Invalid { signal, .. } => signal.value_at(x)?,
+            Signal::Clamp { signal, min, max } => {
+                let mut y = signal.value_at(x)?;
+                if let Some(min) = min {
+                    y = y.max(*min);
+                }
+                if let Some(max) = max {
+                    y = y.min(*max);
+                }
+                y
+            }
         })
     }
 }
@@ -1,10 +1,13 @@
-use std::{fs, io::Write};
+use std::{collections::HashMap, fs, io::Write};
 
 use approx::assert_relative_eq;
 
 use super::*;
 
-use crate::types::formats::custom::{self, DataPoint};
+use crate::{
+    scalar::Dual,
+    types::formats::custom::{self, DataPoint, GapPolicy},
+};
 
 #[test]
 fn convert_constant_signal() {
@@ -30,6 +33,7 @@ fn assert_convert_polynomial_errors(
         degree,
         scale: 1.,
         data,
+        gaps: GapPolicy::Linear,
     };
 
     let result: Result<Signal, Error> = custom_signal.try_into();
@@ -95,6 +99,7 @@ fn linear_interpolation() {
             DataPoint { t: 3., v: 1. },
             DataPoint { t: 4., v: 0.5 },
         ],
+        gaps: GapPolicy::Linear,
     };
 
     let linear_signal: Signal = custom_linear_signal
@@ -147,6 +152,7 @@ fn cubic_interpolation() {
         degree: 3,
         scale: 1.,
         data: data.clone(),
+        gaps: GapPolicy::Linear,
     };
 
     let cubic_signal: Signal = custom_cubic_signal
@@ -175,3 +181,371 @@ fn cubic_interpolation() {
             .expect("could not write data to temporary file");
     }
 }
+
+fn composite_test_signals() -> HashMap<String, custom::Signal> {
+    serde_json::from_str(
+        r#"{
+            "base": { "const": { "scale": 1, "data": 2 } },
+            "ramp": {
+                "poly": {
+                    "degree": 1,
+                    "scale": 1,
+                    "data": [{ "t": 0, "v": 0 }, { "t": 10, "v": 10 }]
+                }
+            },
+            "step": { "step": { "low": 0, "high": 5, "time": 4 } },
+            "total": { "sum": { "signals": ["base", "ramp", "step"] } },
+            "product": { "product": { "signals": ["base", "ramp"] } },
+            "delayed": { "shift": { "signal": "ramp", "time": 2 } },
+            "scaled": { "scale": { "signal": "ramp", "factor": 3 } },
+            "offset": { "offset": { "signal": "ramp", "value": -1 } },
+            "clamped": { "clamp": { "signal": "ramp", "min": 2, "max": 8 } },
+            "floored": { "clamp": { "signal": "ramp", "min": 2 } },
+            "nested": { "scale": { "signal": "total", "factor": 0.5 } }
+        }"#,
+    )
+    .expect("could not parse composite signals")
+}
+
+#[test]
+fn composite_signals() {
+    let signals = composite_test_signals();
+
+    let assert_values = |name: &str, expected: &[(f64, f64)]| {
+        let signal = resolve(name, &signals)
+            .unwrap_or_else(|err| panic!("could not resolve signal '{}': {}", name, err));
+
+        for (t, v) in expected {
+            let y = signal
+                .value_at(*t)
+                .unwrap_or_else(|_| panic!("could not evaluate signal '{}' at {}", name, t));
+            assert_relative_eq!(y, v);
+        }
+    };
+
+    assert_values("total", &[(0., 2.), (3., 5.), (4., 11.), (10., 17.)]);
+    assert_values("product", &[(0., 0.), (2.5, 5.), (10., 20.)]);
+    assert_values("delayed", &[(2., 0.), (5., 3.), (12., 10.)]);
+    assert_values("scaled", &[(1., 3.), (10., 30.)]);
+    assert_values("offset", &[(0., -1.), (10., 9.)]);
+    assert_values("clamped", &[(0., 2.), (5., 5.), (10., 8.)]);
+    assert_values("floored", &[(0., 2.), (10., 10.)]);
+    assert_values("nested", &[(0., 1.), (4., 5.5)]);
+}
+
+#[test]
+fn composite_signal_out_of_bounds_after_shift() {
+    let signals = composite_test_signals();
+
+    let delayed = resolve("delayed", &signals).expect("could not resolve signal");
+
+    assert!(delayed.value_at(1.).is_err());
+}
+
+#[test]
+fn composite_signal_errors() {
+    let mut signals = composite_test_signals();
+    signals.insert(
+        String::from("a"),
+        custom::Signal::Sum {
+            signals: vec![String::from("base"), String::from("b")],
+        },
+    );
+    signals.insert(
+        String::from("b"),
+        custom::Signal::Shift {
+            signal: String::from("a"),
+            time: 1.,
+        },
+    );
+    signals.insert(
+        String::from("self"),
+        custom::Signal::Offset {
+            signal: String::from("self"),
+            value: 1.,
+        },
+    );
+    signals.insert(
+        String::from("dangling"),
+        custom::Signal::Scale {
+            signal: String::from("missing"),
+            factor: 1.,
+        },
+    );
+    signals.insert(
+        String::from("empty"),
+        custom::Signal::Product { signals: vec![] },
+    );
+    signals.insert(
+        String::from("inverted"),
+        custom::Signal::Clamp {
+            signal: String::from("base"),
+            min: Some(2.),
+            max: Some(1.),
+        },
+    );
+
+    let assert_resolve_errors = |name: &str, expected_msg: &str| {
+        let err = resolve(name, &signals).expect_err("resolving should fail");
+        assert!(
+            err.to_string().contains(expected_msg),
+            "error message not as expected for signal '{}': expected {}, got {}",
+            name,
+            expected_msg,
+            err,
+        );
+    };
+
+    assert_resolve_errors("a", "signal reference cycle: a -> b -> a");
+    assert_resolve_errors("self", "signal reference cycle: self -> self");
+    assert_resolve_errors("dangling", "signal with name 'missing' does not exist");
+    assert_resolve_errors("empty", "composite signal 'empty' has no operands");
+    assert_resolve_errors("inverted", "has min 2 larger than max 1");
+
+    let composite: Result<Signal, Error> = signals["total"].clone().try_into();
+    assert!(composite.is_err());
+}
+
+#[test]
+fn fill_gaps_of_measured_data() {
+    let signals: HashMap<String, custom::Signal> = serde_json::from_str(
+        r#"{
+            "linear": {
+                "poly": {
+                    "degree": 1,
+                    "scale": 1,
+                    "data": [{ "t": 0, "v": 0 }, { "t": 1, "v": null }, { "t": 3, "v": 3 }]
+                }
+            },
+            "hold": {
+                "poly": {
+                    "degree": 1,
+                    "scale": 1,
+                    "data": [{ "t": 0, "v": 0 }, { "t": 1, "v": null }, { "t": 3, "v": 3 }],
+                    "gaps": "hold_last"
+                }
+            },
+            "invalid": {
+                "poly": {
+                    "degree": 3,
+                    "scale": 2,
+                    "data": [{ "t": 0, "v": 0 }, { "t": 1, "v": null }, { "t": 3, "v": 3 }],
+                    "gaps": "invalid"
+                }
+            },
+            "delayed": { "shift": { "signal": "invalid", "time": 10 } }
+        }"#,
+    )
+    .expect("could not parse signals");
+
+    for name in ["linear", "hold", "invalid"] {
+        assert_eq!(signals[name].num_gaps(), 2, "gaps of signal '{}'", name);
+    }
+
+    let linear = resolve("linear", &signals).expect("could not resolve signal");
+    assert_relative_eq!(linear.value_at(2.).expect("could not evaluate"), 2.);
+    assert!(linear.is_valid_at(2.));
+
+    let hold = resolve("hold", &signals).expect("could not resolve signal");
+    assert_relative_eq!(hold.value_at(2.).expect("could not evaluate"), 0.);
+
+    let invalid = resolve("invalid", &signals).expect("could not resolve signal");
+    assert_relative_eq!(invalid.value_at(3.).expect("could not evaluate"), 6.);
+    assert!(invalid.is_valid_at(0.));
+    assert!(!invalid.is_valid_at(0.5));
+    assert!(!invalid.is_valid_at(2.5));
+    assert!(invalid.is_valid_at(3.));
+
+    let delayed = resolve("delayed", &signals).expect("could not resolve signal");
+    assert!(!delayed.is_valid_at(12.));
+    assert!(delayed.is_valid_at(13.));
+}
+
+#[test]
+fn derivatives_and_integrals() {
+    let signals = composite_test_signals();
+
+    let ramp = resolve("ramp", &signals).expect("could not resolve signal");
+    assert_relative_eq!(ramp.derivative_at(3.).expect("could not derive"), 1.);
+    assert_relative_eq!(ramp.derivative_at(10.).expect("could not derive"), 1.);
+    assert_relative_eq!(ramp.integral(0., 10.).expect("could not integrate"), 50.);
+    assert_relative_eq!(ramp.integral(4., 2.).expect("could not integrate"), -6.);
+    assert!(ramp.integral(-1., 2.).is_err());
+
+    let step = resolve("step", &signals).expect("could not resolve signal");
+    assert_relative_eq!(step.derivative_at(3.).expect("could not derive"), 0.);
+    assert!(step.derivative_at(4.).is_err());
+    assert!(step.value_at_scalar(Dual::variable(4.)).is_err());
+    assert_eq!(
+        step.value_at_scalar(Dual::constant(4.))
+            .expect("could not evaluate"),
+        Dual::constant(step.value_at(4.).expect("could not evaluate"))
+    );
+    assert_relative_eq!(step.integral(0., 10.).expect("could not integrate"), 30.);
+
+    let total = resolve("total", &signals).expect("could not resolve signal");
+    assert_relative_eq!(total.integral(0., 10.).expect("could not integrate"), 100.);
+
+    // 2 t integrated from 0 to 10
+    let product = resolve("product", &signals).expect("could not resolve signal");
+    assert_relative_eq!(product.derivative_at(5.).expect("could not derive"), 2.);
+    assert_eq!(
+        product
+            .value_at_scalar(Dual::variable(5.))
+            .expect("could not evaluate"),
+        Dual {
+            value: 10.,
+            derivative: 2.
+        }
+    );
+    assert_relative_eq!(
+        product.integral(0., 10.).expect("could not integrate"),
+        100.,
+        epsilon = 1e-12
+    );
+
+    let delayed = resolve("delayed", &signals).expect("could not resolve signal");
+    assert_relative_eq!(delayed.integral(2., 12.).expect("could not integrate"), 50.);
+
+    let clamped = resolve("clamped", &signals).expect("could not resolve signal");
+    assert_relative_eq!(clamped.derivative_at(1.).expect("could not derive"), 0.);
+    assert_relative_eq!(clamped.derivative_at(5.).expect("could not derive"), 1.);
+    assert_relative_eq!(clamped.derivative_at(9.).expect("could not derive"), 0.);
+    assert_relative_eq!(
+        clamped.integral(0., 10.).expect("could not integrate"),
+        2. * 2. + 30. + 2. * 8.,
+        epsilon = 1e-12
+    );
+    assert_relative_eq!(
+        clamped.integral(3., 9.).expect("could not integrate"),
+        (64. - 9.) / 2. + 8.,
+        epsilon = 1e-12
+    );
+
+    let floored = resolve("floored", &signals).expect("could not resolve signal");
+    assert_relative_eq!(
+        floored.integral(0., 10.).expect("could not integrate"),
+        2. * 2. + (100. - 4.) / 2.,
+        epsilon = 1e-12
+    );
+
+    // t^2 crosses 25 inside the only segment of the ramp
+    let square = Signal::Clamp {
+        signal: Box::new(Signal::Product {
+            signals: vec![ramp.clone(), ramp.clone()],
+        }),
+        min: None,
+        max: Some(25.),
+    };
+    assert_relative_eq!(
+        square.integral(0., 10.).expect("could not integrate"),
+        125. / 3. + 25. * 5.,
+        epsilon = 1e-12
+    );
+}
+
+#[test]
+fn cubic_derivatives_and_integrals() {
+    let data = [0., 1., 0.5, 0., 2.]
+        .iter()
+        .enumerate()
+        .map(|(i, v)| DataPoint {
+            t: 2. + i as f64,
+            v: *v,
+        })
+        .collect();
+
+    let cubic_signal: Signal = custom::Signal::Poly {
+        degree: 3,
+        scale: 1.,
+        data,
+        gaps: GapPolicy::Linear,
+    }
+    .try_into()
+    .expect("could not convert cubic signal");
+
+    let dx = 1e-6;
+    for x in [2., 2.5, 3., 4.2, 5.9] {
+        let finite_difference = (cubic_signal.value_at(x + dx).expect("could not evaluate")
+            - cubic_signal.value_at(x).expect("could not evaluate"))
+            / dx;
+
+        assert_relative_eq!(
+            cubic_signal.derivative_at(x).expect("could not derive"),
+            finite_difference,
+            epsilon = 1e-5
+        );
+    }
+
+    // trapezoidal rule with a fine grid
+    let (l, r) = (2.3, 5.6);
+    let n = 10_000;
+    let width = (r - l) / n as f64;
+    let trapezoidal: f64 = (0..n)
+        .map(|i| {
+            let x = l + i as f64 * width;
+            (cubic_signal.value_at(x).expect("could not evaluate")
+                + cubic_signal
+                    .value_at(x + width)
+                    .expect("could not evaluate"))
+                * width
+                / 2.
+        })
+        .sum();
+
+    assert_relative_eq!(
+        cubic_signal.integral(l, r).expect("could not integrate"),
+        trapezoidal,
+        epsilon = 1e-6
+    );
+    assert_relative_eq!(
+        cubic_signal.integral(2., 6.).expect("could not integrate"),
+        cubic_signal.integral(2., 4.).expect("could not integrate")
+            + cubic_signal.integral(4., 6.).expect("could not integrate"),
+        epsilon = 1e-12
+    );
+}
+
+#[test]
+fn batch_evaluation_matches_single_evaluation() {
+    let mut signals = composite_test_signals();
+    signals.insert(
+        String::from("cubic"),
+        custom::Signal::Poly {
+            degree: 3,
+            scale: 1.,
+            data: [0., 1., 0.5, 0., 2., 1.]
+                .iter()
+                .enumerate()
+                .map(|(i, v)| DataPoint {
+                    t: 2. * i as f64,
+                    v: *v,
+                })
+                .collect(),
+            gaps: GapPolicy::Linear,
+        },
+    );
+
+    for name in ["ramp", "cubic", "total", "product", "clamped", "nested"] {
+        let signal = resolve(name, &signals).expect("could not resolve signal");
+
+        let grid = signal
+            .values_on_grid(0., 0.25, 41)
+            .expect("could not evaluate signal on grid");
+        let unsorted = signal
+            .values_at(&[7.3, 0., 10., 2.])
+            .expect("could not evaluate signal at points");
+
+        for (i, y) in grid.iter().enumerate() {
+            let x = i as f64 * 0.25;
+            assert_relative_eq!(*y, signal.value_at(x).expect("could not evaluate"));
+        }
+        for (x, y) in [7.3, 0., 10., 2.].iter().zip(unsorted.iter()) {
+            assert_relative_eq!(*y, signal.value_at(*x).expect("could not evaluate"));
+        }
+    }
+
+    let ramp = resolve("ramp", &signals).expect("could not resolve signal");
+    assert!(ramp.values_on_grid(0., 1., 12).is_err());
+    assert_eq!(ramp.values_at(&[]).expect("no points to evaluate").len(), 0);
+}
@@ -0,0 +1,515 @@
+//! Properties of liquid water following the IAPWS Industrial Formulation 1997 (IAPWS-IF97)
+//!
+//! Implements the fundamental equation of region 1 (liquid water) and the saturation pressure
+//! equation of region 4, as well as the IAPWS 2008 formulation for the viscosity
+//! (without the critical enhancement, which is negligible in region 1).
+//!
+//! All functions take the temperature T in \[°C\] and the pressure p in \[Pa\].
+//!
+//! # Valid Range
+//! * T: \[0, 350\]
+//! * p: \[p_s(T), 100 MPa\], where p_s is the saturation pressure
+//!
+//! # Comparison with the Polynomial Fits
+//! Between 5 °C and 150 °C at 10 bar, the fits of the water module deviate by at most
+//! * 0.08 % in density and 0.15 % in specific heat capacity,
+//! * 12 % in kinematic viscosity (at 5 °C, below 4 % above 20 °C),
+//! * 0.009 GJ/m^3 in energy density.
+//!
+//! The test `generate_comparison_with_fits` writes the full comparison to `/tmp/if97_comparison`.
+
+use anyhow::{anyhow, Error};
+
+use super::thermal_conductivity;
+use crate::{
+    fluid::FluidProperties,
+    scalar::{Dual, Scalar},
+};
+
+/// Specific gas constant of water \[kJ/(kg K)\]
+const R: f64 = 0.461526;
+
+/// Zero of the Celsius scale \[K\]
+const KELVIN: f64 = 273.15;
+
+/// Reducing pressure \[MPa\] and temperature \[K\] of region 1
+const P_STAR: f64 = 16.53;
+const T_STAR: f64 = 1386.;
+
+/// Coefficients I, J and n of the dimensionless Gibbs free energy of region 1
+const REGION_1: [(i32, i32, f64); 34] = [
+    (0, -2, 0.146_329_712_131_67),
+    (0, -1, -0.845_481_871_691_14),
+    (0, 0, -0.375_636_036_720_40e1),
+    (0, 1, 0.338_551_691_683_85e1),
+    (0, 2, -0.957_919_633_878_72),
+    (0, 3, 0.157_720_385_132_28),
+    (0, 4, -0.166_164_171_995_01e-1),
+    (0, 5, 0.812_146_299_835_68e-3),
+    (1, -9, 0.283_190_801_238_04e-3),
+    (1, -7, -0.607_063_015_658_74e-3),
+    (1, -1, -0.189_900_682_184_19e-1),
+    (1, 0, -0.325_297_487_705_05e-1),
+    (1, 1, -0.218_417_171_754_14e-1),
+    (1, 3, -0.528_383_579_699_30e-4),
+    (2, -3, -0.471_843_210_732_67e-3),
+    (2, 0, -0.300_017_807_930_26e-3),
+    (2, 1, 0.476_613_939_069_87e-4),
+    (2, 3, -0.441_418_453_308_46e-5),
+    (2, 17, -0.726_949_962_975_94e-15),
+    (3, -4, -0.316_796_448_450_54e-4),
+    (3, 0, -0.282_707_979_853_12e-5),
+    (3, 6, -0.852_051_281_201_03e-9),
+    (4, -5, -0.224_252_819_080_00e-5),
+    (4, -2, -0.651_712_228_956_01e-6),
+    (4, 10, -0.143_417_299_379_24e-12),
+    (5, -8, -0.405_169_968_601_17e-6),
+    (8, -11, -0.127_343_017_416_41e-8),
+    (8, -6, -0.174_248_712_306_34e-9),
+    (21, -29, -0.687_621_312_955_31e-18),
+    (23, -31, 0.144_783_078_285_21e-19),
+    (29, -38, 0.263_357_816_627_95e-22),
+    (30, -39, -0.119_476_226_400_71e-22),
+    (31, -40, 0.182_280_945_814_04e-23),
+    (32, -41, -0.935_370_872_924_58e-25),
+];
+
+/// Coefficients n1 to n10 of the saturation pressure equation of region 4
+const REGION_4: [f64; 10] = [
+    0.116_705_214_527_67e4,
+    -0.724_213_167_032_06e6,
+    -0.170_738_469_400_92e2,
+    0.120_208_247_024_70e5,
+    -0.323_255_503_223_33e7,
+    0.149_151_086_135_30e2,
+    -0.482_326_573_615_91e4,
+    0.405_113_405_420_57e6,
+    -0.238_555_575_678_49,
+    0.650_175_348_447_98e3,
+];
+
+/// Reducing temperature \[K\], density \[kg/m^3\] and viscosity \[Pa s\] of the viscosity formulation
+const VISCOSITY_T_STAR: f64 = 647.096;
+const VISCOSITY_RHO_STAR: f64 = 322.;
+const VISCOSITY_MU_STAR: f64 = 1e-6;
+
+/// Coefficients H_i of the viscosity in the dilute-gas limit
+const VISCOSITY_H0: [f64; 4] = [1.67752, 2.20462, 0.636_656_4, -0.241_605];
+
+/// Coefficients H_ij of the residual contribution to the viscosity
+const VISCOSITY_H1: [[f64; 7]; 6] = [
+    [
+        5.20094e-1,
+        2.22531e-1,
+        -2.81378e-1,
+        1.61913e-1,
+        -3.25372e-2,
+        0.,
+        0.,
+    ],
+    [8.50895e-2, 9.99115e-1, -9.06851e-1, 2.57399e-1, 0., 0., 0.],
+    [-1.08374, 1.88797, -7.72479e-1, 0., 0., 0., 0.],
+    [
+        -2.89555e-1,
+        1.26613,
+        -4.89837e-1,
+        0.,
+        6.98452e-2,
+        0.,
+        -4.35673e-3,
+    ],
+    [0., 0., -2.57040e-1, 0., 0., 8.72102e-3, 0.],
+    [0., 1.20573e-1, 0., 0., 0., 0., -5.93264e-4],
+];
+
+/// Dimensionless Gibbs free energy gamma and the derivatives used for the properties
+///
+/// Only the temperature is generic, the pressure is a parameter of the fluid.
+struct Gibbs<T> {
+    pi: f64,
+    tau: T,
+    gamma_pi: T,
+    gamma_tau: T,
+    gamma_tau_tau: T,
+}
+
+impl<T: Scalar> Gibbs<T> {
+    fn new(t: T, p: f64) -> Self {
+        let pi = p * 1e-6 / P_STAR;
+        let tau = (t + KELVIN).recip() * T_STAR;
+
+        let (x, y) = (7.1 - pi, tau - 1.222);
+
+        let mut gibbs = Gibbs {
+            pi,
+            tau,
+            gamma_pi: T::zero(),
+            gamma_tau: T::zero(),
+            gamma_tau_tau: T::zero(),
+        };
+
+        for (i, j, n) in REGION_1 {
+            let (fi, fj) = (i as f64, j as f64);
+
+            gibbs.gamma_pi -= y.powi(j) * (n * fi * x.powi(i - 1));
+            gibbs.gamma_tau += y.powi(j - 1) * (n * x.powi(i) * fj);
+            gibbs.gamma_tau_tau += y.powi(j - 2) * (n * x.powi(i) * fj * (fj - 1.));
+        }
+
+        gibbs
+    }
+}
+
+/// Computes the saturation pressure p_s \[Pa\] based on the temperature T \[°C\]
+///
+/// # Valid Range
+/// * T: \[0, 373.946\]
+pub fn saturation_pressure(t: f64) -> f64 {
+    let [n1, n2, n3, n4, n5, n6, n7, n8, n9, n10] = REGION_4;
+
+    let t = t + KELVIN;
+    let theta = t + n9 / (t - n10);
+
+    let a = theta * theta + n1 * theta + n2;
+    let b = n3 * theta * theta + n4 * theta + n5;
+    let c = n6 * theta * theta + n7 * theta + n8;
+
+    (2. * c / (-b + (b * b - 4. * a * c).sqrt())).powi(4) * 1e6
+}
+
+/// Checks whether the temperature T \[°C\] and pressure p \[Pa\] lie in region 1
+pub fn in_region_1(t: f64, p: f64) -> bool {
+    (0. ..=350.).contains(&t) && p >= saturation_pressure(t) && p <= 100e6
+}
+
+/// Computes the density rho \[kg/m^3\]
+pub fn density<T: Scalar>(t: T, p: f64) -> T {
+    let gibbs = Gibbs::new(t, p);
+
+    // specific volume in m^3/kg
+    let v = (t + KELVIN) * gibbs.gamma_pi * (R * 1e3 / p * gibbs.pi);
+
+    v.recip()
+}
+
+/// Computes the specific enthalpy h \[kJ/kg\]
+pub fn enthalpy<T: Scalar>(t: T, p: f64) -> T {
+    let gibbs = Gibbs::new(t, p);
+
+    (t + KELVIN) * gibbs.tau * gibbs.gamma_tau * R
+}
+
+/// Computes the specific isobaric heat capacity c_p \[J/(kg K)\]
+pub fn specific_heat<T: Scalar>(t: T, p: f64) -> T {
+    let gibbs = Gibbs::new(t, p);
+
+    gibbs.tau * gibbs.tau * gibbs.gamma_tau_tau * (-R * 1e3)
+}
+
+/// Computes the dynamic viscosity mu \[Pa s\] based on the temperature T \[°C\]
+/// and the density rho \[kg/m^3\]
+pub fn viscosity_from_density<T: Scalar>(t: T, rho: T) -> T {
+    let t = (t + KELVIN) / VISCOSITY_T_STAR;
+    let rho = rho / VISCOSITY_RHO_STAR;
+
+    let mu_0 = t.sqrt() * 100.
+        / VISCOSITY_H0
+            .iter()
+            .enumerate()
+            .map(|(i, h)| t.powi(i as i32).recip() * *h)
+            .sum::<T>();
+
+    let mut sum = T::zero();
+    for (i, row) in VISCOSITY_H1.iter().enumerate() {
+        for (j, h) in row.iter().enumerate() {
+            sum += (t.recip() - 1.).powi(i as i32) * (rho - 1.).powi(j as i32) * *h;
+        }
+    }
+    let mu_1 = (rho * sum).exp();
+
+    mu_0 * mu_1 * VISCOSITY_MU_STAR
+}
+
+/// Computes the dynamic viscosity mu \[Pa s\]
+pub fn viscosity<T: Scalar>(t: T, p: f64) -> T {
+    viscosity_from_density(t, density(t, p))
+}
+
+/// Computes the energy density e \[GJ/m^3\] as the enthalpy per volume
+pub fn energy_density<T: Scalar>(t: T, p: f64) -> T {
+    density(t, p) * enthalpy(t, p) * 1e-6
+}
+
+/// Liquid water at a constant pressure described by IAPWS-IF97
+///
+/// The thermal conductivity is not part of IAPWS-IF97 and is taken from the polynomial fit.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct If97 {
+    /// Pressure \[Pa\]
+    pub pressure: f64,
+}
+
+impl Default for If97 {
+    /// Water at 10 bar, a typical pressure in district heating networks
+    fn default() -> Self {
+        If97 { pressure: 1e6 }
+    }
+}
+
+/// Maximum number of Newton iterations to invert the energy density
+const MAX_ITERATIONS: usize = 50;
+
+impl FluidProperties for If97 {
+    fn density<T: Scalar>(&self, t: T) -> T {
+        density(t, self.pressure)
+    }
+
+    fn specific_heat<T: Scalar>(&self, t: T) -> T {
+        specific_heat(t, self.pressure)
+    }
+
+    fn viscosity<T: Scalar>(&self, e: T) -> T {
+        let t = self.temperature(e);
+
+        viscosity(t, self.pressure) / density(t, self.pressure)
+    }
+
+    fn thermal_conductivity<T: Scalar>(&self, t: T) -> T {
+        thermal_conductivity(t)
+    }
+
+    fn energy_density<T: Scalar>(&self, t: T) -> Result<T, Error> {
+        if !in_region_1(t.value(), self.pressure) {
+            return Err(anyhow!(
+                "temperature {} not allowed: water is not liquid at {} Pa",
+                t.value(),
+                self.pressure
+            ));
+        }
+
+        Ok(energy_density(t, self.pressure))
+    }
+
+    fn temperature<T: Scalar>(&self, e: T) -> T {
+        // Newton's method, the energy density grows with rho c_p
+        let mut t = super::temperature(e.value());
+
+        for _ in 0..MAX_ITERATIONS {
+            let rho = density(t, self.pressure);
+            let residual = energy_density(t, self.pressure) - e.value();
+
+            let dt = residual / (rho * specific_heat(t, self.pressure) * 1e-9);
+            t -= dt;
+
+            if dt.abs() < 1e-10 {
+                break;
+            }
+        }
+
+        // inverse function theorem with the exact slope of the energy density
+        let de = energy_density(Dual::variable(t), self.pressure).derivative;
+        e.apply(t, 1. / de)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, io::Write};
+
+    use approx::assert_relative_eq;
+
+    use super::*;
+
+    const KELVIN_TO_CELSIUS: f64 = -KELVIN;
+
+    #[test]
+    fn region_1_verification_values() {
+        // IAPWS-IF97, table 5
+        for (t, p, v, h, cp) in [
+            (
+                300.,
+                3e6,
+                0.100_215_168e-2,
+                0.115_331_273e3,
+                0.417_301_218e1,
+            ),
+            (
+                300.,
+                80e6,
+                0.971_180_894e-3,
+                0.184_142_828e3,
+                0.401_008_987e1,
+            ),
+            (
+                500.,
+                3e6,
+                0.120_241_800e-2,
+                0.975_542_239e3,
+                0.465_580_682e1,
+            ),
+        ] {
+            let t = t + KELVIN_TO_CELSIUS;
+
+            assert_relative_eq!(1. / density(t, p), v, max_relative = 1e-8);
+            assert_relative_eq!(enthalpy(t, p), h, max_relative = 1e-8);
+            assert_relative_eq!(specific_heat(t, p) * 1e-3, cp, max_relative = 1e-8);
+        }
+    }
+
+    #[test]
+    fn region_4_verification_values() {
+        // IAPWS-IF97, table 35
+        for (t, p) in [
+            (300., 0.353_658_941e-2),
+            (500., 0.263_889_776e1),
+            (600., 0.123_443_146e2),
+        ] {
+            let t = t + KELVIN_TO_CELSIUS;
+
+            assert_relative_eq!(saturation_pressure(t), p * 1e6, max_relative = 1e-8);
+        }
+    }
+
+    #[test]
+    fn viscosity_verification_values() {
+        // IAPWS 2008 viscosity formulation, table 4
+        for (t, rho, mu) in [
+            (298.15, 998., 889.735_100),
+            (298.15, 1200., 1_437.649_467),
+            (373.15, 1000., 307.883_622),
+        ] {
+            let t = t + KELVIN_TO_CELSIUS;
+
+            assert_relative_eq!(
+                viscosity_from_density(t, rho),
+                mu * 1e-6,
+                max_relative = 1e-8
+            );
+        }
+    }
+
+    #[test]
+    fn region_1_bounds() {
+        assert!(in_region_1(20., 1e5));
+        assert!(!in_region_1(120., 1e5));
+        assert!(in_region_1(120., 1e6));
+        assert!(!in_region_1(20., 101e6));
+
+        assert!(If97 { pressure: 1e5 }.energy_density(120.).is_err());
+    }
+
+    #[test]
+    fn temperature_inverts_energy_density() {
+        let water = If97::default();
+
+        for t in [1., 40., 90., 150.] {
+            let e = water
+                .energy_density(t)
+                .expect("could not compute energy density");
+            assert_relative_eq!(water.temperature(e), t, epsilon = 1e-8);
+        }
+    }
+
+    /// A property evaluated with dual numbers and with plain numbers
+    type Property = (fn(Dual, f64) -> Dual, fn(f64, f64) -> f64);
+
+    #[test]
+    fn dual_numbers_match_finite_differences() {
+        let water = If97::default();
+        let dx = 1e-4;
+
+        for t in [10., 60., 120.] {
+            let p = water.pressure;
+            let cases: [Property; 5] = [
+                (density, density),
+                (enthalpy, enthalpy),
+                (specific_heat, specific_heat),
+                (viscosity, viscosity),
+                (energy_density, energy_density),
+            ];
+            for (dual, plain) in cases {
+                assert_relative_eq!(
+                    dual(Dual::variable(t), p).derivative,
+                    (plain(t + dx, p) - plain(t - dx, p)) / (2. * dx),
+                    max_relative = 1e-6
+                );
+            }
+
+            // the enthalpy grows with c_p
+            assert_relative_eq!(
+                enthalpy(Dual::variable(t), p).derivative * 1e3,
+                specific_heat(t, p),
+                max_relative = 1e-10
+            );
+
+            let e = energy_density(t, p);
+            let temperature = water.temperature(Dual::variable(e));
+            assert_relative_eq!(temperature.value, t, epsilon = 1e-8);
+            assert_relative_eq!(
+                temperature.derivative,
+                (water.temperature(e + dx) - water.temperature(e - dx)) / (2. * dx),
+                max_relative = 1e-5
+            );
+        }
+    }
+
+    /// Compares IAPWS-IF97 at 10 bar with the polynomial fits of the water module
+    ///
+    /// Writes the properties of both between 5 °C and 150 °C to `/tmp/if97_comparison`
+    /// and checks the largest relative deviations.
+    #[test]
+    fn generate_comparison_with_fits() {
+        let mut file = fs::File::create("/tmp/if97_comparison").expect("could not open file");
+        file.write_all(b"t rho_if97 rho_fit cp_if97 cp_fit nu_if97 nu_fit e_if97 e_fit\n")
+            .expect("could not write to file");
+
+        let if97 = If97::default();
+        let fit = super::super::Water;
+
+        let mut max_deviations = [0f64; 3];
+        let mut max_energy_density_deviation = 0f64;
+
+        for i in 0..=29 {
+            let t = 5. + i as f64 * 5.;
+
+            let e = fit
+                .energy_density(t)
+                .expect("could not compute energy density");
+            let values = [
+                (if97.density(t), fit.density(t)),
+                (if97.specific_heat(t), fit.specific_heat(t)),
+                (
+                    viscosity(t, if97.pressure) / density(t, if97.pressure),
+                    fit.viscosity(e),
+                ),
+            ];
+
+            for (max_deviation, (reference, value)) in max_deviations.iter_mut().zip(values) {
+                *max_deviation = max_deviation.max(((value - reference) / reference).abs());
+            }
+
+            let [(rho, rho_fit), (cp, cp_fit), (nu, nu_fit)] = values;
+            let (e_if97, e_fit) = (energy_density(t, if97.pressure), e);
+            max_energy_density_deviation = max_energy_density_deviation.max((e_fit - e_if97).abs());
+
+            file.write_all(
+                format!(
+                    "{} {} {} {} {} {} {} {} {}\n",
+                    t, rho, rho_fit, cp, cp_fit, nu, nu_fit, e_if97, e_fit
+                )
+                .as_bytes(),
+            )
+            .expect("could not write to file");
+        }
+
+        let [rho, cp, nu] = max_deviations;
+        assert!(rho < 1e-3, "density deviates by {}", rho);
+        assert!(cp < 2e-3, "heat capacity deviates by {}", cp);
+        assert!(nu < 0.15, "viscosity deviates by {}", nu);
+        assert!(
+            max_energy_density_deviation < 0.01,
+            "energy density deviates by {}",
+            max_energy_density_deviation
+        );
+    }
+}
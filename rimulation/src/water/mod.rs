@@ -0,0 +1,264 @@
+use anyhow::{anyhow, Error};
+
+use crate::{
+    fluid::FluidProperties,
+    polynome::{poly, poly_derivative, roots},
+    scalar::Scalar,
+};
+
+pub mod if97;
+
+/// Constants for temperature polynomial:
+/// T2, T1, T0: Coefficients derived from water property data
+///
+/// The columns `e` and `nu` of `data/water_properties.csv` are computed with [`if97`] at 10 bar
+/// as input for refitting this and the viscosity polynomial with `rimulation fit`,
+/// the constants are not fitted to them.
+const T2: f64 = 59.2453;
+const T1: f64 = 220.536;
+const T0: f64 = 1.93729;
+
+/// Energy density at the vertex of the temperature polynomial,
+/// the temperature increases with the energy density above it
+const E_VERTEX: f64 = -T1 / (2. * T2);
+
+/// Computes the temperature T \[°C\] based on the energy density e \[GJ/m^3\]
+pub fn temperature<T: Scalar>(e: T) -> T {
+    poly(e, &[T0, T1, T2])
+}
+
+/// Computes the energy density e \[GJ/m^3\] based on the temperature T \[°C\]
+///
+/// # Valid Range
+/// * T > -203.2947
+pub fn energy_density<T: Scalar>(t: T) -> Result<T, Error> {
+    // solve t = T2 e^2 + T1 e + T0 on the increasing branch
+    roots(&[T0 - t.value(), T1, T2], (E_VERTEX, f64::INFINITY))
+        .last()
+        .map(|&e| t.apply(e, 1. / poly_derivative(e, &[T0, T1, T2])))
+        .ok_or_else(|| {
+            anyhow!(
+                "temperature {} not allowed: could not invert temperature polynomial",
+                t.value()
+            )
+        })
+}
+
+/// Constants for viscosity polynomial in \[mm^2/s\]:
+/// NU0-NU4: Coefficients derived from water property data
+const NU4: f64 = 11.9285;
+const NU3: f64 = -22.8079;
+const NU2: f64 = 17.6559;
+const NU1: f64 = -7.00355;
+const NU0: f64 = 1.42624;
+
+/// Computes the kinematic viscosity nu \[mm^2/s\] based on the energy density \[GJ/m^3\]
+///
+/// # Valid Range
+/// * Energy density: \[0.02, 0.58\], i.e. T: \[5, 150\]
+///
+/// Deviates from IAPWS-IF97 by up to 12 % at 5 °C and by less than 4 % above 20 °C,
+/// see [`if97`].
+pub fn viscosity<T: Scalar>(e: T) -> T {
+    poly(e, &[NU0, NU1, NU2, NU3, NU4])
+}
+
+/// Constants for density polynomial:
+/// RHO0-RHO3: Coefficients fitted to `data/water_properties.csv` between 0 °C and 150 °C,
+/// regenerate with `rimulation fit data/water_properties.csv --x t --y rho --degree 3 --name RHO`
+const RHO3: f64 = 8.85259e-6;
+const RHO2: f64 = -4.89323e-3;
+const RHO1: f64 = -1.83695e-2;
+const RHO0: f64 = 1000.18;
+
+/// Computes the density rho \[kg/m^3\] based on the temperature T \[°C\]
+///
+/// # Valid Range
+/// * T: \[0, 150\]
+pub fn density<T: Scalar>(t: T) -> T {
+    poly(t, &[RHO0, RHO1, RHO2, RHO3])
+}
+
+/// Constants for specific heat capacity polynomial:
+/// CP0-CP4: Coefficients fitted to `data/water_properties.csv` between 0 °C and 150 °C,
+/// regenerate with `rimulation fit data/water_properties.csv --x t --y cp --degree 4 --name CP`
+const CP4: f64 = 1.01366e-6;
+const CP3: f64 = -3.32633e-4;
+const CP2: f64 = 4.71178e-2;
+const CP1: f64 = -2.40941;
+const CP0: f64 = 4217.85;
+
+/// Computes the specific heat capacity c_p \[J/(kg K)\] based on the temperature T \[°C\]
+///
+/// # Valid Range
+/// * T: \[0, 150\]
+pub fn specific_heat<T: Scalar>(t: T) -> T {
+    poly(t, &[CP0, CP1, CP2, CP3, CP4])
+}
+
+/// Constants for thermal conductivity polynomial:
+/// K0-K3: Coefficients fitted to `data/water_properties.csv` between 0 °C and 150 °C,
+/// regenerate with `rimulation fit data/water_properties.csv --x t --y k --degree 3 --name K`
+const K3: f64 = 1.63084e-8;
+const K2: f64 = -1.17922e-5;
+const K1: f64 = 2.21311e-3;
+const K0: f64 = 0.559784;
+
+/// Computes the thermal conductivity k \[W/(m K)\] based on the temperature T \[°C\]
+///
+/// # Valid Range
+/// * T: \[0, 150\]
+pub fn thermal_conductivity<T: Scalar>(t: T) -> T {
+    poly(t, &[K0, K1, K2, K3])
+}
+
+/// Liquid water described by the polynomial fits of this module
+#[derive(Debug, Default, PartialEq, Clone, Copy)]
+pub struct Water;
+
+impl FluidProperties for Water {
+    fn density<T: Scalar>(&self, t: T) -> T {
+        density(t)
+    }
+
+    fn specific_heat<T: Scalar>(&self, t: T) -> T {
+        specific_heat(t)
+    }
+
+    /// Converts [`viscosity`] from \[mm^2/s\] to the \[m^2/s\] of [`FluidProperties`]
+    fn viscosity<T: Scalar>(&self, e: T) -> T {
+        viscosity(e) * 1e-6
+    }
+
+    fn thermal_conductivity<T: Scalar>(&self, t: T) -> T {
+        thermal_conductivity(t)
+    }
+
+    fn energy_density<T: Scalar>(&self, t: T) -> Result<T, Error> {
+        energy_density(t)
+    }
+
+    fn temperature<T: Scalar>(&self, e: T) -> T {
+        temperature(e)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use approx::assert_relative_eq;
+
+    use super::*;
+    use crate::{
+        polynome::fit,
+        scalar::Dual,
+        types::formats::custom::{read_columns, Column},
+    };
+
+    /// Columns of the argument and the value, range of the argument and fitted coefficients
+    type FittedColumns<'a> = ((usize, usize), (f64, f64), &'a [f64]);
+
+    #[test]
+    fn coefficients_match_property_table() {
+        let columns = read_columns(
+            Path::new("data/water_properties.csv"),
+            &["t", "rho", "cp", "k", "e", "nu"].map(|name| Column::Name(name.to_string())),
+            true,
+        )
+        .expect("could not read water properties");
+
+        let cases: [FittedColumns; 3] = [
+            ((0, 1), (0., 150.), &[RHO0, RHO1, RHO2, RHO3]),
+            ((0, 2), (0., 150.), &[CP0, CP1, CP2, CP3, CP4]),
+            ((0, 3), (0., 150.), &[K0, K1, K2, K3]),
+        ];
+        for ((x, y), range, coefficients) in cases {
+            let fit = fit(&columns[x], &columns[y], coefficients.len() - 1, range)
+                .expect("could not fit water properties");
+
+            for (fitted, constant) in fit.coefficients.iter().zip(coefficients) {
+                assert_relative_eq!(fitted, constant, max_relative = 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn energy_density_inverts_temperature() {
+        for t in [0., 10., 60., 100., 150.] {
+            let e = Water
+                .energy_density(t)
+                .expect("could not compute energy density");
+            assert_relative_eq!(Water.temperature(e), t, epsilon = 1e-10);
+        }
+    }
+
+    #[test]
+    fn energy_density_consistent_with_heat_capacity() {
+        // the energy density grows with rho c_p
+        let (t, dt) = (70., 1e-3);
+        let de = Water
+            .energy_density(t + dt)
+            .expect("could not compute energy density")
+            - Water
+                .energy_density(t)
+                .expect("could not compute energy density");
+
+        assert_relative_eq!(
+            de / dt * 1e9,
+            Water.density(t) * Water.specific_heat(t),
+            max_relative = 0.05
+        );
+    }
+
+    /// A property evaluated with dual numbers and with plain numbers
+    type Property = (fn(Dual) -> Dual, fn(f64) -> f64);
+
+    #[test]
+    fn dual_numbers_match_finite_differences() {
+        let dx = 1e-6;
+        let slope = |f: &dyn Fn(f64) -> f64, x: f64| (f(x + dx) - f(x - dx)) / (2. * dx);
+
+        for t in [10., 60., 120.] {
+            let e = energy_density(t).expect("could not compute energy density");
+            let de = energy_density(Dual::variable(t)).expect("could not compute energy density");
+
+            assert_eq!(de.value, e);
+            assert_relative_eq!(de.derivative, 1. / poly_derivative(e, &[T0, T1, T2]));
+            assert_relative_eq!(temperature(de).derivative, 1., max_relative = 1e-12);
+
+            let cases: [(Property, f64); 4] = [
+                ((density, density), t),
+                ((specific_heat, specific_heat), t),
+                ((thermal_conductivity, thermal_conductivity), t),
+                ((viscosity, viscosity), e),
+            ];
+            for ((dual, plain), x) in cases {
+                assert_relative_eq!(
+                    dual(Dual::variable(x)).derivative,
+                    slope(&plain, x),
+                    max_relative = 1e-6
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn viscosity_converted_to_square_meters_per_second() {
+        // regression: the polynomial in mm^2/s was used as m^2/s, lowering Reynolds numbers by 1e6
+        let e = energy_density(20.).expect("could not compute energy density");
+
+        assert_relative_eq!(viscosity(e), 1.004, max_relative = 0.04);
+        assert_eq!(Water.viscosity(e), viscosity(e) * 1e-6);
+    }
+
+    #[test]
+    fn viscosity_at_reference_temperatures() {
+        for (t, nu) in [(20., 1.004e-6), (60., 0.475e-6), (100., 0.294e-6)] {
+            let e = Water
+                .energy_density(t)
+                .expect("could not compute energy density");
+            assert_relative_eq!(Water.viscosity(e), nu, max_relative = 0.1);
+        }
+    }
+}